@@ -0,0 +1,58 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Daily task tracking, in the style of "don't break the chain".
+//!
+//! This library holds chain's data model, storage and operation handling, which the `chain`
+//! binary is built on. A typical use is to load the task file, apply an operation, and store the
+//! result:
+//!
+//! ```no_run
+//! use chain::{get_tasks_path, TaskKind, TaskListing, TaskOperation};
+//!
+//! let path = get_tasks_path();
+//! let mut tasks = TaskListing::load(&path).unwrap();
+//!
+//! tasks
+//!     .handle_and_store(
+//!         &TaskOperation::Add {
+//!             description: "practice guitar".to_string(),
+//!             kind: TaskKind::Do,
+//!         },
+//!         &path,
+//!     )
+//!     .unwrap();
+//!
+//! for entry in tasks.list_for_today() {
+//!     println!("{} {}", entry.description, entry.completed.is_some());
+//! }
+//! ```
+//!
+//! Nothing in here writes to the terminal; reports like `TaskListing::list_for_today` and
+//! `TaskListing::history_for_range` return data, which is up to the caller to display.
+
+#![warn(missing_docs)]
+
+/// The data model, storage, and operation handling
+pub mod structs;
+
+pub use structs::tasklisting::get_tasks_path;
+pub use structs::{
+    ChainError, Clock, Completion, DayRules, DayStatus, Export, FixedClock, HistoryRow, ImportMode,
+    ImportSummary, Journal, Pause, Progress, Remark, Schedule, StreakStats, Target, Task,
+    TaskDetails, TaskError, TaskKind, TaskListing, TaskOperation, TodayEntry,
+};
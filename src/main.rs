@@ -16,142 +16,1109 @@
  */
 
 use chrono::prelude::*;
-use dirs;
-use ron;
-use ron::de::Error as RonError;
-use std::error::Error;
-use std::fs::create_dir;
-use std::fs::OpenOptions;
+use std::fs::create_dir_all;
 use std::io::prelude::*;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use structopt::StructOpt;
 
-mod structs;
+mod output;
+mod render;
 mod tui;
 
-use structs::{TaskListing, TaskOperation};
+use output::Format;
 
-/// This allows parsing date strings into `Opt`
+use chain::structs::calendar;
+use chain::structs::checkins::{self, Converted, Source};
+use chain::structs::clock::{self, Clock, FixedClock};
+use chain::structs::config::{self, Config, DefaultCommand};
+use chain::structs::target::parse_amount;
+use chain::structs::transfer::{self, Export, ImportMode};
+use chain::structs::{
+    self,
+    error::{EXIT_IO, EXIT_OPERATION},
+};
+use chain::structs::{
+    migration, profile, storage, ChainError, Pause, Profiles, Schedule, Target, TaskError,
+    TaskKind, TaskListing, TaskOperation,
+};
+
+/// A date given on the command line. It's only parsed once the configuration has been loaded, as
+/// that says which format it's in.
 #[derive(Debug)]
-struct LocalDate {
-    date: Date<Local>,
+struct DateArg(String);
+
+impl std::str::FromStr for DateArg {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(DateArg(s.to_string()))
+    }
+}
+
+impl DateArg {
+    /// Parse the date in the configured format (or ISO 8601), exiting with an error if it can't be
+    fn parse(&self, config: &Config) -> NaiveDate {
+        match config.parse_date(&self.0) {
+            Some(date) => date,
+            None => {
+                println!(
+                    "error: couldn't parse date \"{}\" (expected something like {})",
+                    self.0,
+                    config.format_date(NaiveDate::from_ymd(2019, 12, 31))
+                );
+                std::process::exit(EXIT_OPERATION);
+            }
+        }
+    }
+
+    /// Parse the date as with `parse()`, as a date in the local timezone
+    fn parse_local(&self, config: &Config) -> Date<Local> {
+        let date = self.parse(config);
+        match Local.from_local_date(&date).earliest() {
+            Some(date) => date,
+            None => {
+                println!("error: {} doesn't exist in the local timezone", self.0);
+                std::process::exit(EXIT_OPERATION);
+            }
+        }
+    }
 }
 
-impl std::str::FromStr for LocalDate {
-    type Err = chrono::ParseError;
+/// A task given on the command line: its index, ID or slug, or the start of its description. It's
+/// only looked up once the task listing has been loaded.
+#[derive(Debug)]
+struct TaskArg(String);
+
+impl std::str::FromStr for TaskArg {
+    type Err = std::convert::Infallible;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // chrono is complicated
-        let dt: NaiveDate = NaiveDate::parse_from_str(s, "%F").expect("couldn't parse date");
-        let local: Date<Utc> = Date::<Utc>::from_utc(dt, Utc);
-        Ok(LocalDate {
-            date: local.with_timezone(&Local),
-        })
+        Ok(TaskArg(s.to_string()))
+    }
+}
+
+impl TaskArg {
+    /// Find the index of the task. If several tasks match equally well, the user is asked to pick
+    /// one when running in a terminal; otherwise (or if they don't), this exits with an error.
+    fn resolve(&self, tasks: &TaskListing) -> usize {
+        match tasks.find(&self.0) {
+            Ok(index) => index,
+            Err(structs::TaskError::Ambiguous(selector, matches))
+                if std::io::stdin().is_terminal() && std::io::stdout().is_terminal() =>
+            {
+                match pick_task(&selector, &matches) {
+                    Some(index) => index,
+                    None => {
+                        println!("error: no task was chosen");
+                        std::process::exit(EXIT_OPERATION);
+                    }
+                }
+            }
+            Err(e) => {
+                println!("error: {}", e);
+                std::process::exit(EXIT_OPERATION);
+            }
+        }
+    }
+}
+
+/// Get whether the task at `index` is something to do or something to avoid
+fn kind_of(tasks: &TaskListing, index: usize) -> TaskKind {
+    tasks
+        .task_iter()
+        .nth(index)
+        .map_or(TaskKind::Do, |task| task.kind())
+}
+
+/// Ask the user which of the tasks matching `selector` they meant (each given by its index and
+/// description), returning the index of the one they picked
+fn pick_task(selector: &str, matches: &[(usize, String)]) -> Option<usize> {
+    println!("\"{}\" matches more than one task:", selector);
+    for (n, (index, description)) in matches.iter().enumerate() {
+        println!("  {}) {} (task {})", n + 1, description, index);
     }
+    print!("which one? [1-{}, or nothing to cancel] ", matches.len());
+    std::io::stdout().flush().ok()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).ok()?;
+    let choice: usize = answer.trim().parse().ok()?;
+
+    matches.get(choice.checked_sub(1)?).map(|(index, _)| *index)
 }
 
 /// Configuration for `structopt`
 #[derive(StructOpt, Debug)]
 #[structopt(name = "chain", about = "daily task tracking")]
-enum Opt {
+struct Opt {
+    /// Output format: text, json or csv for today, history, list, stats and log; json, csv or ron
+    /// for export and import
+    #[structopt(long = "format", global = true, default_value = "text")]
+    format: Format,
+    /// Directory holding the task database (defaults to `$CHAIN_DATA_DIR`, or `chain` in the
+    /// platform's data directory)
+    #[structopt(long = "data-dir", global = true, parse(from_os_str))]
+    data_dir: Option<PathBuf>,
+    /// Profile to use, each of which has its own task database (see `chain profile`)
+    #[structopt(long = "profile", global = true)]
+    profile: Option<String>,
+    /// Run as if it were this time (e.g. `2019-12-01T23:30:00+09:00`, or `2019-12-01 23:30` in
+    /// the local timezone), to reproduce what happens at a particular moment
+    #[structopt(long = "now", global = true, hidden = true)]
+    now: Option<String>,
+    /// Defaults to `tui` if not given
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+/// Subcommands of `chain`
+#[derive(StructOpt, Debug)]
+enum Command {
     #[structopt(name = "new", about = "create a new task")]
-    New { description: String },
+    New {
+        description: String,
+        /// Create a task to avoid (e.g. "sugar"), whose chain is kept by days without a slip
+        #[structopt(long = "avoid")]
+        avoid: bool,
+    },
     #[structopt(name = "today", about = "view task status for today")]
     Today,
     #[structopt(name = "move", about = "move a task from some position to another")]
-    Move { from: usize, to: usize },
+    Move { from: TaskArg, to: usize },
     #[structopt(name = "done", about = "mark a task as complete for today")]
     Done {
-        index: usize,
+        task: TaskArg,
         remark: Option<String>,
+        /// Mark the task as complete on an earlier day instead (e.g. 2019-12-01)
+        #[structopt(long = "date")]
+        date: Option<DateArg>,
+        /// For tasks with a target, how much was done (amounts recorded on the same day add up)
+        #[structopt(short = "a", long = "amount", parse(try_from_str = parse_amount))]
+        amount: Option<f64>,
+    },
+    #[structopt(
+        name = "slip",
+        about = "log a slip for a task to avoid, breaking its chain"
+    )]
+    Slip {
+        task: TaskArg,
+        remark: Option<String>,
+        /// Log the slip on an earlier day instead (e.g. 2019-12-01)
+        #[structopt(long = "date")]
+        date: Option<DateArg>,
+    },
+    #[structopt(
+        name = "undone",
+        about = "mark a task as not complete for today (or remove the latest slip)"
+    )]
+    Undone {
+        task: TaskArg,
+        /// Mark the task as not complete on an earlier day instead (e.g. 2019-12-01)
+        #[structopt(long = "date")]
+        date: Option<DateArg>,
     },
     #[structopt(name = "history", about = "show history of task completion")]
-    History { start: LocalDate, end: LocalDate },
+    History { start: DateArg, end: DateArg },
     #[structopt(name = "tui", about = "launch text ui")]
     Tui,
     #[structopt(name = "remark", about = "remark on a task")]
-    Remark { index: usize, remark: String },
+    Remark { task: TaskArg, remark: String },
+    #[structopt(
+        name = "schedule",
+        about = "set the days a task is due (daily, mon,wed,fri, every:3 or 3/week)"
+    )]
+    Schedule { task: TaskArg, schedule: Schedule },
+    #[structopt(
+        name = "target",
+        about = "set how much needs doing each day (e.g. 20 pages), or remove it if none is given"
+    )]
+    Target { task: TaskArg, target: Vec<String> },
+    #[structopt(
+        name = "pause",
+        about = "excuse a task (or every task) on some days without breaking its chain, or list \
+                 pauses if neither is given"
+    )]
+    Pause {
+        task: Option<TaskArg>,
+        /// Pause every task instead of one
+        #[structopt(long = "all", conflicts_with = "task")]
+        all: bool,
+        /// First day of the pause (defaults to today)
+        #[structopt(long = "from")]
+        from: Option<DateArg>,
+        /// Last day of the pause (defaults to the first day)
+        #[structopt(long = "to")]
+        to: Option<DateArg>,
+        /// Why the days are excused (e.g. vacation or sick)
+        #[structopt(long = "reason")]
+        reason: Option<String>,
+        /// Remove the pause covering the first day instead
+        #[structopt(long = "remove", conflicts_with_all = &["to", "reason"])]
+        remove: bool,
+    },
+    #[structopt(name = "stats", about = "show streak statistics for one or all tasks")]
+    Stats { task: Option<TaskArg> },
+    #[structopt(name = "edit", about = "change the description of a task")]
+    Edit { task: TaskArg, description: String },
+    #[structopt(
+        name = "slug",
+        about = "give a task a short name to refer to it by, or remove its name if none is given"
+    )]
+    Slug { task: TaskArg, slug: Option<String> },
+    #[structopt(name = "log", about = "show the revision history of a task")]
+    Log { task: TaskArg },
+    #[structopt(name = "list", about = "list all tasks, along with their schedules")]
+    List {
+        /// List archived tasks instead of active ones
+        #[structopt(long = "archived")]
+        archived: bool,
+    },
+    #[structopt(name = "archive", about = "hide a task, keeping its history")]
+    Archive { task: TaskArg },
+    #[structopt(name = "unarchive", about = "restore an archived task")]
+    Unarchive { task: TaskArg },
+    #[structopt(name = "rm", about = "permanently delete a task and its history")]
+    Rm {
+        task: TaskArg,
+        /// Don't ask for confirmation
+        #[structopt(short = "y", long = "yes")]
+        yes: bool,
+    },
+    #[structopt(name = "undo", about = "undo the last change to the task database")]
+    Undo,
+    #[structopt(name = "redo", about = "redo the last change that was undone")]
+    Redo,
+    #[structopt(
+        name = "export",
+        about = "write every task out, in the format given by --format (ron, json, csv or ics)"
+    )]
+    Export {
+        /// Only include completions and remarks made on or after this day (e.g. 2019-12-01)
+        #[structopt(long = "since")]
+        since: Option<DateArg>,
+        /// With --format ics, also add a recurring to-do for each task, following its schedule
+        #[structopt(long = "todos")]
+        todos: bool,
+        /// Write to this file instead of stdout
+        #[structopt(short = "o", long = "output")]
+        output: Option<PathBuf>,
+    },
+    #[structopt(
+        name = "import",
+        about = "import tasks from a file written by `chain export`, or another habit tracker"
+    )]
+    Import {
+        /// File to import (its format is worked out from the extension unless --format is given)
+        file: PathBuf,
+        /// Import check-ins from another habit tracker instead: loop (Loop Habit Tracker's
+        /// Checkmarks.csv) or generic (date,habit,value rows)
+        #[structopt(long = "from")]
+        from: Option<Source>,
+        /// Add new tasks, and whatever's missing from existing ones (the default)
+        #[structopt(long = "merge")]
+        merge: bool,
+        /// Replace every task with the imported ones
+        #[structopt(long = "replace", conflicts_with = "merge")]
+        replace: bool,
+        /// Show what would change without changing anything
+        #[structopt(long = "dry-run")]
+        dry_run: bool,
+        /// Don't ask for confirmation before replacing tasks
+        #[structopt(short = "y", long = "yes")]
+        yes: bool,
+    },
+    #[structopt(name = "db", about = "maintain the task database")]
+    Db(DbCommand),
+    #[structopt(name = "config", about = "view or change settings")]
+    Config(ConfigCommand),
+    #[structopt(
+        name = "profile",
+        about = "manage profiles, each with its own task database"
+    )]
+    Profile(ProfileCommand),
+    #[structopt(name = "restore", about = "restore the task database from a backup")]
+    Restore {
+        /// List available backups instead of restoring one
+        #[structopt(long = "list")]
+        list: bool,
+        /// Backup to restore (a number from `--list`, a file name, or a path)
+        #[structopt(required_unless = "list")]
+        backup: Option<String>,
+    },
+}
+
+/// Subcommands of `chain db`
+#[derive(StructOpt, Debug)]
+enum DbCommand {
+    #[structopt(name = "check", about = "check that the task database can be loaded")]
+    Check,
+    #[structopt(
+        name = "repair",
+        about = "salvage whatever can still be read from a broken task database"
+    )]
+    Repair,
+    #[structopt(
+        name = "migrate",
+        about = "upgrade the task database to the current format (done automatically otherwise)"
+    )]
+    Migrate {
+        /// Show what would change without changing anything
+        #[structopt(long = "dry-run")]
+        dry_run: bool,
+    },
+}
+
+/// Subcommands of `chain config`
+#[derive(StructOpt, Debug)]
+enum ConfigCommand {
+    #[structopt(
+        name = "get",
+        about = "show a setting, or every setting if none is given"
+    )]
+    Get {
+        /// Name of the setting (e.g. date_format or tui.history_days)
+        key: Option<String>,
+    },
+    #[structopt(name = "set", about = "change a setting")]
+    Set {
+        /// Name of the setting (e.g. date_format or tui.history_days)
+        key: String,
+        /// New value
+        value: String,
+    },
+    #[structopt(
+        name = "edit",
+        about = "open the configuration file in $VISUAL or $EDITOR, then check it"
+    )]
+    Edit,
+}
+
+/// Subcommands of `chain profile`
+#[derive(StructOpt, Debug)]
+enum ProfileCommand {
+    #[structopt(name = "list", about = "list profiles")]
+    List,
+    #[structopt(name = "create", about = "create a profile")]
+    Create {
+        /// Name of the profile (letters, numbers, - and _)
+        name: String,
+    },
+    #[structopt(
+        name = "delete",
+        about = "delete a profile along with all of its tasks"
+    )]
+    Delete {
+        /// Name of the profile
+        name: String,
+        /// Don't ask for confirmation
+        #[structopt(short = "y", long = "yes")]
+        yes: bool,
+    },
+}
+
+/// How `chain import` should apply what it reads
+struct ImportOptions {
+    /// Whether to merge into or replace the existing tasks
+    mode: ImportMode,
+    /// Show what would change without changing anything
+    dry_run: bool,
+    /// Don't ask for confirmation before replacing tasks
+    yes: bool,
+}
+
+/// Loads any existing task data from `tasks_path`, returning it as a `TaskListing` for the caller.
+/// If the file doesn't yet exist (or is empty), the listing is empty.
+fn init_task_listing(tasks_path: &Path) -> Result<TaskListing, ChainError> {
+    let tasks = TaskListing::load(tasks_path)?;
+
+    // An empty file is treated as a new listing, but it might be the result of an older version
+    // crashing while writing it
+    let file_is_empty = std::fs::metadata(tasks_path).map_or(true, |metadata| metadata.len() == 0);
+    if file_is_empty && storage::backups(tasks_path).is_ok_and(|backups| !backups.is_empty()) {
+        eprintln!(
+            "warning: {} is empty, but backups exist (see `chain restore --list`)",
+            tasks_path.display()
+        );
+    }
+
+    Ok(tasks)
+}
+
+/// Describe an error to the user, along with what they might do about it
+fn report_error(e: &ChainError) {
+    println!("error: {}", e);
+
+    if e.is_corruption() {
+        println!();
+        println!(
+            "hint: `chain db check` shows what's wrong with the task database, `chain db repair`"
+        );
+        println!(
+            "      salvages whatever can still be read, and `chain restore --list` shows backups"
+        );
+    } else if let ChainError::Task(TaskError::UnsupportedVersion(_)) = e {
+        println!("hint: this task database was written by a newer version of chain");
+    }
+}
+
+/// Handle `chain db` subcommands. These work on the task file directly rather than on a loaded
+/// `TaskListing`, so that they can be used when it can't be loaded. Returns the exit code.
+fn db_command(command: &DbCommand, tasks_path: &Path) -> i32 {
+    let result = match command {
+        DbCommand::Check => check_task_file(tasks_path),
+        DbCommand::Repair => repair_task_file(tasks_path),
+        DbCommand::Migrate { dry_run } => TaskListing::load(tasks_path).and_then(|mut tasks| {
+            if !migrate_task_listing(&mut tasks, tasks_path, *dry_run)? {
+                println!(
+                    "task database is already at format version {}",
+                    migration::CURRENT_VERSION
+                );
+            }
+            Ok(())
+        }),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            report_error(&e);
+            e.exit_code()
+        }
+    }
 }
 
-/// Ensures that the folder for `TASK_FILE` exists, creates it if it doesn't, and similarly loads
-/// up any existing task data, returning it as a `TaskListing` for the caller. If `TASK_FILE`
-/// doesn't yet exist, it initializes it as an empty file.
-fn init_task_listing() -> TaskListing {
-    // Construct a path to the data file used to persist tasks between invocations
-    let tasks_path = structs::tasklisting::get_tasks_path();
-
-    // TODO: note that the file doesn't initially exist (if so), so that later error handling can
-    // know if errors are expected
-
-    // Create task file if it doesn't exist, then open it (note, need write(true) for file
-    // creation)
-    let mut tasks_file = match OpenOptions::new()
-        .create(true)
-        .write(true)
-        .read(true)
-        .open(&tasks_path)
+/// Check that the task file can be loaded, and describe it if it can
+fn check_task_file(tasks_path: &Path) -> Result<(), ChainError> {
+    let tasks = TaskListing::load(tasks_path)?;
+
+    println!(
+        "{}: ok ({} tasks, format version {})",
+        tasks_path.display(),
+        tasks.total_tasks(),
+        tasks.version()
+    );
+    if tasks.version() < migration::CURRENT_VERSION {
+        println!(
+            "it will be upgraded to format version {} the next time it's used",
+            migration::CURRENT_VERSION
+        );
+    }
+
+    Ok(())
+}
+
+/// Salvage whatever can be read from a broken task file, keeping a copy of the original
+fn repair_task_file(tasks_path: &Path) -> Result<(), ChainError> {
+    let io_error = |error| ChainError::Io {
+        path: tasks_path.to_path_buf(),
+        error,
+    };
+
+    let _lock = storage::lock(tasks_path).map_err(io_error)?;
+
+    let contents = match std::fs::read_to_string(tasks_path) {
+        Ok(contents) => contents,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(io_error(e)),
+    };
+
+    match TaskListing::parse(tasks_path, &contents) {
+        Ok(_) => {
+            println!("{}: nothing to repair", tasks_path.display());
+            return Ok(());
+        }
+        // Don't try to repair something which might just be from a newer version of chain
+        Err(ChainError::Task(e)) => return Err(e.into()),
+        Err(_) => (),
+    }
+
+    let (mut tasks, report) = structs::repair::salvage(&contents);
+    migration::upgrade(&mut tasks)?;
+
+    for change in &report {
+        println!("{}", change);
+    }
+    println!("salvaged {} tasks", tasks.total_tasks());
+
+    let original = storage::keep_copy(tasks_path, "broken").map_err(io_error)?;
+    tasks.store(tasks_path.to_path_buf())?;
+
+    println!("original task database saved to {}", original.display());
+
+    Ok(())
+}
+
+/// Print the available backups of the task file, newest first
+fn list_backups(tasks_path: &Path) {
+    let backups = match storage::backups(tasks_path) {
+        Ok(backups) => backups,
+        Err(e) => {
+            println!("error: couldn't read backups: {}", e);
+            return;
+        }
+    };
+
+    if backups.is_empty() {
+        println!("no backups found");
+    }
+
+    for (n, backup) in backups.iter().enumerate() {
+        println!("{:<4}{}", n, backup.file_name().unwrap().to_string_lossy());
+    }
+}
+
+/// Replace the task file with one of its backups (as numbered by `list_backups()`, or by file
+/// name), returning the restored listing. The backup is checked and upgraded just like the task
+/// file is when it's loaded, and the current task file is backed up first, so this can itself be
+/// reversed.
+fn restore_backup(
+    tasks_path: &Path,
+    backup: &str,
+    backup_count: usize,
+) -> Result<TaskListing, String> {
+    let backups = storage::backups(tasks_path).map_err(|e| e.to_string())?;
+
+    let backup_path = match backup.parse::<usize>() {
+        Ok(n) => backups.get(n).cloned(),
+        Err(_) => backups
+            .iter()
+            .find(|path| path.file_name().is_some_and(|name| name == backup))
+            .cloned()
+            .or_else(|| {
+                // Also allow restoring from any other file (e.g. a copy kept by a migration)
+                Some(std::path::PathBuf::from(backup)).filter(|path| path.is_file())
+            }),
+    }
+    .ok_or_else(|| {
+        format!(
+            "no backup named \"{}\" (see `chain restore --list`)",
+            backup
+        )
+    })?;
+
+    let contents = std::fs::read_to_string(&backup_path).map_err(|e| e.to_string())?;
+
+    // Don't replace a working task file with a broken one, or one in a format which is too new
+    let mut tasks = TaskListing::parse(&backup_path, &contents).map_err(|e| e.to_string())?;
+    let original_version = tasks.version();
+    let migrations = migration::upgrade(&mut tasks).map_err(|e| e.to_string())?;
+
+    let _lock = storage::lock(tasks_path).map_err(|e| e.to_string())?;
+    storage::backup(tasks_path, backup_count).map_err(|e| e.to_string())?;
+    if migrations.is_empty() {
+        storage::write_atomic(tasks_path, contents.as_bytes()).map_err(|e| e.to_string())?;
+    } else {
+        tasks
+            .store(tasks_path.to_path_buf())
+            .map_err(|e| e.to_string())?;
+    }
+
+    println!("restored {}", backup_path.display());
+    if !migrations.is_empty() {
+        println!(
+            "upgraded it from format version {} to {}",
+            original_version,
+            migration::CURRENT_VERSION
+        );
+    }
+
+    Ok(tasks)
+}
+
+/// Upgrade `tasks` to the current file format if it was loaded from an older one. Unless this is a
+/// `dry_run`, the original file is copied alongside it before the upgraded listing is stored.
+///
+/// Returns true if any migrations were needed.
+fn migrate_task_listing(
+    tasks: &mut TaskListing,
+    tasks_path: &Path,
+    dry_run: bool,
+) -> Result<bool, ChainError> {
+    let original_version = tasks.version();
+    let migrations = migration::upgrade(tasks)?;
+
+    if migrations.is_empty() {
+        return Ok(false);
+    }
+
+    let verb = if dry_run {
+        "would migrate"
+    } else {
+        "migrating"
+    };
+    for step in &migrations {
+        println!(
+            "{} task database from format version {} to {}: {}",
+            verb, step.from, step.to, step.description
+        );
+        for change in &step.changes {
+            println!("    {}", change);
+        }
+    }
+
+    if dry_run {
+        return Ok(true);
+    }
+
+    let io_error = |error| ChainError::Io {
+        path: tasks_path.to_path_buf(),
+        error,
+    };
+
+    let _lock = storage::lock(tasks_path).map_err(io_error)?;
+
+    let original =
+        storage::keep_copy(tasks_path, &format!("v{}", original_version)).map_err(io_error)?;
+    tasks.store(tasks_path.to_path_buf())?;
+
+    println!("original task database saved to {}", original.display());
+
+    Ok(true)
+}
+
+/// Handle `chain restore`, returning the exit code
+fn restore_command(
+    tasks_path: &Path,
+    list: bool,
+    backup: &Option<String>,
+    config: &Config,
+    clock: Arc<dyn Clock>,
+) -> i32 {
+    if list {
+        list_backups(tasks_path);
+    } else if let Some(backup) = backup {
+        let backup_count = storage::backup_count_override().unwrap_or(config.backups);
+        match restore_backup(tasks_path, backup, backup_count) {
+            Ok(mut restored) => {
+                restored.set_rules(config.day_rules());
+                restored.set_clock(clock);
+                render::today(&restored, &restored.list_for_today())
+            }
+            Err(e) => {
+                println!("error: {}", e);
+                return EXIT_OPERATION;
+            }
+        }
+    }
+
+    0
+}
+
+/// Handle `chain export`, returning the exit code
+fn export_tasks(
+    tasks: &TaskListing,
+    format: Format,
+    since: Option<Date<Local>>,
+    todos: bool,
+    output: Option<PathBuf>,
+) -> i32 {
+    let export = Export::new(tasks, since);
+
+    let serialized = match format {
+        Format::Json => export.to_json(),
+        Format::Csv => export.to_csv(),
+        Format::Ics => calendar::to_ics(&export, tasks.rules(), todos),
+        Format::Text | Format::Ron => export.to_ron(),
+    };
+
+    match output {
+        // Replace the file in one go, so that anything serving it never sees half of it
+        Some(path) => match storage::write_atomic(&path, serialized.as_bytes()) {
+            Ok(()) => 0,
+            Err(error) => {
+                println!("error: {}", ChainError::Io { path, error });
+                EXIT_IO
+            }
+        },
+        None => output_result(std::io::stdout().write_all(serialized.as_bytes())),
+    }
+}
+
+/// Handle `chain import`, returning the exit code
+fn import_tasks(
+    tasks: &mut TaskListing,
+    tasks_path: &Path,
+    file: &Path,
+    format: Format,
+    from: Option<Source>,
+    options: ImportOptions,
+) -> i32 {
+    let ImportOptions { mode, dry_run, yes } = options;
+
+    let contents = match std::fs::read_to_string(file) {
+        Ok(contents) => contents,
+        Err(error) => {
+            let e = ChainError::Io {
+                path: file.to_path_buf(),
+                error,
+            };
+            println!("error: {}", e);
+            return e.exit_code();
+        }
+    };
+
+    // Work out the format from the extension, or failing that, the contents
+    let format = match format {
+        Format::Text => match file.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Format::Json,
+            Some("csv") => Format::Csv,
+            Some("ron") => Format::Ron,
+            _ if contents.trim_start().starts_with('{') => Format::Json,
+            _ if contents.trim_start().starts_with('(') => Format::Ron,
+            _ if contents.trim_start().starts_with("Export") => Format::Ron,
+            _ => Format::Csv,
+        },
+        format => format,
+    };
+
+    // Check-ins from other habit trackers are converted into an export first
+    let converted = match from {
+        Some(source) => checkins::convert(source, file, &contents, tasks.rules(), tasks.now()),
+        None => match format {
+            Format::Json => Export::from_json(file, &contents, tasks.rules()),
+            Format::Csv => Export::from_csv(file, &contents, tasks.rules(), tasks.now()),
+            Format::Text | Format::Ron => Export::from_ron(file, &contents, tasks.rules()),
+            Format::Ics => {
+                println!("error: calendar files can't be imported");
+                return EXIT_OPERATION;
+            }
+        }
+        .map(|export| Converted {
+            export,
+            unmapped: Vec::new(),
+        }),
+    };
+    let Converted { export, unmapped } = match converted {
+        Ok(converted) => converted,
+        Err(e) => {
+            println!("error: {}", e);
+            return e.exit_code();
+        }
+    };
+
+    if !unmapped.is_empty() {
+        println!("couldn't import everything from {}:", file.display());
+        for problem in unmapped.iter() {
+            println!("    {}", problem);
+        }
+        println!();
+    }
+
+    if dry_run {
+        // The listing is only changed in memory, so nothing is stored
+        let summary = transfer::import(tasks, export, mode);
+        render::import_summary(&summary, true);
+        return 0;
+    }
+
+    if mode == ImportMode::Replace
+        && !yes
+        && !confirm(&format!(
+            "replace all {} tasks with the {} imported ones?",
+            tasks.total_tasks(),
+            export.tasks.len()
+        ))
     {
-        Err(e) => panic!(
-            "couldn't open {}: {}; {:?}",
-            tasks_path.to_str().unwrap(),
-            e.description(),
-            e
-        ),
-        Ok(file) => file,
+        return 0;
+    }
+
+    match tasks.modify_and_store(tasks_path, |tasks| transfer::import(tasks, export, mode)) {
+        Ok(summary) => {
+            render::import_summary(&summary, false);
+            0
+        }
+        Err(e) => {
+            let e = ChainError::from(e);
+            report_error(&e);
+            e.exit_code()
+        }
+    }
+}
+
+/// Report a failure to write the output of a command, returning the exit code
+fn output_result(result: std::io::Result<()>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error: couldn't write output: {}", e);
+            EXIT_IO
+        }
+    }
+}
+
+/// Ask the user a yes/no question on the terminal, defaulting to "no"
+fn confirm(question: &str) -> bool {
+    print!("{} [y/N] ", question);
+    if std::io::stdout().flush().is_err() {
+        return false;
+    }
+
+    let mut answer = String::new();
+    match std::io::stdin().read_line(&mut answer) {
+        Ok(_) => {
+            let answer = answer.trim().to_lowercase();
+            answer == "y" || answer == "yes"
+        }
+        Err(_) => false,
+    }
+}
+
+/// Handle `chain config` subcommands, returning the exit code. `config` is the result of loading
+/// the configuration file at `path`, which may have failed.
+fn config_command(command: &ConfigCommand, path: &Path, config: Result<Config, ChainError>) -> i32 {
+    let result = match command {
+        ConfigCommand::Get { key } => config.map(|config| match key {
+            Some(key) => match config.get(key) {
+                Some(value) => println!("{}", value),
+                None => println!(
+                    "error: there's no setting called \"{}\" (settings are {})",
+                    key,
+                    Config::keys().join(", ")
+                ),
+            },
+            None => print!("{}", config.to_toml()),
+        }),
+        ConfigCommand::Set { key, value } => Config::set(path, key, value).map(|_| {
+            println!("{} = {}", key, value);
+        }),
+        ConfigCommand::Edit => return edit_config(path),
     };
 
-    // Load existing tasks data
-    let mut tasks_file_string = String::new();
-    match tasks_file.read_to_string(&mut tasks_file_string) {
-        Err(e) => panic!(
-            "couldn't read {}: {}",
-            tasks_path.to_str().unwrap(),
-            e.description()
-        ),
-        Ok(_) => (),
-    }
-
-    // TODO: explicitly check that a file was just created before silently handling errors
-    let tasks: TaskListing = match ron::de::from_str(&tasks_file_string) {
-        Err(e) => match e {
-            RonError::IoError(s) => panic!("RON deserialization IoError: {}", s),
-            RonError::Message(s) => panic!("RON deserialization Message: {}", s),
-            RonError::Parser(e, pos) => match e {
-                ron::de::ParseError::ExpectedUnit => {
-                    if pos.col == 1 && pos.line == 1 {
-                        // Empty file was just created, we can ignore this
-                        TaskListing::new()
-                    } else {
-                        panic!("RON Parser error at line {}, col {}", pos.line, pos.col);
-                    }
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            println!("error: {}", e);
+            e.exit_code()
+        }
+    }
+}
+
+/// Open the configuration file at `path` in the user's editor (creating it from a template if it
+/// doesn't exist yet), and check it afterwards, offering to edit it again if it's invalid. Returns
+/// the exit code.
+fn edit_config(path: &Path) -> i32 {
+    if !path.exists() {
+        let created = match path.parent() {
+            Some(dir) => create_dir_all(dir),
+            None => Ok(()),
+        }
+        .and_then(|_| std::fs::write(path, config::TEMPLATE));
+
+        if let Err(e) = created {
+            println!("error: couldn't create {}: {}", path.display(), e);
+            return EXIT_IO;
+        }
+    }
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // The editor may be given with arguments (e.g. `code --wait`)
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let args: Vec<&str> = words.collect();
+
+    loop {
+        match std::process::Command::new(program)
+            .args(&args)
+            .arg(path)
+            .status()
+        {
+            Ok(status) if !status.success() => println!("{} exited with {}", program, status),
+            Ok(_) => (),
+            Err(e) => {
+                println!("error: couldn't run {}: {}", program, e);
+                return EXIT_OPERATION;
+            }
+        }
+
+        match Config::load(path) {
+            Ok(_) => return 0,
+            Err(e) => {
+                println!("error: {}", e);
+                if !confirm("edit it again?") {
+                    return e.exit_code();
                 }
-                ron::de::ParseError::ExpectedStruct => {
-                    // No struct was found, file was just created
-                    TaskListing::new()
+            }
+        }
+    }
+}
+
+/// Handle `chain profile` subcommands, returning the exit code
+fn profile_command(
+    command: &ProfileCommand,
+    profiles: &Profiles,
+    current: &str,
+    config: &Config,
+) -> i32 {
+    let result = match command {
+        ProfileCommand::List => match profiles.list() {
+            Ok(names) => {
+                for name in names {
+                    let marker = if name == current { "*" } else { " " };
+                    println!("{} {}", marker, name);
                 }
-                _ => panic!("Unhandled RON parser error: {:?}", e),
-            },
+                Ok(())
+            }
+            Err(error) => Err(ChainError::Io {
+                path: profiles.data_dir().to_path_buf(),
+                error,
+            }),
         },
-        Ok(tasks) => tasks,
+        ProfileCommand::Create { name } => profiles.create(name).map(|_| {
+            println!(
+                "created profile \"{}\" (use it with `chain --profile {}`)",
+                name, name
+            );
+        }),
+        ProfileCommand::Delete { name, yes } => {
+            if !profiles.exists(name)
+                || *yes
+                || !config.confirm
+                || confirm(&format!(
+                    "delete profile \"{}\" along with all of its tasks?",
+                    name
+                ))
+            {
+                profiles.delete(name).map(|_| {
+                    println!("deleted profile \"{}\"", name);
+                })
+            } else {
+                Ok(())
+            }
+        }
     };
 
-    tasks
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            println!("error: {}", e);
+            e.exit_code()
+        }
+    }
 }
 
 fn main() {
+    let opt = Opt::from_args();
+
+    // Settings can be changed even if the configuration file is broken, so that it can be fixed
+    let config_path = config::config_path();
+    let config = Config::load(&config_path);
+    if let Some(Command::Config(command)) = &opt.command {
+        std::process::exit(config_command(command, &config_path, config));
+    }
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            println!("error: {}", e);
+            println!();
+            println!("hint: `chain config edit` opens the configuration file so it can be fixed");
+            std::process::exit(e.exit_code());
+        }
+    };
+
     // If the data folder doesn't exist, create it
-    let mut data_path = dirs::data_dir().unwrap();
-    data_path.push("chain");
+    let profiles = Profiles::new(opt.data_dir.unwrap_or_else(profile::default_data_dir));
+    let data_path = profiles.data_dir();
 
     if !data_path.exists() {
-        println!("{:?} doesn't exist, creating", data_path);
-        match create_dir(&data_path) {
-            Err(e) => panic!("couldn't create {:?}: {:?}", &data_path, e),
-            Ok(_) => println!("created {:?}", &data_path),
+        eprintln!("{:?} doesn't exist, creating", data_path);
+        match create_dir_all(data_path) {
+            Err(e) => {
+                println!("error: couldn't create {}: {}", data_path.display(), e);
+                std::process::exit(EXIT_IO);
+            }
+            Ok(_) => eprintln!("created {:?}", data_path),
+        }
+    }
+
+    let profile = opt
+        .profile
+        .unwrap_or_else(|| profile::DEFAULT_PROFILE.to_string());
+
+    let clock: Arc<dyn Clock> = match opt.now.as_deref().map(clock::parse_datetime) {
+        Some(Some(now)) => Arc::new(FixedClock::new(now)),
+        Some(None) => {
+            println!(
+                "error: couldn't parse time \"{}\" (expected something like 2019-12-01T23:30:00+09:00)",
+                opt.now.unwrap_or_default()
+            );
+            std::process::exit(EXIT_OPERATION);
+        }
+        None => clock::system(),
+    };
+
+    // Default to the configured subcommand (`tui` unless set otherwise) if none is explicitly
+    // specified
+    let format = opt.format;
+    let subcommand = opt.command.unwrap_or(match config.default_command {
+        DefaultCommand::Tui => Command::Tui,
+        DefaultCommand::Today => Command::Today,
+        DefaultCommand::List => Command::List { archived: false },
+        DefaultCommand::Stats => Command::Stats { task: None },
+    });
+
+    // Only reports, exports and imports can be formatted, as everything else is for people to read
+    match subcommand {
+        Command::Export { .. } | Command::Import { .. } => (),
+        Command::Today
+        | Command::History { .. }
+        | Command::List { .. }
+        | Command::Stats { .. }
+        | Command::Log { .. }
+            if format != Format::Ron && format != Format::Ics => {}
+        _ if format != Format::Text => {
+            println!(
+                "error: --format {} doesn't apply to this command",
+                format!("{:?}", format).to_lowercase()
+            );
+            std::process::exit(EXIT_OPERATION);
+        }
+        _ => (),
+    }
+
+    // Profiles are managed without loading any task file
+    if let Command::Profile(command) = &subcommand {
+        std::process::exit(profile_command(command, &profiles, &profile, &config));
+    }
+
+    let tasks_path = match profiles.tasks_path(&profile) {
+        Ok(tasks_path) => tasks_path,
+        Err(e) => {
+            let e = ChainError::from(e);
+            println!("error: {}", e);
+            std::process::exit(e.exit_code());
+        }
+    };
+
+    // These work on the task file directly, so that they can be used when it can't be loaded
+    match &subcommand {
+        Command::Db(command) => std::process::exit(db_command(command, &tasks_path)),
+        Command::Restore { list, backup } => {
+            std::process::exit(restore_command(&tasks_path, *list, backup, &config, clock))
         }
+        _ => (),
     }
 
-    // Initialize the `TaskListing` before parsing command args
-    let mut tasks: TaskListing = init_task_listing();
+    // Initialize the `TaskListing`, upgrading it if it's in an old format
+    let mut tasks: TaskListing = match init_task_listing(&tasks_path).and_then(|mut tasks| {
+        migrate_task_listing(&mut tasks, &tasks_path, false)?;
+        Ok(tasks)
+    }) {
+        Ok(tasks) => tasks,
+        Err(e) => {
+            report_error(&e);
+            std::process::exit(e.exit_code());
+        }
+    };
+    tasks.set_rules(config.day_rules());
+    tasks.set_clock(clock);
+    tasks.set_backup_count(storage::backup_count_override().unwrap_or(config.backups));
+
+    // The process exits with this once everything else is done
+    let mut exit_code = 0;
 
     // We may run a command that indicates a single operation to perform
     let mut operation: Option<TaskOperation> = None;
@@ -159,125 +1126,429 @@ fn main() {
     // We may want to show a user the updated task listing after operation is complete
     let mut list_after = false;
 
-    // Default to the `tui` subcommand if none is explicitly specified
-    let subcommand = if std::env::args().count() == 1 {
-        Opt::Tui
-    } else {
-        Opt::from_args()
-    };
+    // `chain today` shows the listing whether or not anything changed
+    let showing_today = matches!(subcommand, Command::Today);
 
     // Handle manipulation of `TaskListing` according to command line args given
     match subcommand {
         // Create a new task
-        Opt::New { description } => {
-            println!("new task: {}", description);
+        Command::New { description, avoid } => {
+            let kind = if avoid {
+                println!("new task to avoid: {}", description);
+                TaskKind::Avoid
+            } else {
+                println!("new task: {}", description);
+                TaskKind::Do
+            };
 
-            operation = Some(TaskOperation::Add { description });
+            operation = Some(TaskOperation::Add { description, kind });
 
             list_after = true;
         }
         // Display tasks that need to be done today
-        Opt::Today => {
+        Command::Today => {
             // Display header
-            println!();
-            println!("Task status for {}", Local::today().format("%F"));
-            println!();
+            if format == Format::Text {
+                println!();
+                println!(
+                    "Task status for {}",
+                    config.format_date(tasks.today().naive_local())
+                );
+                println!();
+            }
 
             list_after = true;
         }
         // Re-order tasks
-        Opt::Move { from, to } => {
-            operation = Some(TaskOperation::Reorder { from, to });
+        Command::Move { from, to } => {
+            operation = Some(TaskOperation::Reorder {
+                from: from.resolve(&tasks),
+                to,
+            });
 
             list_after = true;
         }
         // Mark a task as done for the day
-        Opt::Done { index, remark } => {
-            operation = Some(TaskOperation::MarkComplete {
-                task_index: index,
-                remark,
+        Command::Done {
+            task,
+            remark,
+            date,
+            amount,
+        } => {
+            let task_index = task.resolve(&tasks);
+
+            if kind_of(&tasks, task_index) == TaskKind::Avoid {
+                println!(
+                    "error: task {} is something to avoid, so use `chain slip` to log a slip",
+                    task_index
+                );
+                exit_code = EXIT_OPERATION;
+            } else {
+                operation = Some(TaskOperation::MarkComplete {
+                    task_index,
+                    remark,
+                    date: date.map(|date| date.parse(&config)),
+                    amount,
+                });
+
+                list_after = true;
+            }
+        }
+        // Log a slip for a task to avoid
+        Command::Slip { task, remark, date } => {
+            let task_index = task.resolve(&tasks);
+
+            if kind_of(&tasks, task_index) == TaskKind::Do {
+                println!(
+                    "error: task {} isn't something to avoid, so use `chain done` to complete it",
+                    task_index
+                );
+                exit_code = EXIT_OPERATION;
+            } else {
+                operation = Some(TaskOperation::MarkComplete {
+                    task_index,
+                    remark,
+                    date: date.map(|date| date.parse(&config)),
+                    amount: None,
+                });
+
+                list_after = true;
+            }
+        }
+        // Remove the completion for a day
+        Command::Undone { task, date } => {
+            operation = Some(TaskOperation::Uncomplete {
+                task_index: task.resolve(&tasks),
+                date: date.map_or_else(|| tasks.today().naive_local(), |date| date.parse(&config)),
             });
 
             list_after = true;
         }
         // Display a history of task completion within some date range
-        Opt::History { start, end } => {
+        Command::History { start, end } => {
             // TODO: this one is an oddball, perhaps each arm should return an enumerated value
             // describing the report to be shown afterward a command is processed
-            let start = start.date;
-            let end = end.date;
+            let start = start.parse_local(&config);
+            let end = end.parse_local(&config);
 
             let mut error = false;
 
             if start > end {
                 error = true;
                 println!("error: start comes after end");
+                exit_code = EXIT_OPERATION;
             }
 
-            if !error {
+            if !error && format == Format::Text {
                 let num_days = end.signed_duration_since(start).num_days() + 1;
                 let s_if_plural = if num_days > 1 { "s" } else { "" };
-                let today_if_end_is_today = if end == Local::today() { "(today)" } else { "" };
+                let today_if_end_is_today = if end == tasks.today() { "(today)" } else { "" };
 
                 println!();
                 println!(
                     "{} day{} of History from {} to {} {}",
                     num_days,
                     s_if_plural,
-                    start.format("%F"),
-                    end.format("%F"),
+                    config.format_date(start.naive_local()),
+                    config.format_date(end.naive_local()),
                     /* need to lop off timezone */ today_if_end_is_today
                 );
                 println!();
+            }
+
+            if !error {
+                let rows = tasks.history_for_range(start, end);
+                exit_code = output_result(output::history(format, &tasks, &rows, start, end));
+            }
+        }
+        // Display streak statistics
+        Command::Stats { task } => {
+            let index = task.map(|task| task.resolve(&tasks));
+
+            if format == Format::Text {
+                println!();
+            }
+
+            match tasks.stats(index) {
+                Ok(stats) => {
+                    exit_code =
+                        output_result(output::stats(format, &tasks, &stats, &config.date_format))
+                }
+                Err(e) => {
+                    println!("error: {}", e);
+                    exit_code = EXIT_OPERATION;
+                }
+            }
+        }
+        // Change the description of a task
+        Command::Edit { task, description } => {
+            operation = Some(TaskOperation::Revise {
+                task_index: task.resolve(&tasks),
+                description,
+            });
+
+            list_after = true;
+        }
+        // Give a task a short name, or remove it
+        Command::Slug { task, slug } => {
+            operation = Some(TaskOperation::SetSlug {
+                task_index: task.resolve(&tasks),
+                slug,
+            });
+        }
+        // Display the revision history of a task
+        Command::Log { task } => {
+            let index = task.resolve(&tasks);
+
+            if format == Format::Text {
+                println!();
+            }
 
-                tasks.history_for_range(start, end);
+            match tasks.log(index) {
+                Ok(revisions) => {
+                    let id = tasks.task_iter().nth(index).unwrap().id();
+                    exit_code = output_result(output::log(
+                        format,
+                        index,
+                        id,
+                        revisions,
+                        &config.date_format,
+                    ))
+                }
+                Err(e) => {
+                    println!("error: {}", e);
+                    exit_code = EXIT_OPERATION;
+                }
+            }
+        }
+        // List active or archived tasks
+        Command::List { archived } => {
+            if format == Format::Text {
+                println!();
             }
+
+            exit_code = output_result(output::list(format, &tasks, archived, &config.date_format));
         }
+        // Hide a task without losing its history
+        Command::Archive { task } => {
+            operation = Some(TaskOperation::Archive {
+                task_index: task.resolve(&tasks),
+            });
+
+            list_after = true;
+        }
+        // Restore an archived task
+        Command::Unarchive { task } => {
+            operation = Some(TaskOperation::Unarchive {
+                task_index: task.resolve(&tasks),
+            });
+
+            list_after = true;
+        }
+        // Permanently delete a task
+        Command::Rm { task, yes } => {
+            let index = task.resolve(&tasks);
+            let description = tasks.task_iter().nth(index).unwrap().description();
+
+            if yes
+                || !config.confirm
+                || confirm(&format!(
+                    "delete \"{}\" and all of its history?",
+                    description
+                ))
+            {
+                operation = Some(TaskOperation::Delete { task_index: index });
+
+                list_after = true;
+            }
+        }
+        // Reverse the last change
+        Command::Undo => {
+            if let Some(entry) = tasks.journal().peek_undo() {
+                println!("undo: {}", entry.operation);
+            }
+
+            operation = Some(TaskOperation::Undo);
+
+            list_after = true;
+        }
+        // Re-apply the last change that was reversed
+        Command::Redo => {
+            if let Some(entry) = tasks.journal().peek_redo() {
+                println!("redo: {}", entry.operation);
+            }
+
+            operation = Some(TaskOperation::Redo);
+
+            list_after = true;
+        }
+        // Copy every task out of the task database
+        Command::Export {
+            since,
+            todos,
+            output,
+        } => {
+            let since = since.map(|since| since.parse_local(&config));
+            exit_code = export_tasks(&tasks, format, since, todos, output);
+        }
+        // Bring in tasks from an export
+        Command::Import {
+            file,
+            from,
+            merge,
+            replace,
+            dry_run,
+            yes,
+        } => {
+            // --merge and --replace conflict, so this only needs to check one of them
+            let mode = if replace && !merge {
+                ImportMode::Replace
+            } else {
+                ImportMode::Merge
+            };
+            let options = ImportOptions {
+                mode,
+                dry_run,
+                yes: yes || !config.confirm,
+            };
+            exit_code = import_tasks(&mut tasks, &tasks_path, &file, format, from, options);
+        }
+        // Handled before the listing was loaded
+        Command::Db(_) | Command::Restore { .. } | Command::Profile(_) | Command::Config(_) => (),
         // Display an interactive TUI
-        Opt::Tui => {
+        Command::Tui => {
             // NOTE: this will run its own loop, and create a stream of TaskOperation which will be
             // handled by TaskListing internally
-            tui::new_loop(&mut tasks);
+            tui::new_loop(&mut tasks, &tasks_path, &config);
         }
         // Add a remark to a task
-        Opt::Remark { index, remark } => {
+        Command::Remark { task, remark } => {
             operation = Some(TaskOperation::AddRemark {
-                task_index: index,
+                task_index: task.resolve(&tasks),
                 remark,
             });
         }
+        // Change how much needs to be recorded each day
+        Command::Target { task, target } => {
+            let target = if target.is_empty() {
+                Ok(None)
+            } else {
+                target.join(" ").parse::<Target>().map(Some)
+            };
+
+            match target {
+                Ok(target) => {
+                    operation = Some(TaskOperation::SetTarget {
+                        task_index: task.resolve(&tasks),
+                        target,
+                    });
+
+                    list_after = true;
+                }
+                Err(e) => {
+                    println!("error: {}", e);
+                    exit_code = EXIT_OPERATION;
+                }
+            }
+        }
+        // Excuse a task (or every task) on a range of days, or remove or list such pauses
+        Command::Pause {
+            task,
+            all,
+            from,
+            to,
+            reason,
+            remove,
+        } => {
+            let task_index = task.map(|task| task.resolve(&tasks));
+            let from_day = from.map_or_else(|| tasks.today(), |date| date.parse_local(&config));
+            let from = from_day.naive_local();
+            let to = to.map_or(from, |date| date.parse(&config));
+
+            if task_index.is_none() && !all {
+                render::pauses(&tasks);
+            } else if remove {
+                let pause = match task_index {
+                    Some(index) => tasks
+                        .task_iter()
+                        .nth(index)
+                        .and_then(|task| task.pause_on(from_day, &[])),
+                    None => tasks.pauses().iter().find(|pause| pause.covers(from_day)),
+                };
+
+                match pause {
+                    Some(pause) => {
+                        operation = Some(TaskOperation::RemovePause {
+                            task_index,
+                            pause: pause.clone(),
+                        });
+
+                        list_after = true;
+                    }
+                    None => {
+                        match task_index {
+                            Some(index) => {
+                                println!("error: task {} isn't paused on {}", index, from)
+                            }
+                            None => println!("error: there's no pause for every task on {}", from),
+                        }
+                        exit_code = EXIT_OPERATION;
+                    }
+                }
+            } else {
+                match Pause::new(from, to, reason) {
+                    Ok(pause) => {
+                        operation = Some(TaskOperation::AddPause { task_index, pause });
+
+                        list_after = true;
+                    }
+                    Err(e) => {
+                        println!("error: {}", e);
+                        exit_code = EXIT_OPERATION;
+                    }
+                }
+            }
+        }
+        // Change the days on which a task is due
+        Command::Schedule { task, schedule } => {
+            operation = Some(TaskOperation::SetSchedule {
+                task_index: task.resolve(&tasks),
+                schedule,
+            });
+
+            list_after = true;
+        }
     };
 
     // Handle an operation if the command wasn't merely to display information
     let mut modifications_made: bool = false;
     if let Some(op) = operation {
-        match tasks.handle_operation(&op) {
+        match tasks.handle_and_store(&op, &tasks_path) {
             Err(e) => {
-                println!("error: {}", e.description());
+                let e = ChainError::from(e);
+                report_error(&e);
+                exit_code = e.exit_code();
             }
             Ok(_) => modifications_made = true,
         }
     }
 
     if list_after {
-        match Opt::from_args() {
-            Opt::Today => {
-                // Always causes listing to be displayed
-                tasks.list_for_today();
-            }
-            Opt::Done { .. } | Opt::Move { .. } | Opt::New { .. } if modifications_made => {
-                // Only display the listing if something changed
-                tasks.list_for_today();
-            }
-            _ => (),
+        if showing_today {
+            // Always causes listing to be displayed
+            exit_code = output_result(output::today(format, &tasks, &tasks.list_for_today()));
+        } else if modifications_made {
+            // Only display the listing if something changed
+            render::today(&tasks, &tasks.list_for_today());
         }
     }
 
-    match tasks.store(structs::tasklisting::get_tasks_path()) {
-        Err(e) => println!("\nfailed to store tasks: {}", e.description()),
-        Ok(_) if modifications_made => println!("\ntask database successfully updated"),
-        Ok(_) => (),
+    if modifications_made {
+        println!("\ntask database successfully updated");
     }
 
     // All done!
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
 }
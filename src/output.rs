@@ -0,0 +1,1010 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use chrono::prelude::*;
+use serde::Serialize;
+use std::io;
+
+use chain::{
+    DayRules, DayStatus, HistoryRow, Pause, Progress, Remark, StreakStats, Task, TaskDetails,
+    TaskKind, TaskListing, TodayEntry,
+};
+
+use super::render;
+
+/// Version of the JSON and CSV output schema, which is included in JSON output. Fields may be
+/// added without changing it, but it changes whenever a field is removed, renamed, or changes
+/// meaning (and the README says what changed).
+///
+/// Version 2 added task kinds, targets and pauses, along with the statuses they bring.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// How the output of read-only commands is formatted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Columns for people to read (the default)
+    Text,
+    /// A single JSON object
+    Json,
+    /// A header row followed by one row per record
+    Csv,
+    /// chain's own format (only for export and import)
+    Ron,
+    /// An iCalendar file (only for export)
+    Ics,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            "ron" => Ok(Format::Ron),
+            "ics" => Ok(Format::Ics),
+            _ => Err(format!(
+                "unknown format \"{}\" (use text, json, csv, ron or ics)",
+                s
+            )),
+        }
+    }
+}
+
+/// Format a timestamp in the local timezone, to the second (e.g. `2019-12-01T08:30:00+01:00`)
+fn timestamp<Tz: TimeZone>(datetime: &DateTime<Tz>) -> String {
+    datetime
+        .with_timezone(&Local)
+        .to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+/// Name of a `DayStatus` in the schema
+fn status_name(status: DayStatus) -> &'static str {
+    match status {
+        DayStatus::Completed => "completed",
+        DayStatus::Partial => "partial",
+        DayStatus::Pending => "pending",
+        DayStatus::Missed => "missed",
+        DayStatus::Slipped => "slipped",
+        DayStatus::Excused => "excused",
+        DayStatus::NotDue => "not_due",
+        DayStatus::NotCreated => "not_created",
+        DayStatus::Future => "future",
+    }
+}
+
+/// ID of a task, for output
+fn id_of(task: Option<&Task>) -> String {
+    task.map_or_else(String::new, |task| task.id().to_string())
+}
+
+/// Slug of a task (if it has one), for output
+fn slug_of(task: Option<&Task>) -> Option<String> {
+    task.and_then(|task| task.slug().map(str::to_string))
+}
+
+/// Kind of a task (`do` or `avoid`), for output
+fn kind_of(task: Option<&Task>) -> String {
+    task.map_or(TaskKind::Do, Task::kind).to_string()
+}
+
+/// Remarks are joined into a single CSV field with this
+const CSV_REMARK_SEPARATOR: &str = "; ";
+
+/// A remark on a task
+#[derive(Serialize)]
+struct RemarkRecord {
+    /// When the remark was made
+    datetime: String,
+    /// The remark itself
+    remark: String,
+}
+
+impl RemarkRecord {
+    fn new(remark: &Remark) -> RemarkRecord {
+        RemarkRecord {
+            datetime: timestamp(&remark.datetime()),
+            remark: remark.remark().to_string(),
+        }
+    }
+}
+
+/// Every remark made on some task on `date`
+fn remarks_on(task: Option<&Task>, date: Date<Local>, rules: &DayRules) -> Vec<RemarkRecord> {
+    task.map(|task| task.remarks_on(date, rules))
+        .unwrap_or_default()
+        .into_iter()
+        .map(RemarkRecord::new)
+        .collect()
+}
+
+/// Join remarks into a single CSV field
+fn join_remarks(remarks: &[RemarkRecord]) -> String {
+    remarks
+        .iter()
+        .map(|remark| remark.remark.as_str())
+        .collect::<Vec<&str>>()
+        .join(CSV_REMARK_SEPARATOR)
+}
+
+/// Progress toward a task's daily target
+#[derive(Serialize)]
+struct ProgressRecord {
+    amount: f64,
+    target: f64,
+    unit: String,
+}
+
+impl ProgressRecord {
+    fn new(progress: &Progress) -> ProgressRecord {
+        ProgressRecord {
+            amount: progress.amount,
+            target: progress.target.amount(),
+            unit: progress.target.unit().to_string(),
+        }
+    }
+}
+
+/// Days on which a task is excused
+#[derive(Serialize)]
+struct PauseRecord {
+    from: String,
+    to: String,
+    reason: Option<String>,
+}
+
+impl PauseRecord {
+    fn new(pause: &Pause) -> PauseRecord {
+        PauseRecord {
+            from: pause.from().format("%F").to_string(),
+            to: pause.to().format("%F").to_string(),
+            reason: pause.reason().map(str::to_string),
+        }
+    }
+}
+
+/// A task's status for today
+#[derive(Serialize)]
+struct TodayRecord {
+    index: usize,
+    id: String,
+    slug: Option<String>,
+    description: String,
+    kind: String,
+    due: bool,
+    completed_at: Option<String>,
+    slipped_at: Option<String>,
+    streak: u32,
+    next: bool,
+    progress: Option<ProgressRecord>,
+    pause: Option<PauseRecord>,
+    remarks: Vec<RemarkRecord>,
+}
+
+/// The output of `chain today`
+#[derive(Serialize)]
+struct TodayOutput {
+    schema: u32,
+    date: String,
+    tasks: Vec<TodayRecord>,
+}
+
+/// A row of `chain today` CSV output
+#[derive(Serialize)]
+struct TodayCsvRow<'a> {
+    index: usize,
+    id: &'a str,
+    slug: Option<&'a str>,
+    description: &'a str,
+    kind: &'a str,
+    due: bool,
+    completed_at: Option<&'a str>,
+    slipped_at: Option<&'a str>,
+    streak: u32,
+    next: bool,
+    amount: Option<f64>,
+    target: Option<f64>,
+    unit: Option<&'a str>,
+    pause_from: Option<&'a str>,
+    pause_to: Option<&'a str>,
+    pause_reason: Option<&'a str>,
+    remarks: String,
+}
+
+/// A task's status on a single day
+#[derive(Serialize)]
+struct DayRecord {
+    date: String,
+    status: &'static str,
+    progress: Option<ProgressRecord>,
+    remarks: Vec<RemarkRecord>,
+}
+
+/// A task's status on each day of a range
+#[derive(Serialize)]
+struct HistoryRecord {
+    index: usize,
+    id: String,
+    slug: Option<String>,
+    description: String,
+    kind: String,
+    days: Vec<DayRecord>,
+}
+
+/// The output of `chain history`
+#[derive(Serialize)]
+struct HistoryOutput {
+    schema: u32,
+    start: String,
+    end: String,
+    tasks: Vec<HistoryRecord>,
+}
+
+/// A row of `chain history` CSV output (one per task per day)
+#[derive(Serialize)]
+struct HistoryCsvRow<'a> {
+    index: usize,
+    id: &'a str,
+    slug: Option<&'a str>,
+    description: &'a str,
+    kind: &'a str,
+    date: &'a str,
+    status: &'static str,
+    amount: Option<f64>,
+    target: Option<f64>,
+    unit: Option<&'a str>,
+    remarks: String,
+}
+
+/// A task and its schedule
+#[derive(Serialize)]
+struct ListRecord {
+    index: usize,
+    id: String,
+    slug: Option<String>,
+    description: String,
+    kind: String,
+    schedule: String,
+    target: Option<String>,
+    archived_at: Option<String>,
+}
+
+/// The output of `chain list`
+#[derive(Serialize)]
+struct ListOutput {
+    schema: u32,
+    tasks: Vec<ListRecord>,
+}
+
+/// An unbroken run of completions
+#[derive(Serialize)]
+struct StreakRecord {
+    start: String,
+    end: String,
+    length: u32,
+}
+
+/// Streak statistics for a task
+#[derive(Serialize)]
+struct StatsRecord {
+    index: usize,
+    id: String,
+    slug: Option<String>,
+    description: String,
+    kind: String,
+    schedule: String,
+    current_streak: u32,
+    longest_streak: u32,
+    completion_rate: Option<f64>,
+    due_days: u32,
+    completed_days: u32,
+    streaks: Vec<StreakRecord>,
+}
+
+/// The output of `chain stats`
+#[derive(Serialize)]
+struct StatsOutput {
+    schema: u32,
+    tasks: Vec<StatsRecord>,
+}
+
+/// A row of `chain stats` CSV output (the individual streaks are left out)
+#[derive(Serialize)]
+struct StatsCsvRow<'a> {
+    index: usize,
+    id: &'a str,
+    slug: Option<&'a str>,
+    description: &'a str,
+    kind: &'a str,
+    schedule: &'a str,
+    current_streak: u32,
+    longest_streak: u32,
+    completion_rate: Option<f64>,
+    due_days: u32,
+    completed_days: u32,
+}
+
+/// A revision of a task's details
+#[derive(Serialize)]
+struct RevisionRecord {
+    revision_id: u64,
+    revised: String,
+    description: String,
+    schedule: String,
+    target: Option<String>,
+}
+
+/// The output of `chain log`
+#[derive(Serialize)]
+struct LogOutput {
+    schema: u32,
+    index: usize,
+    id: String,
+    revisions: Vec<RevisionRecord>,
+}
+
+/// Write a value to `out` as JSON
+fn write_json<T: Serialize>(mut out: impl io::Write, value: &T) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut out, value)?;
+    out.write_all(b"\n")
+}
+
+/// Write rows to `out` as CSV, with a header row
+fn write_csv<T: Serialize>(
+    out: impl io::Write,
+    rows: impl IntoIterator<Item = T>,
+) -> io::Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    for row in rows {
+        writer.serialize(row).map_err(io::Error::other)?;
+    }
+    writer.flush()
+}
+
+/// Output the status of tasks for today
+pub fn today(format: Format, tasks: &TaskListing, entries: &[TodayEntry]) -> io::Result<()> {
+    if format == Format::Text {
+        render::today(tasks, entries);
+        return Ok(());
+    }
+
+    write_today(io::stdout().lock(), format, tasks, entries)
+}
+
+/// Write the status of tasks for today to `out`, as JSON or CSV
+fn write_today(
+    out: impl io::Write,
+    format: Format,
+    tasks: &TaskListing,
+    entries: &[TodayEntry],
+) -> io::Result<()> {
+    let today = tasks.today();
+    let records: Vec<TodayRecord> = entries
+        .iter()
+        .map(|entry| {
+            let task = tasks.task_iter().nth(entry.task_index);
+
+            TodayRecord {
+                index: entry.task_index,
+                id: id_of(task),
+                slug: slug_of(task),
+                description: entry.description.clone(),
+                kind: entry.kind.to_string(),
+                due: entry.due,
+                completed_at: entry.completed.as_ref().map(timestamp),
+                slipped_at: entry.slipped.as_ref().map(timestamp),
+                streak: entry.streak,
+                next: entry.next,
+                progress: entry.progress.as_ref().map(ProgressRecord::new),
+                pause: entry.pause.as_ref().map(PauseRecord::new),
+                remarks: remarks_on(task, today, tasks.rules()),
+            }
+        })
+        .collect();
+
+    match format {
+        Format::Json => write_json(
+            out,
+            &TodayOutput {
+                schema: SCHEMA_VERSION,
+                date: today.format("%F").to_string(),
+                tasks: records,
+            },
+        ),
+        _ => write_csv(
+            out,
+            records.iter().map(|record| TodayCsvRow {
+                index: record.index,
+                id: &record.id,
+                slug: record.slug.as_deref(),
+                description: &record.description,
+                kind: &record.kind,
+                due: record.due,
+                completed_at: record.completed_at.as_deref(),
+                slipped_at: record.slipped_at.as_deref(),
+                streak: record.streak,
+                next: record.next,
+                amount: record.progress.as_ref().map(|p| p.amount),
+                target: record.progress.as_ref().map(|p| p.target),
+                unit: record.progress.as_ref().map(|p| p.unit.as_str()),
+                pause_from: record.pause.as_ref().map(|p| p.from.as_str()),
+                pause_to: record.pause.as_ref().map(|p| p.to.as_str()),
+                pause_reason: record.pause.as_ref().and_then(|p| p.reason.as_deref()),
+                remarks: join_remarks(&record.remarks),
+            }),
+        ),
+    }
+}
+
+/// Output the history of task completion from `start` to `end`
+pub fn history(
+    format: Format,
+    tasks: &TaskListing,
+    rows: &[HistoryRow],
+    start: Date<Local>,
+    end: Date<Local>,
+) -> io::Result<()> {
+    if format == Format::Text {
+        render::history(tasks, rows);
+        return Ok(());
+    }
+
+    write_history(io::stdout().lock(), format, tasks, rows, start, end)
+}
+
+/// Write the history of task completion from `start` to `end` to `out`, as JSON or CSV
+fn write_history(
+    out: impl io::Write,
+    format: Format,
+    tasks: &TaskListing,
+    rows: &[HistoryRow],
+    start: Date<Local>,
+    end: Date<Local>,
+) -> io::Result<()> {
+    let records: Vec<HistoryRecord> = rows
+        .iter()
+        .map(|row| {
+            let task = tasks.task_iter().nth(row.task_index);
+
+            HistoryRecord {
+                index: row.task_index,
+                id: id_of(task),
+                slug: slug_of(task),
+                description: row.description.clone(),
+                kind: kind_of(task),
+                days: row
+                    .days
+                    .iter()
+                    .map(|(date, status)| DayRecord {
+                        date: date.format("%F").to_string(),
+                        status: status_name(*status),
+                        progress: task
+                            .and_then(|task| task.progress_on(*date, tasks.rules()))
+                            .as_ref()
+                            .map(ProgressRecord::new),
+                        remarks: remarks_on(task, *date, tasks.rules()),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    match format {
+        Format::Json => write_json(
+            out,
+            &HistoryOutput {
+                schema: SCHEMA_VERSION,
+                start: start.format("%F").to_string(),
+                end: end.format("%F").to_string(),
+                tasks: records,
+            },
+        ),
+        _ => write_csv(
+            out,
+            records.iter().flat_map(|record| {
+                record.days.iter().map(move |day| HistoryCsvRow {
+                    index: record.index,
+                    id: &record.id,
+                    slug: record.slug.as_deref(),
+                    description: &record.description,
+                    kind: &record.kind,
+                    date: &day.date,
+                    status: day.status,
+                    amount: day.progress.as_ref().map(|p| p.amount),
+                    target: day.progress.as_ref().map(|p| p.target),
+                    unit: day.progress.as_ref().map(|p| p.unit.as_str()),
+                    remarks: join_remarks(&day.remarks),
+                })
+            }),
+        ),
+    }
+}
+
+/// Output either active or archived tasks, along with their schedules. Text output shows dates
+/// in `date_format`.
+pub fn list(
+    format: Format,
+    tasks: &TaskListing,
+    archived: bool,
+    date_format: &str,
+) -> io::Result<()> {
+    if format == Format::Text {
+        render::list(tasks, archived, date_format);
+        return Ok(());
+    }
+
+    write_list(io::stdout().lock(), format, tasks, archived)
+}
+
+/// Write either active or archived tasks to `out`, as JSON or CSV
+fn write_list(
+    out: impl io::Write,
+    format: Format,
+    tasks: &TaskListing,
+    archived: bool,
+) -> io::Result<()> {
+    let records: Vec<ListRecord> = tasks
+        .task_iter()
+        .enumerate()
+        .filter(|(_, task)| task.is_archived() == archived)
+        .map(|(index, task)| ListRecord {
+            index,
+            id: task.id().to_string(),
+            slug: task.slug().map(str::to_string),
+            description: task.description().clone(),
+            kind: task.kind().to_string(),
+            schedule: task.schedule().to_string(),
+            target: task.target().map(|target| target.to_string()),
+            archived_at: task.archived().as_ref().map(timestamp),
+        })
+        .collect();
+
+    match format {
+        Format::Json => write_json(
+            out,
+            &ListOutput {
+                schema: SCHEMA_VERSION,
+                tasks: records,
+            },
+        ),
+        _ => write_csv(out, records),
+    }
+}
+
+/// Output streak statistics for each task. Text output shows dates in `date_format`.
+pub fn stats(
+    format: Format,
+    tasks: &TaskListing,
+    stats: &[(usize, StreakStats)],
+    date_format: &str,
+) -> io::Result<()> {
+    if format == Format::Text {
+        render::stats(tasks, stats, date_format);
+        return Ok(());
+    }
+
+    write_stats(io::stdout().lock(), format, tasks, stats)
+}
+
+/// Write streak statistics for each task to `out`, as JSON or CSV
+fn write_stats(
+    out: impl io::Write,
+    format: Format,
+    tasks: &TaskListing,
+    stats: &[(usize, StreakStats)],
+) -> io::Result<()> {
+    let records: Vec<StatsRecord> = stats
+        .iter()
+        .filter_map(|(index, stats)| {
+            let task = tasks.task_iter().nth(*index)?;
+
+            Some(StatsRecord {
+                index: *index,
+                id: task.id().to_string(),
+                slug: task.slug().map(str::to_string),
+                description: task.description().clone(),
+                kind: task.kind().to_string(),
+                schedule: task.schedule().to_string(),
+                current_streak: stats.current_length(),
+                longest_streak: stats.longest().map_or(0, |longest| longest.length),
+                completion_rate: stats.completion_rate(),
+                due_days: stats.due_days,
+                completed_days: stats.completed_days,
+                streaks: stats
+                    .streaks
+                    .iter()
+                    .map(|streak| StreakRecord {
+                        start: streak.start.format("%F").to_string(),
+                        end: streak.end.format("%F").to_string(),
+                        length: streak.length,
+                    })
+                    .collect(),
+            })
+        })
+        .collect();
+
+    match format {
+        Format::Json => write_json(
+            out,
+            &StatsOutput {
+                schema: SCHEMA_VERSION,
+                tasks: records,
+            },
+        ),
+        _ => write_csv(
+            out,
+            records.iter().map(|record| StatsCsvRow {
+                index: record.index,
+                id: &record.id,
+                slug: record.slug.as_deref(),
+                description: &record.description,
+                kind: &record.kind,
+                schedule: &record.schedule,
+                current_streak: record.current_streak,
+                longest_streak: record.longest_streak,
+                completion_rate: record.completion_rate,
+                due_days: record.due_days,
+                completed_days: record.completed_days,
+            }),
+        ),
+    }
+}
+
+/// Output every revision made to a task's details, oldest first. Text output shows dates in
+/// `date_format`.
+pub fn log<'a>(
+    format: Format,
+    index: usize,
+    id: &str,
+    revisions: impl Iterator<Item = &'a TaskDetails>,
+    date_format: &str,
+) -> io::Result<()> {
+    if format == Format::Text {
+        render::log(revisions, date_format);
+        return Ok(());
+    }
+
+    write_log(io::stdout().lock(), format, index, id, revisions)
+}
+
+/// Write every revision made to a task's details to `out`, as JSON or CSV
+fn write_log<'a>(
+    out: impl io::Write,
+    format: Format,
+    index: usize,
+    id: &str,
+    revisions: impl Iterator<Item = &'a TaskDetails>,
+) -> io::Result<()> {
+    let records: Vec<RevisionRecord> = revisions
+        .map(|details| RevisionRecord {
+            revision_id: details.revision_id(),
+            revised: timestamp(&details.revised()),
+            description: details.description().clone(),
+            schedule: details.schedule().to_string(),
+            target: details.target().map(|target| target.to_string()),
+        })
+        .collect();
+
+    match format {
+        Format::Json => write_json(
+            out,
+            &LogOutput {
+                schema: SCHEMA_VERSION,
+                index,
+                id: id.to_string(),
+                revisions: records,
+            },
+        ),
+        _ => write_csv(out, records),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        write_history, write_list, write_log, write_stats, write_today, Format, SCHEMA_VERSION,
+    };
+    use chain::{FixedClock, Pause, TaskKind, TaskListing, TaskOperation};
+    use chrono::prelude::*;
+    use serde_json::Value;
+    use std::io;
+    use std::sync::Arc;
+
+    // These tests pin the name and shape of every field. If one of them has to change because a
+    // field was removed, renamed or changed meaning, bump `SCHEMA_VERSION` and say what changed in
+    // the README.
+
+    /// A listing created yesterday, with a task completed yesterday which has since been given a
+    /// target (with an amount and a remark recorded today), a task to avoid which slipped today,
+    /// and a task paused today
+    fn listing() -> TaskListing {
+        let now = Local.ymd(2019, 12, 1).and_hms(12, 0, 0);
+        let clock = Arc::new(FixedClock::new(now.with_timezone(&now.offset().fix())));
+        let mut list = TaskListing::new();
+        list.set_clock(clock.clone());
+
+        for (description, kind) in [
+            ("read", TaskKind::Do),
+            ("sugar", TaskKind::Avoid),
+            ("gym", TaskKind::Do),
+        ] {
+            let add = TaskOperation::Add {
+                description: description.to_string(),
+                kind,
+            };
+            list.handle_operation(&add).unwrap();
+        }
+        list.handle_operation(&TaskOperation::MarkComplete {
+            task_index: 0,
+            remark: None,
+            date: None,
+            amount: None,
+        })
+        .unwrap();
+
+        clock.advance(chrono::Duration::days(1));
+        let today = list.today().naive_local();
+        for op in [
+            TaskOperation::SetTarget {
+                task_index: 0,
+                target: Some("20 pages".parse().unwrap()),
+            },
+            TaskOperation::MarkComplete {
+                task_index: 0,
+                remark: Some("chapter 3".to_string()),
+                date: None,
+                amount: Some(5.0),
+            },
+            TaskOperation::MarkComplete {
+                task_index: 1,
+                remark: None,
+                date: None,
+                amount: None,
+            },
+            TaskOperation::AddPause {
+                task_index: Some(2),
+                pause: Pause::new(today, today, Some("sick".to_string())).unwrap(),
+            },
+        ]
+        .iter()
+        {
+            list.handle_operation(op).unwrap();
+        }
+
+        list
+    }
+
+    /// Write something as JSON and parse it back
+    fn json(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> Value {
+        let mut out = Vec::new();
+        write(&mut out).unwrap();
+        serde_json::from_slice(&out).unwrap()
+    }
+
+    /// Write something as CSV, returning its header row
+    fn csv_header(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> String {
+        let mut out = Vec::new();
+        write(&mut out).unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .next()
+            .unwrap()
+            .to_string()
+    }
+
+    /// Names of an object's fields, in alphabetical order
+    fn fields(value: &Value) -> Vec<&str> {
+        let mut fields: Vec<&str> = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        fields.sort_unstable();
+        fields
+    }
+
+    /// The record of the task with the given description in `tasks`
+    fn task<'a>(output: &'a Value, description: &str) -> &'a Value {
+        output["tasks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|task| task["description"] == description)
+            .unwrap()
+    }
+
+    #[test]
+    fn today_schema() {
+        let list = listing();
+        let entries = list.list_for_today();
+
+        let output = json(|out| write_today(out, Format::Json, &list, &entries));
+        assert_eq!(fields(&output), ["date", "schema", "tasks"]);
+        assert_eq!(output["schema"], SCHEMA_VERSION);
+        assert_eq!(output["date"], "2019-12-02");
+
+        let read = task(&output, "read");
+        assert_eq!(
+            fields(read),
+            [
+                "completed_at",
+                "description",
+                "due",
+                "id",
+                "index",
+                "kind",
+                "next",
+                "pause",
+                "progress",
+                "remarks",
+                "slipped_at",
+                "slug",
+                "streak",
+            ]
+        );
+        assert_eq!(read["kind"], "do");
+        assert_eq!(fields(&read["progress"]), ["amount", "target", "unit"]);
+        assert_eq!(fields(&read["remarks"][0]), ["datetime", "remark"]);
+        assert_eq!(read["remarks"][0]["remark"], "chapter 3");
+
+        let sugar = task(&output, "sugar");
+        assert_eq!(sugar["kind"], "avoid");
+        assert!(sugar["slipped_at"].is_string());
+        assert!(sugar["completed_at"].is_null());
+
+        let gym = task(&output, "gym");
+        assert_eq!(fields(&gym["pause"]), ["from", "reason", "to"]);
+        assert_eq!(gym["due"], false);
+
+        assert_eq!(
+            csv_header(|out| write_today(out, Format::Csv, &list, &entries)),
+            "index,id,slug,description,kind,due,completed_at,slipped_at,streak,next,amount,target,\
+             unit,pause_from,pause_to,pause_reason,remarks"
+        );
+    }
+
+    #[test]
+    fn history_schema() {
+        let list = listing();
+        let today = list.today();
+        let rows = list.history_for_range(today.pred(), today);
+        let history = |out: &mut Vec<u8>, format| {
+            write_history(out, format, &list, &rows, today.pred(), today)
+        };
+
+        let output = json(|out| history(out, Format::Json));
+        assert_eq!(fields(&output), ["end", "schema", "start", "tasks"]);
+        assert_eq!(output["schema"], SCHEMA_VERSION);
+
+        let read = task(&output, "read");
+        assert_eq!(
+            fields(read),
+            ["days", "description", "id", "index", "kind", "slug"]
+        );
+        assert_eq!(
+            fields(&read["days"][1]),
+            ["date", "progress", "remarks", "status"]
+        );
+        let statuses = |description| -> Vec<Value> {
+            let days = task(&output, description)["days"].as_array().unwrap();
+            days.iter().map(|day| day["status"].clone()).collect()
+        };
+        assert_eq!(statuses("read"), ["completed", "partial"]);
+        assert_eq!(statuses("sugar"), ["completed", "slipped"]);
+        assert_eq!(statuses("gym"), ["missed", "excused"]);
+
+        assert_eq!(
+            csv_header(|out| history(out, Format::Csv)),
+            "index,id,slug,description,kind,date,status,amount,target,unit,remarks"
+        );
+    }
+
+    #[test]
+    fn list_schema() {
+        let list = listing();
+
+        let output = json(|out| write_list(out, Format::Json, &list, false));
+        assert_eq!(fields(&output), ["schema", "tasks"]);
+        assert_eq!(output["schema"], SCHEMA_VERSION);
+
+        let read = task(&output, "read");
+        assert_eq!(
+            fields(read),
+            [
+                "archived_at",
+                "description",
+                "id",
+                "index",
+                "kind",
+                "schedule",
+                "slug",
+                "target",
+            ]
+        );
+        assert_eq!(read["target"], "20 pages");
+
+        assert_eq!(
+            csv_header(|out| write_list(out, Format::Csv, &list, false)),
+            "index,id,slug,description,kind,schedule,target,archived_at"
+        );
+    }
+
+    #[test]
+    fn stats_schema() {
+        let list = listing();
+        let stats = list.stats(None).unwrap();
+
+        let output = json(|out| write_stats(out, Format::Json, &list, &stats));
+        assert_eq!(fields(&output), ["schema", "tasks"]);
+        assert_eq!(output["schema"], SCHEMA_VERSION);
+
+        let read = task(&output, "read");
+        assert_eq!(
+            fields(read),
+            [
+                "completed_days",
+                "completion_rate",
+                "current_streak",
+                "description",
+                "due_days",
+                "id",
+                "index",
+                "kind",
+                "longest_streak",
+                "schedule",
+                "slug",
+                "streaks",
+            ]
+        );
+        assert_eq!(fields(&read["streaks"][0]), ["end", "length", "start"]);
+
+        assert_eq!(
+            csv_header(|out| write_stats(out, Format::Csv, &list, &stats)),
+            "index,id,slug,description,kind,schedule,current_streak,longest_streak,\
+             completion_rate,due_days,completed_days"
+        );
+    }
+
+    #[test]
+    fn log_schema() {
+        let list = listing();
+        let id = list.task_iter().next().unwrap().id();
+
+        let output = json(|out| write_log(out, Format::Json, 0, id, list.log(0).unwrap()));
+        assert_eq!(fields(&output), ["id", "index", "revisions", "schema"]);
+        assert_eq!(output["schema"], SCHEMA_VERSION);
+        assert_eq!(
+            fields(&output["revisions"][1]),
+            [
+                "description",
+                "revised",
+                "revision_id",
+                "schedule",
+                "target"
+            ]
+        );
+        assert_eq!(output["revisions"][1]["target"], "20 pages");
+
+        assert_eq!(
+            csv_header(|out| write_log(out, Format::Csv, 0, id, list.log(0).unwrap())),
+            "revision_id,revised,description,schedule,target"
+        );
+    }
+}
@@ -0,0 +1,412 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use chrono::prelude::*;
+
+use chain::structs::transfer::Additions;
+use chain::{
+    DayStatus, HistoryRow, ImportSummary, Pause, StreakStats, Task, TaskDetails, TaskKind,
+    TaskListing, TodayEntry,
+};
+
+/// Number of columns each field is padded to a multiple of
+const INDENT_SIZE: usize = 4;
+
+/// Width of a column holding `len` characters, padded to the next multiple of `INDENT_SIZE`
+fn padded_width(len: usize) -> usize {
+    ((len / INDENT_SIZE) + 1) * INDENT_SIZE
+}
+
+/// Width of the column holding task indexes
+fn id_width(tasks: &TaskListing) -> usize {
+    padded_width(tasks.total_tasks().to_string().chars().count())
+}
+
+/// The name a task can be referred to by: its slug if it has one, otherwise its ID
+fn name_of(task: &Task) -> &str {
+    task.slug().unwrap_or_else(|| task.id())
+}
+
+/// Display the status of tasks for today (with completion status, times, and note on which task
+/// is next)
+pub fn today(tasks: &TaskListing, entries: &[TodayEntry]) {
+    let description_width = padded_width(
+        entries
+            .iter()
+            .map(|entry| entry.description.chars().count())
+            .max()
+            .unwrap_or(0),
+    );
+    let id_width = id_width(tasks);
+
+    for entry in entries {
+        // Check box
+        if entry.completed.is_some() {
+            print!("{:<4}", "[x]");
+        } else if entry.slipped.is_some() {
+            // Something which was meant to be avoided happened
+            print!("{:<4}", "[!]");
+        } else if entry.due {
+            print!("{:<4}", "[ ]")
+        } else {
+            // Not due today, so skipping it won't break the chain
+            print!("{:<4}", "[-]")
+        }
+
+        // Numeric ID (used for "order" subcommand)
+        print!("{:<width$}", entry.task_index, width = id_width);
+
+        // Description
+        print!("{:<width$}", entry.description, width = description_width);
+
+        // Completion time (or when a task to avoid slipped)
+        let timestamp_display: String = match entry.completed.or(entry.slipped) {
+            Some(datetime) => format!("{:02}:{:02}", datetime.hour(), datetime.minute()),
+            None => "--:--".into(),
+        };
+        print!(
+            "{:<width$}",
+            timestamp_display,
+            width = padded_width(timestamp_display.chars().count())
+        );
+
+        // Current streak
+        let streak_display = format!("{}d", entry.streak);
+        print!(
+            "{:<width$}",
+            streak_display,
+            width = padded_width(streak_display.chars().count())
+        );
+
+        // Progress toward the target, for tasks which have one
+        if let Some(progress) = &entry.progress {
+            print!("{}  ", progress);
+        }
+
+        // Paused tasks aren't due, so say why
+        match entry.pause.as_ref().and_then(Pause::reason) {
+            Some(reason) => print!("(paused: {})  ", reason),
+            None if entry.pause.is_some() => print!("(paused)  "),
+            None => {}
+        }
+
+        // Tasks to avoid are kept by not doing them
+        if entry.kind == TaskKind::Avoid {
+            print!("(avoid)");
+        }
+
+        // Mark next task to be done
+        if entry.next {
+            print!("(next)");
+        }
+
+        println!();
+    }
+}
+
+/// Display either active or archived tasks, along with their schedules (and when they were
+/// archived, in `date_format`)
+pub fn list(tasks: &TaskListing, archived: bool, date_format: &str) {
+    let id_width = id_width(tasks);
+    let name_width = padded_width(
+        tasks
+            .task_iter()
+            .map(|task| name_of(task).chars().count())
+            .max()
+            .unwrap_or(0),
+    );
+    let any_targets = tasks.task_iter().any(|task| task.target().is_some());
+    let any_avoided = tasks.task_iter().any(|task| task.kind() == TaskKind::Avoid);
+
+    for (n, task) in tasks.task_iter().enumerate() {
+        if task.is_archived() != archived {
+            continue;
+        }
+
+        print!("{:<width$}", n, width = id_width);
+        print!("{:<width$}", name_of(task), width = name_width);
+        // Only shown if some task is something to avoid
+        if any_avoided {
+            print!(
+                "{:<8}",
+                if task.kind() == TaskKind::Avoid {
+                    "avoid"
+                } else {
+                    ""
+                }
+            );
+        }
+        print!("{:<12}", task.schedule().to_string());
+        // Only shown if some task has a target
+        if any_targets {
+            print!(
+                "{:<12}",
+                task.target().map_or_else(String::new, |t| t.to_string())
+            );
+        }
+        if let Some(date) = task.archived_on(tasks.rules()) {
+            print!("{:<12}", date.format(date_format).to_string());
+        }
+        println!("{}", task.description());
+    }
+}
+
+/// Display a grid of task completion, one row per task and one column per day
+pub fn history(tasks: &TaskListing, rows: &[HistoryRow]) {
+    let description_width = padded_width(
+        rows.iter()
+            .map(|row| row.description.chars().count())
+            .max()
+            .unwrap_or(0),
+    );
+    let id_width = id_width(tasks);
+
+    // Print header row
+    // TODO: break up rendering into multiple rows once this gets to the point that we care
+    // about terminal width
+    print!("{}{}", " ".repeat(id_width), " ".repeat(description_width));
+    if let Some(row) = rows.first() {
+        for (date, _) in row.days.iter() {
+            print!(
+                "{:<width$}",
+                format!("|{:<02}", date.day()),
+                width = INDENT_SIZE + 1
+            );
+        }
+    }
+    println!();
+
+    for row in rows {
+        // Numeric ID
+        print!("{:<width$}", row.task_index, width = id_width);
+        // Description
+        print!("{:<width$}", row.description, width = description_width);
+
+        let mut any_done = false;
+        let mut last_complete = false;
+        for (n, (_, status)) in row.days.iter().enumerate() {
+            print!("|");
+
+            if *status == DayStatus::Future {
+                print!("    ");
+                continue;
+            }
+
+            match status {
+                DayStatus::Completed => {
+                    print!("o");
+                    last_complete = true;
+                    any_done = true;
+                }
+                DayStatus::Pending => print!("?"),
+                // A task to avoid slipped, breaking the chain
+                DayStatus::Slipped => {
+                    print!("!");
+                    last_complete = false;
+                }
+                // Some, but not all, of the target was met
+                DayStatus::Partial => print!("~"),
+                // Excused (e.g. on vacation), which neither adds to nor breaks the chain
+                DayStatus::Excused => print!("-"),
+                // Only the day which broke the chain is marked
+                DayStatus::Missed if any_done && last_complete => {
+                    print!("x");
+                    last_complete = false;
+                }
+                _ => print!(" "),
+            }
+
+            // Link completions, up to today
+            if let Some((_, next_status)) = row.days.get(n + 1) {
+                if last_complete && *next_status != DayStatus::Future {
+                    print!("-o-");
+                } else {
+                    print!("   ");
+                }
+            }
+        }
+        println!();
+    }
+}
+
+/// Display streak statistics for each task, with dates in `date_format`
+pub fn stats(tasks: &TaskListing, stats: &[(usize, StreakStats)], date_format: &str) {
+    for (n, stats) in stats {
+        let task = match tasks.task_iter().nth(*n) {
+            Some(task) => task,
+            None => continue,
+        };
+
+        println!("{:<4}{}", n, task.description());
+        println!("    schedule:        {}", task.schedule());
+        println!("    current streak:  {}d", stats.current_length());
+        match stats.longest() {
+            Some(longest) => println!(
+                "    longest streak:  {}d ({} to {})",
+                longest.length,
+                longest.start.format(date_format),
+                longest.end.format(date_format)
+            ),
+            None => println!("    longest streak:  0d"),
+        }
+        match stats.completion_rate() {
+            Some(rate) => println!(
+                "    completion rate: {:.0}% ({} of {} due days)",
+                rate * 100.0,
+                stats.completed_days,
+                stats.due_days
+            ),
+            None => println!("    completion rate: --"),
+        }
+        if !stats.streaks.is_empty() {
+            println!("    streaks:");
+            for streak in stats.streaks.iter().rev() {
+                println!(
+                    "        {} to {}  {}d",
+                    streak.start.format(date_format),
+                    streak.end.format(date_format),
+                    streak.length
+                );
+            }
+        }
+        println!();
+    }
+}
+
+/// Display every pause, those for every task (marked `all`) first, then those of each task
+pub fn pauses(tasks: &TaskListing) {
+    let rows: Vec<(String, &Pause, String)> = tasks
+        .pauses()
+        .iter()
+        .map(|pause| ("all".to_string(), pause, "(every task)".to_string()))
+        .chain(tasks.task_iter().enumerate().flat_map(|(n, task)| {
+            task.pauses()
+                .iter()
+                .map(move |pause| (n.to_string(), pause, task.description().clone()))
+        }))
+        .collect();
+
+    if rows.is_empty() {
+        println!("no pauses");
+        return;
+    }
+
+    let id_width = padded_width("all".len()).max(id_width(tasks));
+    let pause_width = padded_width(
+        rows.iter()
+            .map(|(_, pause, _)| pause.to_string().chars().count())
+            .max()
+            .unwrap_or(0),
+    );
+
+    for (id, pause, description) in rows {
+        print!("{:<width$}", id, width = id_width);
+        print!("{:<width$}", pause.to_string(), width = pause_width);
+        println!("{}", description);
+    }
+}
+
+/// Display every revision made to a task's details, oldest first, with dates in `date_format`
+pub fn log<'a>(revisions: impl Iterator<Item = &'a TaskDetails>, date_format: &str) {
+    let datetime_format = format!("{} %R", date_format);
+    let revisions: Vec<&TaskDetails> = revisions.collect();
+    // Only shown if some revision has a target
+    let any_targets = revisions.iter().any(|details| details.target().is_some());
+
+    for details in revisions {
+        let target = match details.target() {
+            Some(target) => format!("{:<12}", target.to_string()),
+            None if any_targets => " ".repeat(12),
+            None => String::new(),
+        };
+
+        println!(
+            "{:<4}{}  {:<12}{}{}",
+            details.revision_id(),
+            details
+                .revised()
+                .with_timezone(&Local)
+                .format(&datetime_format),
+            details.schedule().to_string(),
+            target,
+            details.description()
+        );
+    }
+}
+
+/// Describe what was added to a task by an import (e.g. "2 completions, 1 remark")
+fn describe_additions(additions: &Additions) -> String {
+    let counts = [
+        (additions.revisions, "revision"),
+        (additions.completions, "completion"),
+        (additions.remarks, "remark"),
+        (additions.pauses, "pause"),
+    ];
+
+    counts
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, noun)| format!("{} {}{}", count, noun, if *count == 1 { "" } else { "s" }))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Display what an import changed (or would change, for a dry run)
+pub fn import_summary(summary: &ImportSummary, dry_run: bool) {
+    let (add, remove) = if dry_run {
+        ("would add", "would remove")
+    } else {
+        ("added", "removed")
+    };
+
+    if summary.removed > 0 {
+        println!("{} {} existing tasks", remove, summary.removed);
+    }
+    if !summary.added.is_empty() {
+        println!("{} {} tasks:", add, summary.added.len());
+        for description in summary.added.iter() {
+            println!("    + {}", description);
+        }
+    }
+    if !summary.merged.is_empty() {
+        println!("{} to {} existing tasks:", add, summary.merged.len());
+        for (description, additions) in summary.merged.iter() {
+            println!("    {}: {}", description, describe_additions(additions));
+        }
+    }
+    if summary.pauses_removed > 0 {
+        println!(
+            "{} {} existing pauses for every task",
+            remove, summary.pauses_removed
+        );
+    }
+    if summary.pauses_added > 0 {
+        println!("{} {} pauses for every task", add, summary.pauses_added);
+    }
+    if summary.unchanged > 0 {
+        println!("{} tasks were already up to date", summary.unchanged);
+    }
+    if summary.duplicates > 0 {
+        println!(
+            "{} tasks were left out, as they were repeated in the import",
+            summary.duplicates
+        );
+    }
+    if summary.is_empty() {
+        println!("nothing to import");
+    }
+}
@@ -15,11 +15,80 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
+/// The full set of tasks, and the operations on it
 pub mod tasklisting;
 pub use tasklisting::TaskListing;
 
+/// Operations which change a `TaskListing`
 pub mod taskoperation;
 pub use taskoperation::TaskOperation;
 
+/// A single task, along with its completions, remarks and revisions
 pub mod task;
-pub use task::{Completion as TaskCompletion, Task, TaskDetails, TaskError};
+pub use task::{Completion, Remark, Task, TaskDetails, TaskError, TaskKind};
+
+/// Which days a task is due on
+pub mod schedule;
+pub use schedule::Schedule;
+
+/// The record of applied operations used for undo and redo
+pub mod journal;
+pub use journal::Journal;
+
+/// Streak statistics for a task
+pub mod streak;
+pub use streak::StreakStats;
+
+/// Writing the task file safely, locking it, and keeping backups
+pub mod storage;
+
+/// Upgrading task files written in older versions of the format
+pub mod migration;
+
+/// Errors from loading the task file or handling operations, and exit codes for them
+pub mod error;
+pub use error::ChainError;
+
+/// Recovering tasks from a task file which can't be loaded
+pub mod repair;
+
+/// Data behind chain's reports (e.g. today's tasks, or the history of completions)
+pub mod report;
+pub use report::{DayStatus, HistoryRow, TodayEntry};
+
+/// Exporting tasks, and importing them again
+pub mod transfer;
+pub use transfer::{Export, ImportMode, ImportSummary};
+
+/// Importing check-ins from other habit trackers
+pub mod checkins;
+
+/// Exporting completions as iCalendar events
+pub mod calendar;
+
+/// Where task files are kept, and named profiles with a task file each
+pub mod profile;
+pub use profile::{ProfileError, Profiles};
+
+/// Preferences for how days are grouped
+pub mod dayrules;
+pub use dayrules::DayRules;
+
+/// The user's configuration file
+pub mod config;
+pub use config::Config;
+
+/// Where the current time comes from
+pub mod clock;
+pub use clock::{Clock, FixedClock};
+
+/// Matching what the user typed against task descriptions
+pub mod fuzzy;
+
+/// Daily targets for tasks which are measured (e.g. pages read)
+pub mod target;
+pub use target::{Progress, Target};
+
+/// Days on which tasks are excused (e.g. vacations)
+pub mod pause;
+pub use pause::Pause;
@@ -0,0 +1,325 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use chrono::prelude::*;
+use std::collections::BTreeMap;
+
+use super::dayrules::local_date;
+use super::DayRules;
+use super::Export;
+use super::Schedule;
+use super::Task;
+use super::TaskKind;
+
+/// Lines in iCalendar files shouldn't be longer than this many bytes (RFC 5545, section 3.1)
+const MAX_LINE_LENGTH: usize = 75;
+
+/// Escape text for use in an iCalendar property value
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => (),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Add a content line, folding it onto continuation lines if it's too long
+fn push_line(ics: &mut String, line: &str) {
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            // Continuation lines start with a space, which counts towards their length
+            ics.push_str("\r\n ");
+            length = 1;
+        }
+        ics.push(c);
+        length += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+/// Format a date as an all-day iCalendar value
+fn ics_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+/// Format a timestamp as an iCalendar UTC date-time
+fn ics_datetime(datetime: DateTime<Utc>) -> String {
+    datetime.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// An identifier for a task which stays the same between exports, so that calendar apps update
+/// entries instead of duplicating them
+fn task_uid(task: &Task) -> String {
+    format!("task-{}", task.id())
+}
+
+/// Abbreviation of a weekday, as used in `RRULE`
+fn rrule_day(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+/// The recurrence rule for a schedule. Tasks due some number of times per week can be done on any
+/// days, which `RRULE` can't express, so they recur weekly.
+fn rrule(schedule: &Schedule) -> String {
+    match schedule {
+        Schedule::Daily => "FREQ=DAILY".to_string(),
+        Schedule::Weekdays(days) => format!(
+            "FREQ=WEEKLY;BYDAY={}",
+            days.iter()
+                .map(|day| rrule_day(*day))
+                .collect::<Vec<&str>>()
+                .join(",")
+        ),
+        Schedule::EveryNDays(n) => format!("FREQ=DAILY;INTERVAL={}", n),
+        Schedule::TimesPerWeek(_) => "FREQ=WEEKLY".to_string(),
+    }
+}
+
+/// Write an export as an iCalendar file, with an all-day event for each day a task was completed
+/// (or slipped, for tasks to avoid) on, as `rules` say when days start. The remarks made on
+/// completion are the event's description. If `todos` is true, each active task to do also gets a
+/// recurring to-do following its schedule.
+pub fn to_ics(export: &Export, rules: &DayRules, todos: bool) -> String {
+    let mut ics = String::new();
+    let stamp = ics_datetime(export.exported);
+
+    push_line(&mut ics, "BEGIN:VCALENDAR");
+    push_line(&mut ics, "VERSION:2.0");
+    push_line(
+        &mut ics,
+        &format!("PRODID:-//chain//chain {}//EN", env!("CARGO_PKG_VERSION")),
+    );
+    push_line(&mut ics, "CALSCALE:GREGORIAN");
+
+    for task in export.tasks.iter() {
+        let uid = task_uid(task);
+
+        // Several completions (amounts toward a target, or slips) can count for the same day, but
+        // each day only gets one event
+        let mut days: BTreeMap<NaiveDate, Vec<&str>> = BTreeMap::new();
+        for completion in task.completions() {
+            days.entry(completion.day(rules))
+                .or_default()
+                .extend(completion.remark().map(|remark| remark.remark()));
+        }
+
+        for (day, remarks) in days {
+            let date = local_date(day);
+            let description = task
+                .details_on(date, rules)
+                .map_or(task.description(), |details| details.description());
+
+            push_line(&mut ics, "BEGIN:VEVENT");
+            push_line(
+                &mut ics,
+                &format!("UID:{}-{}@chain", uid, ics_date(date.naive_local())),
+            );
+            push_line(&mut ics, &format!("DTSTAMP:{}", stamp));
+            push_line(
+                &mut ics,
+                &format!("DTSTART;VALUE=DATE:{}", ics_date(date.naive_local())),
+            );
+            push_line(
+                &mut ics,
+                &format!("DTEND;VALUE=DATE:{}", ics_date(date.succ().naive_local())),
+            );
+            // Events for tasks to avoid are slips, rather than completions
+            let summary = match task.kind() {
+                TaskKind::Do => escape(description),
+                TaskKind::Avoid => format!("slipped: {}", escape(description)),
+            };
+            push_line(&mut ics, &format!("SUMMARY:{}", summary));
+            if !remarks.is_empty() {
+                push_line(
+                    &mut ics,
+                    &format!("DESCRIPTION:{}", escape(&remarks.join("\n"))),
+                );
+            }
+            push_line(&mut ics, "TRANSP:TRANSPARENT");
+            push_line(&mut ics, "END:VEVENT");
+        }
+
+        // There's nothing to do for tasks to avoid
+        if todos && task.kind() == TaskKind::Do && !task.is_archived() && task.created().is_some() {
+            push_line(&mut ics, "BEGIN:VTODO");
+            push_line(&mut ics, &format!("UID:{}@chain", uid));
+            push_line(&mut ics, &format!("DTSTAMP:{}", stamp));
+            push_line(
+                &mut ics,
+                &format!(
+                    "DTSTART;VALUE=DATE:{}",
+                    ics_date(task.created_on(rules).naive_local())
+                ),
+            );
+            push_line(&mut ics, &format!("SUMMARY:{}", escape(task.description())));
+            if let Schedule::TimesPerWeek(n) = task.schedule() {
+                push_line(&mut ics, &format!("DESCRIPTION:due {} times per week", n));
+            }
+            push_line(&mut ics, &format!("RRULE:{}", rrule(task.schedule())));
+            push_line(&mut ics, "END:VTODO");
+        }
+    }
+
+    push_line(&mut ics, "END:VCALENDAR");
+
+    ics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{push_line, to_ics};
+    use crate::structs::{Export, FixedClock, TaskKind, TaskListing, TaskOperation};
+    use chrono::prelude::*;
+    use std::sync::Arc;
+
+    fn now() -> DateTime<FixedOffset> {
+        let now = Local.ymd(2019, 12, 1).and_hms(12, 0, 0);
+        now.with_timezone(&now.offset().fix())
+    }
+
+    /// An empty listing whose clock is stopped at local noon on 2019-12-01
+    fn listing() -> TaskListing {
+        let mut list = TaskListing::new();
+        list.set_clock(Arc::new(FixedClock::new(now())));
+        list
+    }
+
+    #[test]
+    fn completions_become_events() {
+        let mut list = listing();
+        for op in [
+            TaskOperation::Add {
+                description: "read; then write".to_string(),
+                kind: TaskKind::Do,
+            },
+            TaskOperation::MarkComplete {
+                task_index: 0,
+                remark: Some("chapter 3, finally".to_string()),
+                date: None,
+                amount: None,
+            },
+            TaskOperation::SetSchedule {
+                task_index: 0,
+                schedule: "mon,fri".parse().unwrap(),
+            },
+        ]
+        .iter()
+        {
+            list.handle_operation(op).unwrap();
+        }
+
+        let ics = to_ics(&Export::new(&list, None), list.rules(), true);
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 1);
+        let stamp = now().with_timezone(&Utc).format("%Y%m%dT%H%M%SZ");
+        assert!(ics.contains(&format!("DTSTAMP:{}\r\n", stamp)));
+        assert!(ics.contains("SUMMARY:read\\; then write\r\n"));
+        assert!(ics.contains("DESCRIPTION:chapter 3\\, finally\r\n"));
+        assert!(ics.contains("RRULE:FREQ=WEEKLY;BYDAY=MO,FR\r\n"));
+
+        let without_todos = to_ics(&Export::new(&list, None), list.rules(), false);
+        assert!(!without_todos.contains("VTODO"));
+    }
+
+    #[test]
+    fn slips_become_one_event_per_day() {
+        let mut list = listing();
+        for op in [
+            TaskOperation::Add {
+                description: "sugar".to_string(),
+                kind: TaskKind::Avoid,
+            },
+            TaskOperation::MarkComplete {
+                task_index: 0,
+                remark: Some("cake".to_string()),
+                date: None,
+                amount: None,
+            },
+            TaskOperation::MarkComplete {
+                task_index: 0,
+                remark: Some("more cake".to_string()),
+                date: None,
+                amount: None,
+            },
+        ]
+        .iter()
+        {
+            list.handle_operation(op).unwrap();
+        }
+
+        let ics = to_ics(&Export::new(&list, None), list.rules(), true);
+
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 1);
+        assert!(ics.contains("SUMMARY:slipped: sugar\r\n"));
+        assert!(ics.contains("DESCRIPTION:cake\\nmore cake\r\n"));
+        assert!(!ics.contains("VTODO"));
+    }
+
+    #[test]
+    fn tasks_created_together_have_their_own_uids() {
+        let mut list = listing();
+        for description in ["read", "write"] {
+            list.handle_operation(&TaskOperation::Add {
+                description: description.to_string(),
+                kind: TaskKind::Do,
+            })
+            .unwrap();
+        }
+
+        let ics = to_ics(&Export::new(&list, None), list.rules(), true);
+
+        let uids: Vec<&str> = ics
+            .split("\r\n")
+            .filter(|line| line.starts_with("UID:"))
+            .collect();
+        assert_eq!(uids.len(), 2);
+        assert_ne!(uids[0], uids[1]);
+        for task in list.task_iter() {
+            assert!(ics.contains(&format!("UID:task-{}@chain\r\n", task.id())));
+        }
+    }
+
+    #[test]
+    fn long_lines_are_folded() {
+        let mut ics = String::new();
+        push_line(&mut ics, &format!("SUMMARY:{}", "é".repeat(100)));
+
+        for line in ics.split("\r\n") {
+            assert!(line.len() <= 75);
+        }
+        assert_eq!(ics.replace("\r\n ", "").trim_end().len(), 8 + 200);
+    }
+}
@@ -0,0 +1,564 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use chrono::prelude::*;
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::dayrules::local_date;
+use super::tasklisting::id_from_hash;
+use super::transfer::EXPORT_VERSION;
+use super::ChainError;
+use super::DayRules;
+use super::Export;
+use super::Schedule;
+use super::{Completion, Task, TaskDetails};
+
+/// Where check-ins being imported came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    /// `Checkmarks.csv` from a Loop Habit Tracker export, which has a `Date` column followed by a
+    /// column for each habit
+    Loop,
+    /// A CSV file with `date,habit,value` rows (a header row is optional)
+    Generic,
+}
+
+impl std::str::FromStr for Source {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "loop" => Ok(Source::Loop),
+            "generic" => Ok(Source::Generic),
+            _ => Err(format!("unknown source \"{}\" (use loop or generic)", s)),
+        }
+    }
+}
+
+/// Check-ins converted into tasks, along with a description of everything that couldn't be
+#[derive(Debug)]
+pub struct Converted {
+    /// A task for each habit, which can be imported like any other export
+    pub export: Export,
+    /// Everything which couldn't be mapped onto a task or completion
+    pub unmapped: Vec<String>,
+}
+
+/// Loop Habit Tracker marks habits checked by the user with this
+const LOOP_YES_MANUAL: i64 = 2;
+/// Loop Habit Tracker marks days which were implicitly done (i.e. not needed to meet the habit's
+/// frequency) with this
+const LOOP_YES_AUTO: i64 = 1;
+/// Loop Habit Tracker marks days which weren't done with this
+const LOOP_NO: i64 = 0;
+/// Loop Habit Tracker marks days which were skipped with this
+const LOOP_SKIP: i64 = 3;
+/// Loop Habit Tracker marks days it has no entry for with this
+const LOOP_UNKNOWN: i64 = -1;
+
+/// Check-ins for a single habit, as they're read
+#[derive(Default)]
+struct Habit {
+    /// Days on which the habit was done, along with the time it was checked in (if known)
+    checkins: BTreeMap<NaiveDate, Option<NaiveTime>>,
+    /// The first day anything was recorded for the habit
+    first_seen: Option<NaiveDate>,
+}
+
+impl Habit {
+    fn seen(&mut self, date: NaiveDate) {
+        self.first_seen = Some(self.first_seen.map_or(date, |first| first.min(date)));
+    }
+
+    /// Turn the habit into a task, created at the start (as `rules` define it) of the first day
+    /// anything was recorded for it. The task is left without an ID, which `convert()` gives it.
+    fn into_task(self, name: String, rules: &DayRules) -> Option<Task> {
+        let first_seen = self.first_seen?;
+        let created = local_datetime(first_seen, rules.day_start);
+
+        let completions = self
+            .checkins
+            .into_iter()
+            .map(|(date, time)| {
+                // Check-ins without a time (or at a time which would count for another day) are
+                // recorded in the middle of the day, like backdated completions
+                let middle = || rules.middle_of(&local_date(date)).with_timezone(&Utc);
+                let datetime = match time {
+                    Some(time) => {
+                        let datetime = local_datetime(date, time);
+                        if rules.day_of(&datetime.with_timezone(&Local)).naive_local() == date {
+                            datetime
+                        } else {
+                            middle()
+                        }
+                    }
+                    None => middle(),
+                };
+                let datetime = datetime.max(created);
+
+                // Check-ins are read in the local timezone, so that's where they were recorded
+                let offset = datetime.with_timezone(&Local).offset().fix();
+                Completion::new(datetime, Some(offset), None)
+            })
+            .collect();
+
+        Some(Task::from_parts(
+            vec![TaskDetails::revision(
+                created,
+                Some(created.with_timezone(&Local).offset().fix()),
+                0,
+                name,
+                Schedule::Daily,
+            )],
+            completions,
+            Vec::new(),
+            None,
+        ))
+    }
+}
+
+/// Hash a habit's name (FNV-1a), so that it gets the same ID every time the same check-ins are
+/// converted, even by another build. `attempt` is mixed in to find another ID when one can't be
+/// used.
+fn habit_hash(name: &str, attempt: u64) -> u64 {
+    name.bytes()
+        .chain(attempt.to_le_bytes().iter().copied())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Interpret a date and time in the local timezone
+fn local_datetime(date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+    let naive = date.and_time(time);
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|datetime| datetime.with_timezone(&Utc))
+        .unwrap_or_else(|| DateTime::from_utc(naive, Utc))
+}
+
+/// Read a date, optionally with a time (e.g. `2019-12-01`, `2019-12-01 08:30` or an RFC 3339
+/// timestamp)
+fn parse_date(text: &str) -> Option<(NaiveDate, Option<NaiveTime>)> {
+    let text = text.trim();
+
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(text) {
+        let local = datetime.with_timezone(&Local);
+        return Some((local.date().naive_local(), Some(local.time())));
+    }
+    for format in &["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(text, format) {
+            return Some((datetime.date(), Some(datetime.time())));
+        }
+    }
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .ok()
+        .map(|date| (date, None))
+}
+
+/// Convert check-ins from `source`, as read from `path` (which is only used to describe errors).
+/// Each check-in counts for the day it's listed under, as `rules` define days, and the conversion
+/// is treated as an export made `now`.
+pub fn convert(
+    source: Source,
+    path: &Path,
+    contents: &str,
+    rules: &DayRules,
+    now: DateTime<FixedOffset>,
+) -> Result<Converted, ChainError> {
+    let mut unmapped = Vec::new();
+    let habits = match source {
+        Source::Loop => read_loop(path, contents, &mut unmapped)?,
+        Source::Generic => read_generic(path, contents, &mut unmapped)?,
+    };
+
+    let mut tasks: Vec<Task> = Vec::new();
+    for (name, habit) in habits {
+        match habit.into_task(name.clone(), rules) {
+            Some(mut task) => {
+                let mut attempt = 0;
+                let id = loop {
+                    match id_from_hash(habit_hash(&name, attempt)) {
+                        Some(id) if !tasks.iter().any(|task| task.id() == id) => break id,
+                        _ => attempt += 1,
+                    }
+                };
+                task.set_id(id);
+                tasks.push(task);
+            }
+            None => unmapped.push(format!("\"{}\" has no check-ins, so it was left out", name)),
+        }
+    }
+
+    Ok(Converted {
+        export: Export {
+            version: EXPORT_VERSION,
+            exported: now.with_timezone(&Utc),
+            tasks,
+            pauses: Vec::new(),
+        },
+        unmapped,
+    })
+}
+
+/// Describe a CSV error as a parse error
+fn csv_error(path: &Path, contents: &str, e: csv::Error) -> ChainError {
+    let position = e.position().map(|position| (position.line() as usize, 1));
+    ChainError::syntax(path, contents, e.to_string(), position)
+}
+
+/// Read Loop Habit Tracker's `Checkmarks.csv`, in which each row has a date followed by the
+/// status of each habit on that day
+fn read_loop(
+    path: &Path,
+    contents: &str,
+    unmapped: &mut Vec<String>,
+) -> Result<Vec<(String, Habit)>, ChainError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(contents.as_bytes());
+    let mut records = reader.records();
+
+    let header = match records.next() {
+        Some(header) => header.map_err(|e| csv_error(path, contents, e))?,
+        None => Default::default(),
+    };
+    if header.get(0).map(str::trim) != Some("Date") || header.len() < 2 {
+        return Err(ChainError::syntax(
+            path,
+            contents,
+            "expected a header of \"Date\" followed by habit names (as in Loop Habit Tracker's \
+             Checkmarks.csv)"
+                .to_string(),
+            Some((1, 1)),
+        ));
+    }
+
+    let mut habits: Vec<(String, Habit)> = header
+        .iter()
+        .skip(1)
+        .map(|name| (name.trim().to_string(), Habit::default()))
+        .collect();
+    let mut skipped = vec![0; habits.len()];
+    let mut automatic = vec![0; habits.len()];
+    let mut amounts = vec![0; habits.len()];
+
+    for record in records {
+        let record = record.map_err(|e| csv_error(path, contents, e))?;
+        let line = record.position().map_or(0, |position| position.line());
+
+        let date = match record.get(0).and_then(parse_date) {
+            Some((date, _)) => date,
+            None => {
+                unmapped.push(format!(
+                    "line {}: \"{}\" isn't a date",
+                    line,
+                    record.get(0).unwrap_or("")
+                ));
+                continue;
+            }
+        };
+
+        for (n, (name, habit)) in habits.iter_mut().enumerate() {
+            let value = record.get(n + 1).unwrap_or("").trim();
+            match value.parse::<i64>() {
+                Ok(LOOP_UNKNOWN) => (),
+                Ok(LOOP_NO) => habit.seen(date),
+                Ok(LOOP_YES_AUTO) => {
+                    habit.seen(date);
+                    automatic[n] += 1;
+                }
+                Ok(LOOP_YES_MANUAL) => {
+                    habit.seen(date);
+                    habit.checkins.insert(date, None);
+                }
+                Ok(LOOP_SKIP) => {
+                    habit.seen(date);
+                    skipped[n] += 1;
+                }
+                // Numerical habits store amounts (multiplied by 1000)
+                Ok(amount) if amount > 0 => {
+                    habit.seen(date);
+                    habit.checkins.insert(date, None);
+                    amounts[n] += 1;
+                }
+                _ if value.is_empty() => (),
+                _ => unmapped.push(format!(
+                    "line {}: \"{}\" isn't a check-in for \"{}\"",
+                    line, value, name
+                )),
+            }
+        }
+    }
+
+    for (n, (name, _)) in habits.iter().enumerate() {
+        if skipped[n] > 0 {
+            unmapped.push(format!(
+                "\"{}\": {} skipped days were left out",
+                name, skipped[n]
+            ));
+        }
+        if automatic[n] > 0 {
+            unmapped.push(format!(
+                "\"{}\": {} days which Loop marked as done automatically were left out",
+                name, automatic[n]
+            ));
+        }
+        if amounts[n] > 0 {
+            unmapped.push(format!(
+                "\"{}\": {} amounts were imported as plain completions",
+                name, amounts[n]
+            ));
+        }
+    }
+
+    Ok(habits)
+}
+
+/// Read `date,habit,value` rows. A value of `1`, `y`, `yes`, `true`, `x` or any positive number
+/// is a check-in, and `0`, `n`, `no`, `false` or nothing isn't.
+fn read_generic(
+    path: &Path,
+    contents: &str,
+    unmapped: &mut Vec<String>,
+) -> Result<Vec<(String, Habit)>, ChainError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(contents.as_bytes());
+
+    let mut habits: Vec<(String, Habit)> = Vec::new();
+
+    for (n, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| csv_error(path, contents, e))?;
+        let line = record.position().map_or(0, |position| position.line());
+
+        let field = |index: usize| record.get(index).unwrap_or("").trim();
+
+        // The header row is optional
+        if n == 0 && field(0).eq_ignore_ascii_case("date") {
+            continue;
+        }
+
+        let (date, time) = match parse_date(field(0)) {
+            Some(date) => date,
+            None => {
+                unmapped.push(format!("line {}: \"{}\" isn't a date", line, field(0)));
+                continue;
+            }
+        };
+        let name = field(1);
+        if name.is_empty() {
+            unmapped.push(format!("line {}: no habit given", line));
+            continue;
+        }
+
+        let done = match field(2).to_lowercase().as_str() {
+            "1" | "y" | "yes" | "true" | "x" => true,
+            "" | "0" | "n" | "no" | "false" => false,
+            value => match value.parse::<f64>() {
+                Ok(amount) => amount > 0.0,
+                Err(_) => {
+                    unmapped.push(format!(
+                        "line {}: \"{}\" isn't a check-in for \"{}\"",
+                        line,
+                        field(2),
+                        name
+                    ));
+                    continue;
+                }
+            },
+        };
+
+        let position = match habits.iter().position(|(habit, _)| habit == name) {
+            Some(position) => position,
+            None => {
+                habits.push((name.to_string(), Habit::default()));
+                habits.len() - 1
+            }
+        };
+        let habit = &mut habits[position].1;
+
+        habit.seen(date);
+        if done {
+            match habit.checkins.entry(date) {
+                Entry::Occupied(_) => unmapped.push(format!(
+                    "line {}: \"{}\" was already checked in on {}",
+                    line, name, date
+                )),
+                Entry::Vacant(entry) => {
+                    entry.insert(time);
+                }
+            }
+        }
+    }
+
+    Ok(habits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convert, Source};
+    use crate::structs::transfer::import;
+    use crate::structs::{DayRules, FixedClock, ImportMode, TaskListing};
+    use chrono::prelude::*;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    /// When check-ins are converted in these tests
+    fn now() -> DateTime<FixedOffset> {
+        FixedOffset::east(0).ymd(2019, 12, 10).and_hms(12, 0, 0)
+    }
+
+    #[test]
+    fn convert_loop() {
+        let contents = "Date,Meditate,Run\n\
+                        2019-12-03,2,3\n\
+                        2019-12-02,0,2\n\
+                        2019-12-01,2,-1\n";
+        let converted = convert(
+            Source::Loop,
+            Path::new("Checkmarks.csv"),
+            contents,
+            &DayRules::default(),
+            now(),
+        )
+        .unwrap();
+
+        assert_eq!(converted.export.exported, now());
+        let tasks = &converted.export.tasks;
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].description(), "Meditate");
+        assert_eq!(tasks[0].completions().len(), 2);
+        assert_eq!(tasks[1].completions().len(), 1);
+        // The skipped day is reported
+        assert_eq!(converted.unmapped.len(), 1);
+
+        assert!(convert(
+            Source::Loop,
+            Path::new("Habits.csv"),
+            "Position,Name\n1,Run\n",
+            &DayRules::default(),
+            now(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn habits_started_together_stay_apart() {
+        let contents = "Date,Meditate,Run,Read\n\
+                        2019-12-02,2,0,2\n\
+                        2019-12-01,2,2,2\n";
+        let converted = || {
+            convert(
+                Source::Loop,
+                Path::new("Checkmarks.csv"),
+                contents,
+                &DayRules::default(),
+                now(),
+            )
+            .unwrap()
+            .export
+        };
+
+        let mut list = TaskListing::new();
+        list.set_clock(Arc::new(FixedClock::new(now())));
+        let summary = import(&mut list, converted(), ImportMode::Merge);
+        assert_eq!(summary.added, vec!["Meditate", "Run", "Read"]);
+        assert_eq!(summary.duplicates, 0);
+        assert_eq!(list.total_tasks(), 3);
+
+        // Converting the same check-ins again gives the same tasks
+        let summary = import(&mut list, converted(), ImportMode::Merge);
+        assert!(summary.is_empty());
+        assert_eq!(summary.unchanged, 3);
+    }
+
+    #[test]
+    fn convert_generic() {
+        let contents = "date,habit,value\n\
+                        2019-12-01,read,1\n\
+                        2019-12-01 21:30,read,yes\n\
+                        2019-12-02T07:00:00+00:00,gym,1\n\
+                        2019-12-03,gym,0\n\
+                        someday,gym,1\n\
+                        2019-12-04,gym,maybe\n";
+        let converted = convert(
+            Source::Generic,
+            Path::new("checkins.csv"),
+            contents,
+            &DayRules::default(),
+            now(),
+        )
+        .unwrap();
+
+        let tasks = &converted.export.tasks;
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].completions().len(), 1);
+        assert_eq!(tasks[1].completions().len(), 1);
+        // The repeated check-in, the bad date and the bad value are reported
+        assert_eq!(converted.unmapped.len(), 3);
+    }
+
+    #[test]
+    fn convert_with_late_day_start() {
+        let rules = DayRules {
+            day_start: NaiveTime::from_hms(4, 0, 0),
+            ..DayRules::default()
+        };
+        let contents = "2019-12-01 01:00,read,1\n2019-12-02 23:30,read,1\n";
+        let converted =
+            convert(Source::Generic, Path::new("a.csv"), contents, &rules, now()).unwrap();
+
+        let task = &converted.export.tasks[0];
+        assert_eq!(task.created_on(&rules), Local.ymd(2019, 12, 1));
+        // Each check-in still counts for the day it was listed under
+        assert!(task.completed_on(Local.ymd(2019, 12, 1), &rules));
+        assert!(task.completed_on(Local.ymd(2019, 12, 2), &rules));
+    }
+
+    #[test]
+    fn convert_is_repeatable() {
+        let contents = "2019-12-01,read,1\n";
+        let first = convert(
+            Source::Generic,
+            Path::new("a.csv"),
+            contents,
+            &DayRules::default(),
+            now(),
+        )
+        .unwrap();
+        let second = convert(
+            Source::Generic,
+            Path::new("a.csv"),
+            contents,
+            &DayRules::default(),
+            now(),
+        )
+        .unwrap();
+
+        // Tasks are recognised by when they were created, so this has to be the same each time
+        assert_eq!(
+            first.export.tasks[0].created(),
+            second.export.tasks[0].created()
+        );
+    }
+}
@@ -0,0 +1,208 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use chrono::prelude::*;
+use std::sync::{Arc, Mutex};
+
+use super::dayrules::local_date;
+use super::DayRules;
+
+/// Where chain gets the current time from. Everything which depends on the time goes through the
+/// `Clock` carried by the `TaskListing`, so that it can be tested at (or reproduce a bug at) any
+/// moment.
+pub trait Clock {
+    /// Get the current time, in the timezone the clock is in
+    fn now(&self) -> DateTime<FixedOffset>;
+
+    /// Get the day it is now, as `rules` define days
+    fn today(&self, rules: &DayRules) -> Date<Local> {
+        local_date(rules.day_of(&self.now()).naive_local())
+    }
+}
+
+/// The system clock, in the local timezone
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<FixedOffset> {
+        let now = Local::now();
+        now.with_timezone(&now.offset().fix())
+    }
+}
+
+/// A clock which stays at the time it's set to until it's moved, in whichever timezone that time
+/// is in
+#[derive(Debug)]
+pub struct FixedClock {
+    now: Mutex<DateTime<FixedOffset>>,
+}
+
+impl FixedClock {
+    /// Create a clock which is stopped at `now`
+    pub fn new(now: DateTime<FixedOffset>) -> FixedClock {
+        FixedClock {
+            now: Mutex::new(now),
+        }
+    }
+
+    /// Move the clock to `now`
+    pub fn set(&self, now: DateTime<FixedOffset>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    /// Move the clock forward (or back, for a negative `duration`)
+    pub fn advance(&self, duration: chrono::Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now = *now + duration;
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<FixedOffset> {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// Get the system clock, which a `TaskListing` carries unless it's given another
+pub fn system() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// Parse a point in time, either in RFC 3339 (e.g. `2019-12-01T23:30:00+09:00`), or as a date
+/// and time (or just a date, meaning the start of it) in the local timezone
+pub fn parse_datetime(s: &str) -> Option<DateTime<FixedOffset>> {
+    let s = s.trim();
+
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+        return Some(datetime);
+    }
+
+    let naive = [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .ok()
+            .map(|date| date.and_hms(0, 0, 0))
+    })?;
+
+    let local = Local.from_local_datetime(&naive).earliest()?;
+    Some(local.with_timezone(&local.offset().fix()))
+}
+
+/// Whatever a test prints after this (on the same line) is returned by `output_in_zone()`
+#[cfg(test)]
+pub(crate) const ZONE_OUTPUT: &str = "zone output: ";
+
+/// Run the (ignored) test at `path` (e.g. `structs::clock::tests::some_test`) in another process,
+/// whose local timezone is `zone` (e.g. `Asia/Tokyo`), returning each line it printed after
+/// `ZONE_OUTPUT`. The local timezone can't be changed once a process has started, so this is
+/// how tests check what happens in some other timezone.
+#[cfg(test)]
+pub(crate) fn output_in_zone(path: &str, zone: &str) -> Vec<String> {
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args([
+            path,
+            "--exact",
+            "--ignored",
+            "--nocapture",
+            "--test-threads=1",
+        ])
+        .env("TZ", zone)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        output.status.success(),
+        "{} failed in {}:\n{}{}",
+        path,
+        zone,
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    stdout
+        .lines()
+        .filter_map(|line| line.split_once(ZONE_OUTPUT))
+        .map(|(_, output)| output.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_datetime, Clock, FixedClock};
+    use crate::structs::DayRules;
+    use chrono::prelude::*;
+
+    #[test]
+    fn fixed_clock() {
+        let tokyo = FixedOffset::east(9 * 3600);
+        let clock = FixedClock::new(tokyo.ymd(2019, 12, 1).and_hms(23, 30, 0));
+        let rules = DayRules::default();
+
+        assert_eq!(
+            clock.today(&rules).naive_local(),
+            NaiveDate::from_ymd(2019, 12, 1)
+        );
+
+        clock.advance(chrono::Duration::hours(1));
+        assert_eq!(clock.now(), tokyo.ymd(2019, 12, 2).and_hms(0, 30, 0));
+        assert_eq!(
+            clock.today(&rules).naive_local(),
+            NaiveDate::from_ymd(2019, 12, 2)
+        );
+
+        let late = DayRules {
+            day_start: NaiveTime::from_hms(4, 0, 0),
+            ..DayRules::default()
+        };
+        assert_eq!(
+            clock.today(&late).naive_local(),
+            NaiveDate::from_ymd(2019, 12, 1)
+        );
+    }
+
+    #[test]
+    fn parse_times() {
+        assert_eq!(
+            parse_datetime("2019-12-01T23:30:00+09:00"),
+            Some(
+                FixedOffset::east(9 * 3600)
+                    .ymd(2019, 12, 1)
+                    .and_hms(23, 30, 0)
+            )
+        );
+
+        let local = parse_datetime("2019-12-01 23:30").unwrap();
+        assert_eq!(
+            local.naive_local(),
+            NaiveDate::from_ymd(2019, 12, 1).and_hms(23, 30, 0)
+        );
+        assert_eq!(
+            parse_datetime("2019-12-01").unwrap().naive_local(),
+            NaiveDate::from_ymd(2019, 12, 1).and_hms(0, 0, 0)
+        );
+
+        assert_eq!(parse_datetime("yesterday"), None);
+    }
+}
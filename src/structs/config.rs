@@ -0,0 +1,570 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::path::{Path, PathBuf};
+
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::storage;
+use super::ChainError;
+use super::DayRules;
+
+/// Environment variable which overrides where the configuration file is kept
+pub const CONFIG_VAR: &str = "CHAIN_CONFIG";
+
+/// Name of the configuration file, within chain's folder in the platform's config directory
+const CONFIG_FILE: &str = "config.toml";
+
+/// Written by `chain config edit` when there's no configuration file yet. Every setting has its
+/// default value, so this is equivalent to having no file at all.
+pub const TEMPLATE: &str = r#"# chain configuration
+
+# Subcommand run when none is given: tui, today, list or stats
+default_command = "tui"
+
+# Format of dates given on the command line and shown in reports (see
+# https://docs.rs/chrono/0.4/chrono/format/strftime/). ISO 8601 dates (e.g.
+# 2019-12-01) are always accepted too.
+date_format = "%F"
+
+# First day of the week, used for tasks due some number of times per week
+week_start = "Mon"
+
+# Time at which each day starts. Something done after midnight but before this
+# counts for the day before (e.g. "04:00" for night owls).
+day_start = "00:00"
+
+# Ask before deleting or replacing tasks
+confirm = true
+
+# Number of backups of the task database to keep (0 disables backups). The
+# CHAIN_BACKUPS environment variable takes precedence over this.
+backups = 10
+
+[tui]
+# Minimum number of days of history shown next to each task
+history_days = 5
+
+# Colours of the calendar cells: default, black, red, green, yellow, blue,
+# magenta, cyan or white
+[theme]
+completed = "green"
+missed = "red"
+pending = "yellow"
+not_due = "default"
+"#;
+
+/// Get the path of the configuration file: `CHAIN_CONFIG` if it's set, otherwise `config.toml` in
+/// a `chain` folder within the platform's config directory
+pub fn config_path() -> PathBuf {
+    match std::env::var_os(CONFIG_VAR) {
+        Some(path) if !path.is_empty() => PathBuf::from(path),
+        _ => {
+            let mut path = dirs::config_dir().unwrap();
+            path.push("chain");
+            path.push(CONFIG_FILE);
+            path
+        }
+    }
+}
+
+/// Subcommands which can be run when none is given
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DefaultCommand {
+    /// Launch the text UI
+    Tui,
+    /// Show the status of tasks for today
+    Today,
+    /// List tasks along with their schedules
+    List,
+    /// Show streak statistics for every task
+    Stats,
+}
+
+/// Colours which can be used in the TUI
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Colour {
+    /// The terminal's own foreground colour
+    Default,
+    /// Black
+    Black,
+    /// Red
+    Red,
+    /// Green
+    Green,
+    /// Yellow
+    Yellow,
+    /// Blue
+    Blue,
+    /// Magenta
+    Magenta,
+    /// Cyan
+    Cyan,
+    /// White
+    White,
+}
+
+/// Colours used for each kind of calendar cell in the TUI
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    /// Days on which the task was completed
+    pub completed: Colour,
+    /// Days on which the task was due but not completed
+    pub missed: Colour,
+    /// Today, if the task is due but hasn't been completed yet
+    pub pending: Colour,
+    /// Days on which the task wasn't due
+    pub not_due: Colour,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            completed: Colour::Green,
+            missed: Colour::Red,
+            pending: Colour::Yellow,
+            not_due: Colour::Default,
+        }
+    }
+}
+
+/// Settings for the TUI
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TuiConfig {
+    /// Minimum number of days of history shown next to each task (descriptions are truncated to
+    /// make room if needed)
+    pub history_days: usize,
+}
+
+impl Default for TuiConfig {
+    fn default() -> Self {
+        TuiConfig { history_days: 5 }
+    }
+}
+
+/// The user's configuration, read from `config.toml`. Anything missing from the file takes its
+/// default value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Subcommand run when none is given
+    pub default_command: DefaultCommand,
+    /// Format of dates given on the command line and shown in reports
+    pub date_format: String,
+    /// First day of the week
+    pub week_start: Weekday,
+    /// Time at which each day starts, as `HH:MM`
+    pub day_start: String,
+    /// Whether to ask before deleting or replacing tasks
+    pub confirm: bool,
+    /// Number of backups of the task file to keep
+    pub backups: usize,
+    /// Settings for the TUI
+    pub tui: TuiConfig,
+    /// Colours used in the TUI
+    pub theme: Theme,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            default_command: DefaultCommand::Tui,
+            date_format: "%F".to_string(),
+            week_start: Weekday::Mon,
+            day_start: "00:00".to_string(),
+            confirm: true,
+            backups: 10,
+            tui: TuiConfig::default(),
+            theme: Theme::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration file at `path`. A missing file is treated as an empty one, so every
+    /// setting takes its default value.
+    pub fn load(path: &Path) -> Result<Config, ChainError> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Config::parse(path, &contents),
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(error) => Err(ChainError::Io {
+                path: path.to_path_buf(),
+                error,
+            }),
+        }
+    }
+
+    /// Parse and validate the contents of the configuration file at `path` (which is only used to
+    /// describe errors)
+    pub fn parse(path: &Path, contents: &str) -> Result<Config, ChainError> {
+        let config: Config = toml::from_str(contents).map_err(|e| toml_error(path, contents, e))?;
+
+        let problems = config.problems();
+        if !problems.is_empty() {
+            return Err(ChainError::Validation {
+                path: path.to_path_buf(),
+                problems,
+            });
+        }
+
+        Ok(config)
+    }
+
+    /// Check that every setting makes sense, returning a description of each problem found
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        // A format which loses information (or has invalid specifiers) can't be used to read
+        // dates back in
+        let sample = NaiveDate::from_ymd(2019, 12, 31);
+        let formatted = format_date(sample, &self.date_format);
+        if NaiveDate::parse_from_str(&formatted, &self.date_format) != Ok(sample) {
+            problems.push(format!(
+                "date_format: \"{}\" must include the year, month and day (e.g. \"%d/%m/%Y\")",
+                self.date_format
+            ));
+        }
+
+        if parse_time(&self.day_start).is_none() {
+            problems.push(format!(
+                "day_start: \"{}\" isn't a time like \"04:00\"",
+                self.day_start
+            ));
+        }
+
+        if self.tui.history_days == 0 {
+            problems.push("tui.history_days: must be at least 1".to_string());
+        }
+
+        problems
+    }
+
+    /// Get the rules for grouping days which follow from this configuration
+    pub fn day_rules(&self) -> DayRules {
+        DayRules {
+            week_start: self.week_start,
+            day_start: self.day_start(),
+        }
+    }
+
+    /// Get the time at which each day starts (midnight if the setting is invalid, which `problems`
+    /// reports)
+    pub fn day_start(&self) -> NaiveTime {
+        parse_time(&self.day_start).unwrap_or_else(|| NaiveTime::from_hms(0, 0, 0))
+    }
+
+    /// Get a setting by its name (e.g. `date_format` or `tui.history_days`), as it would be
+    /// written in the file (without quotes around strings). Tables are shown in full.
+    pub fn get(&self, key: &str) -> Option<String> {
+        let value = toml::Value::try_from(self).ok()?;
+        let value = lookup(&value, key)?;
+
+        Some(match value {
+            toml::Value::String(s) => s.clone(),
+            toml::Value::Table(_) => toml::to_string(value).ok()?.trim_end().to_string(),
+            value => value.to_string(),
+        })
+    }
+
+    /// Write out every setting as TOML
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).unwrap()
+    }
+
+    /// Get the name of every setting, in alphabetical order
+    pub fn keys() -> Vec<String> {
+        let mut keys = Vec::new();
+        if let Ok(toml::Value::Table(table)) = toml::Value::try_from(Config::default()) {
+            collect_keys(&table, "", &mut keys);
+        }
+
+        keys
+    }
+
+    /// Change a setting in the configuration file at `path`, creating it if needed. Other
+    /// settings are kept as they are, though comments are not.
+    ///
+    /// `value` is interpreted according to the type of the setting, and the whole configuration
+    /// is validated before anything is written. Returns the new configuration.
+    pub fn set(path: &Path, key: &str, value: &str) -> Result<Config, ChainError> {
+        let invalid = |problem: String| ChainError::Validation {
+            path: path.to_path_buf(),
+            problems: vec![problem],
+        };
+
+        let defaults = toml::Value::try_from(Config::default()).unwrap();
+        let value = match lookup(&defaults, key) {
+            Some(toml::Value::String(_)) => toml::Value::String(value.to_string()),
+            Some(toml::Value::Integer(_)) => match value.parse::<i64>() {
+                Ok(n) => toml::Value::Integer(n),
+                Err(_) => return Err(invalid(format!("{}: expected a number", key))),
+            },
+            Some(toml::Value::Boolean(_)) => match value {
+                "true" | "yes" | "on" => toml::Value::Boolean(true),
+                "false" | "no" | "off" => toml::Value::Boolean(false),
+                _ => return Err(invalid(format!("{}: expected true or false", key))),
+            },
+            _ => {
+                return Err(invalid(format!(
+                    "there's no setting called \"{}\" (settings are {})",
+                    key,
+                    Config::keys().join(", ")
+                )))
+            }
+        };
+
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(error) => {
+                return Err(ChainError::Io {
+                    path: path.to_path_buf(),
+                    error,
+                })
+            }
+        };
+        let mut file: toml::Value =
+            toml::from_str(&contents).map_err(|e| toml_error(path, &contents, e))?;
+
+        // Insert the value, creating any tables along the way
+        let mut table = &mut file;
+        let mut parts = key.split('.').peekable();
+        while let Some(part) = parts.next() {
+            let entries = match table {
+                toml::Value::Table(entries) => entries,
+                _ => return Err(invalid(format!("{}: isn't a table", part))),
+            };
+            if parts.peek().is_none() {
+                entries.insert(part.to_string(), value);
+                break;
+            }
+            table = entries
+                .entry(part.to_string())
+                .or_insert_with(|| toml::Value::Table(Default::default()));
+        }
+
+        let contents = toml::to_string(&file).map_err(|e| invalid(e.to_string()))?;
+        let config = Config::parse(path, &contents)?;
+
+        let io_error = |error| ChainError::Io {
+            path: path.to_path_buf(),
+            error,
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        storage::write_atomic(path, contents.as_bytes()).map_err(io_error)?;
+
+        Ok(config)
+    }
+
+    /// Parse a date given by the user, in either the configured format or ISO 8601
+    pub fn parse_date(&self, s: &str) -> Option<NaiveDate> {
+        NaiveDate::parse_from_str(s, &self.date_format)
+            .or_else(|_| NaiveDate::parse_from_str(s, "%F"))
+            .ok()
+    }
+
+    /// Format a date for display in the configured format
+    pub fn format_date(&self, date: NaiveDate) -> String {
+        format_date(date, &self.date_format)
+    }
+}
+
+/// Parse a time of day written as `HH:MM`
+fn parse_time(s: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M").ok()
+}
+
+/// Format `date`, or return an empty string if `format` is invalid (rather than panicking, as
+/// `Display` would)
+fn format_date(date: NaiveDate, format: &str) -> String {
+    use std::fmt::Write;
+
+    let mut formatted = String::new();
+    match write!(formatted, "{}", date.format(format)) {
+        Ok(()) => formatted,
+        Err(_) => String::new(),
+    }
+}
+
+/// Describe an error from parsing `contents` as TOML
+fn toml_error(path: &Path, contents: &str, e: toml::de::Error) -> ChainError {
+    // The position is 0 based, and is reported separately
+    let mut position = e.line_col().map(|(line, col)| (line + 1, col + 1));
+    let message = e.to_string();
+    let message = match message.rfind(" at line ") {
+        Some(end) if position.is_some() => message[..end].to_string(),
+        _ => message,
+    };
+
+    // Unknown settings are reported at the start of the table they're in (and unknown tables at
+    // the start of the next one), so point at the setting itself instead. It's most likely to be
+    // after the reported position, but may be before it.
+    if let Some(name) = message
+        .strip_prefix("unknown field `")
+        .and_then(|rest| rest.split('`').next())
+    {
+        let defines_name = |(_, line): &(usize, &str)| {
+            let line = line.trim_start();
+            line.strip_prefix(name)
+                .is_some_and(|rest| rest.trim_start().starts_with('='))
+                || line.starts_with(&format!("[{}]", name))
+        };
+        let start = position.map_or(0, |(line, _)| line - 1);
+        let found = contents
+            .lines()
+            .enumerate()
+            .skip(start)
+            .find(defines_name)
+            .or_else(|| contents.lines().enumerate().find(defines_name));
+        if let Some((n, line)) = found {
+            position = Some((n + 1, line.len() - line.trim_start().len() + 1));
+        }
+    }
+
+    ChainError::syntax(path, contents, message, position)
+}
+
+/// Find a value by its dotted name (e.g. `tui.history_days`)
+fn lookup<'a>(value: &'a toml::Value, key: &str) -> Option<&'a toml::Value> {
+    key.split('.')
+        .try_fold(value, |value, part| value.get(part))
+}
+
+/// Add the dotted name of every value (other than tables) in `table` to `keys`
+fn collect_keys(table: &toml::value::Table, prefix: &str, keys: &mut Vec<String>) {
+    for (name, value) in table {
+        let key = format!("{}{}", prefix, name);
+        match value {
+            toml::Value::Table(table) => collect_keys(table, &format!("{}.", key), keys),
+            _ => keys.push(key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Colour, Config, DefaultCommand, TEMPLATE};
+    use crate::structs::ChainError;
+    use chrono::prelude::*;
+    use std::path::Path;
+
+    #[test]
+    fn template_has_defaults() {
+        let config = Config::parse(Path::new("config.toml"), TEMPLATE).unwrap();
+        assert_eq!(config, Config::default());
+        assert_eq!(Config::parse(Path::new("config.toml"), "").unwrap(), config);
+    }
+
+    #[test]
+    fn parse_settings() {
+        let contents = "default_command = \"today\"\nweek_start = \"sunday\"\n\n[theme]\nmissed = \"magenta\"\n";
+        let config = Config::parse(Path::new("config.toml"), contents).unwrap();
+
+        assert_eq!(config.default_command, DefaultCommand::Today);
+        assert_eq!(config.week_start, Weekday::Sun);
+        assert_eq!(config.theme.missed, Colour::Magenta);
+        assert_eq!(config.theme.completed, Colour::Green);
+        assert_eq!(config.get("theme.missed").unwrap(), "magenta");
+        assert_eq!(config.get("tui.history_days").unwrap(), "5");
+    }
+
+    #[test]
+    fn invalid_settings_are_reported() {
+        let path = Path::new("config.toml");
+
+        match Config::parse(path, "confirm = true\ncolour = \"red\"\n") {
+            Err(ChainError::Parse { position, .. }) => assert_eq!(position, Some((2, 1))),
+            result => panic!("expected a parse error, got {:?}", result),
+        }
+
+        match Config::parse(path, "date_format = \"%Y-%m\"\n[tui]\nhistory_days = 0\n") {
+            Err(ChainError::Validation { problems, .. }) => assert_eq!(problems.len(), 2),
+            result => panic!("expected a validation error, got {:?}", result),
+        }
+
+        assert!(Config::parse(path, "theme = { completed = \"purple\" }").is_err());
+
+        // Unknown tables are pointed out too
+        match Config::parse(
+            path,
+            "confirm = true\n\n[tv]\nhistory_days = 3\n\n[theme]\n",
+        ) {
+            Err(ChainError::Parse { position, .. }) => assert_eq!(position, Some((3, 1))),
+            result => panic!("expected a parse error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn day_start_setting() {
+        let path = Path::new("config.toml");
+
+        let config = Config::parse(path, "day_start = \"04:30\"\n").unwrap();
+        assert_eq!(config.day_rules().day_start, NaiveTime::from_hms(4, 30, 0));
+        assert_eq!(
+            Config::default().day_rules().day_start,
+            NaiveTime::from_hms(0, 0, 0)
+        );
+
+        for invalid in &["4am", "25:00", ""] {
+            let contents = format!("day_start = \"{}\"\n", invalid);
+            match Config::parse(path, &contents) {
+                Err(ChainError::Validation { problems, .. }) => assert_eq!(problems.len(), 1),
+                result => panic!("expected a validation error, got {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn dates_in_configured_format() {
+        let config = Config {
+            date_format: "%d/%m/%Y".to_string(),
+            ..Config::default()
+        };
+        let date = NaiveDate::from_ymd(2019, 12, 1);
+
+        assert_eq!(config.format_date(date), "01/12/2019");
+        assert_eq!(config.parse_date("01/12/2019"), Some(date));
+        assert_eq!(config.parse_date("2019-12-01"), Some(date));
+        assert_eq!(config.parse_date("12/2019"), None);
+    }
+
+    #[test]
+    fn set_keeps_other_settings() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("chain-test-config-{}.toml", std::process::id()));
+        std::fs::write(&path, "confirm = false\n").unwrap();
+
+        let config = Config::set(&path, "tui.history_days", "14").unwrap();
+        assert_eq!(config.tui.history_days, 14);
+        assert!(!config.confirm);
+        assert_eq!(Config::load(&path).unwrap(), config);
+
+        assert!(Config::set(&path, "tui.history_days", "lots").is_err());
+        assert!(Config::set(&path, "week_start", "someday").is_err());
+        assert!(Config::set(&path, "nonsense", "1").is_err());
+        assert_eq!(Config::load(&path).unwrap(), config);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
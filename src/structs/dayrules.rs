@@ -0,0 +1,236 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use chrono::prelude::*;
+
+/// The user's preferences for how days are grouped, which affect when tasks are due and which day
+/// a completion counts for.
+///
+/// These aren't stored with the tasks, so the `TaskListing` is given them after it's loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayRules {
+    /// First day of the week, used for tasks due some number of times per week
+    pub week_start: Weekday,
+    /// Time at which each day starts (e.g. 04:00, so that something done at 00:30 counts for the
+    /// day before)
+    pub day_start: NaiveTime,
+}
+
+impl Default for DayRules {
+    fn default() -> Self {
+        DayRules {
+            week_start: Weekday::Mon,
+            day_start: NaiveTime::from_hms(0, 0, 0),
+        }
+    }
+}
+
+impl DayRules {
+    /// Get the first day of the week containing `date`
+    pub fn week_of(&self, date: NaiveDate) -> NaiveDate {
+        let days_into_week = (7 + date.weekday().num_days_from_monday()
+            - self.week_start.num_days_from_monday())
+            % 7;
+
+        date - chrono::Duration::days(i64::from(days_into_week))
+    }
+
+    /// Get the day something which happened at `datetime` counts for.
+    ///
+    /// This goes by the time on the clock rather than the time elapsed since midnight, so a day
+    /// which starts at 04:00 still starts at 04:00 on the night the clocks change.
+    pub fn day_of<Tz: TimeZone>(&self, datetime: &DateTime<Tz>) -> Date<Tz> {
+        let date = datetime.date();
+
+        if datetime.time() < self.day_start {
+            date.pred()
+        } else {
+            date
+        }
+    }
+
+    /// Get the time within `date` which is furthest from either end of it (i.e. 12 hours after the
+    /// day starts), where backdated completions are recorded so that they're unambiguously on the
+    /// right day
+    pub fn middle_of<Tz: TimeZone>(&self, date: &Date<Tz>) -> DateTime<Tz> {
+        let start = date.naive_local().and_time(self.day_start);
+        let middle = start + chrono::Duration::hours(12);
+
+        date.timezone()
+            .from_local_datetime(&middle)
+            .earliest()
+            .unwrap_or_else(|| date.and_hms(12, 0, 0))
+    }
+}
+
+/// Interpret a calendar date in the local timezone
+pub fn local_date(date: NaiveDate) -> Date<Local> {
+    Local
+        .from_local_date(&date)
+        .earliest()
+        .unwrap_or_else(|| Local.from_utc_date(&date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DayRules;
+    use crate::structs::clock::{output_in_zone, ZONE_OUTPUT};
+    use chrono::prelude::*;
+
+    /// Rules for a day which starts at 04:00
+    fn late_night() -> DayRules {
+        DayRules {
+            day_start: NaiveTime::from_hms(4, 0, 0),
+            ..DayRules::default()
+        }
+    }
+
+    #[test]
+    fn week_of() {
+        // 2019-12-04 was a Wednesday
+        let date = NaiveDate::from_ymd(2019, 12, 4);
+
+        assert_eq!(
+            DayRules::default().week_of(date),
+            NaiveDate::from_ymd(2019, 12, 2)
+        );
+
+        let sunday = DayRules {
+            week_start: Weekday::Sun,
+            ..DayRules::default()
+        };
+        assert_eq!(sunday.week_of(date), NaiveDate::from_ymd(2019, 12, 1));
+        assert_eq!(
+            sunday.week_of(NaiveDate::from_ymd(2019, 12, 1)),
+            NaiveDate::from_ymd(2019, 12, 1)
+        );
+
+        let thursday = DayRules {
+            week_start: Weekday::Thu,
+            ..DayRules::default()
+        };
+        assert_eq!(thursday.week_of(date), NaiveDate::from_ymd(2019, 11, 28));
+    }
+
+    #[test]
+    fn day_boundary() {
+        let utc = |h, m, s| Utc.ymd(2019, 12, 4).and_hms(h, m, s);
+        let rules = late_night();
+
+        assert_eq!(rules.day_of(&utc(0, 30, 0)), Utc.ymd(2019, 12, 3));
+        assert_eq!(rules.day_of(&utc(3, 59, 59)), Utc.ymd(2019, 12, 3));
+        assert_eq!(rules.day_of(&utc(4, 0, 0)), Utc.ymd(2019, 12, 4));
+        assert_eq!(rules.day_of(&utc(23, 59, 59)), Utc.ymd(2019, 12, 4));
+
+        // Days start at midnight by default
+        let rules = DayRules::default();
+        assert_eq!(rules.day_of(&utc(0, 0, 0)), Utc.ymd(2019, 12, 4));
+        assert_eq!(
+            rules.day_of(&Utc.ymd(2019, 12, 3).and_hms(23, 59, 59)),
+            Utc.ymd(2019, 12, 3)
+        );
+    }
+
+    /// Check `day_of()` and `middle_of()` on the days the clocks changed in 2019, for
+    /// `days_across_dst`, which runs this in Europe/Berlin
+    #[test]
+    #[ignore = "run in a timezone with daylight saving time by days_across_dst"]
+    fn days_across_dst_in_zone() {
+        let local = |month, day, h, m| Local.ymd(2019, month, day).and_hms(h, m, 0);
+        let rules = late_night();
+
+        println!(
+            "{}winter offset {}",
+            ZONE_OUTPUT,
+            local(1, 1, 12, 0).offset()
+        );
+        println!(
+            "{}summer offset {}",
+            ZONE_OUTPUT,
+            local(7, 1, 12, 0).offset()
+        );
+
+        // Clocks went forward from 02:00 CET to 03:00 CEST on 2019-03-31, so 04:30 CEST is only
+        // two and a half hours after midnight, but the day has still started
+        assert_eq!(rules.day_of(&local(3, 31, 1, 30)), Local.ymd(2019, 3, 30));
+        assert_eq!(rules.day_of(&local(3, 31, 3, 30)), Local.ymd(2019, 3, 30));
+        assert_eq!(rules.day_of(&local(3, 31, 4, 30)), Local.ymd(2019, 3, 31));
+
+        // A day which starts at a time skipped that night starts when the clocks change
+        let skipped = DayRules {
+            day_start: NaiveTime::from_hms(2, 30, 0),
+            ..DayRules::default()
+        };
+        assert_eq!(skipped.day_of(&local(3, 31, 1, 59)), Local.ymd(2019, 3, 30));
+        assert_eq!(skipped.day_of(&local(3, 31, 3, 0)), Local.ymd(2019, 3, 31));
+
+        // Clocks went back from 03:00 CEST to 02:00 CET on 2019-10-27, so 02:30 happened twice,
+        // and it was before the day started both times
+        let first = Utc
+            .ymd(2019, 10, 27)
+            .and_hms(0, 30, 0)
+            .with_timezone(&Local);
+        let second = Utc
+            .ymd(2019, 10, 27)
+            .and_hms(1, 30, 0)
+            .with_timezone(&Local);
+        assert_eq!(first.naive_local(), second.naive_local());
+        assert_eq!(rules.day_of(&first), Local.ymd(2019, 10, 26));
+        assert_eq!(rules.day_of(&second), Local.ymd(2019, 10, 26));
+        assert_eq!(rules.day_of(&local(10, 27, 4, 0)), Local.ymd(2019, 10, 27));
+
+        // The middle of the day is 12 hours after it starts on the clock, whichever way the
+        // clocks changed
+        for &(month, day) in &[(3, 31), (10, 27)] {
+            let date = Local.ymd(2019, month, day);
+            let middle = rules.middle_of(&date);
+            assert_eq!(middle.naive_local(), date.naive_local().and_hms(16, 0, 0));
+            assert_eq!(rules.day_of(&middle), date);
+        }
+
+        // When that time was skipped (or happened twice), it's still on the right day
+        let afternoon = DayRules {
+            day_start: NaiveTime::from_hms(14, 30, 0),
+            ..DayRules::default()
+        };
+        for &(month, day) in &[(3, 30), (10, 26)] {
+            let date = Local.ymd(2019, month, day);
+            assert_eq!(afternoon.day_of(&afternoon.middle_of(&date)), date);
+        }
+    }
+
+    #[test]
+    fn days_across_dst() {
+        let output = output_in_zone(
+            "structs::dayrules::tests::days_across_dst_in_zone",
+            "Europe/Berlin",
+        );
+
+        // The checks ran in a timezone with daylight saving time
+        assert_eq!(output, vec!["winter offset +01:00", "summer offset +02:00"]);
+    }
+
+    #[test]
+    fn middle_of_day() {
+        let rules = late_night();
+        let date = Local.ymd(2019, 12, 4);
+
+        let middle = rules.middle_of(&date);
+        assert_eq!(middle.naive_local(), date.naive_local().and_hms(16, 0, 0));
+        assert_eq!(rules.day_of(&middle), date);
+    }
+}
@@ -0,0 +1,232 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::ProfileError;
+use super::TaskError;
+
+/// Exit code used when an operation couldn't be carried out (e.g. a task didn't exist)
+pub const EXIT_OPERATION: i32 = 1;
+/// Exit code used when the task file couldn't be read or written
+pub const EXIT_IO: i32 = 2;
+/// Exit code used when the task file couldn't be parsed
+pub const EXIT_PARSE: i32 = 3;
+/// Exit code used when the task file was parsed, but contains data chain can't work with
+pub const EXIT_INVALID: i32 = 4;
+/// Exit code used when the task file was written by a newer version of chain
+pub const EXIT_VERSION: i32 = 5;
+
+/// Everything that can go wrong while loading the task file, or handling an operation on it
+#[derive(Debug)]
+pub enum ChainError {
+    /// The task file couldn't be read or written
+    Io {
+        /// Path of the task file
+        path: PathBuf,
+        /// What went wrong
+        error: io::Error,
+    },
+    /// The task file isn't valid RON, or doesn't have the structure of a `TaskListing`
+    Parse {
+        /// Path of the task file
+        path: PathBuf,
+        /// What the parser found wrong
+        message: String,
+        /// Line and column of the problem (both starting at 1), if known
+        position: Option<(usize, usize)>,
+        /// The offending line, with the column marked
+        snippet: Option<String>,
+    },
+    /// The task file was parsed, but contains data chain can't work with
+    Validation {
+        /// Path of the task file
+        path: PathBuf,
+        /// Description of each problem found
+        problems: Vec<String>,
+    },
+    /// An operation couldn't be carried out
+    Task(TaskError),
+    /// A profile couldn't be used, created or deleted
+    Profile(ProfileError),
+}
+
+impl ChainError {
+    /// Describe an error from parsing `contents`, which were read from `path`
+    pub fn parse(path: &Path, contents: &str, error: ron::de::Error) -> ChainError {
+        match error {
+            ron::de::Error::Parser(_, position) => ChainError::syntax(
+                path,
+                contents,
+                parse_message(&error),
+                Some((position.line, position.col)),
+            ),
+            ron::de::Error::Message(message) | ron::de::Error::IoError(message) => {
+                ChainError::syntax(path, contents, message, None)
+            }
+        }
+    }
+
+    /// Describe a problem found at `position` (line and column, both starting at 1) while parsing
+    /// `contents`, which were read from `path`. This is used for formats other than RON.
+    pub fn syntax(
+        path: &Path,
+        contents: &str,
+        message: String,
+        position: Option<(usize, usize)>,
+    ) -> ChainError {
+        ChainError::Parse {
+            path: path.to_path_buf(),
+            message,
+            position,
+            snippet: position.and_then(|(line, col)| snippet(contents, line, col)),
+        }
+    }
+
+    /// The code the process should exit with because of this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ChainError::Io { .. } => EXIT_IO,
+            ChainError::Parse { .. } => EXIT_PARSE,
+            ChainError::Validation { .. } => EXIT_INVALID,
+            ChainError::Task(TaskError::UnsupportedVersion(_)) => EXIT_VERSION,
+            ChainError::Task(_) | ChainError::Profile(_) => EXIT_OPERATION,
+        }
+    }
+
+    /// Returns true if the error is a problem with the contents of the task file (as opposed to a
+    /// problem accessing it, or with some operation)
+    pub fn is_corruption(&self) -> bool {
+        match self {
+            ChainError::Parse { .. } | ChainError::Validation { .. } => true,
+            ChainError::Io { .. } | ChainError::Task(_) | ChainError::Profile(_) => false,
+        }
+    }
+}
+
+/// Get a message for a RON parser error, without the position `Display` puts in front of it
+#[allow(deprecated)]
+fn parse_message(error: &ron::de::Error) -> String {
+    error.description().to_string()
+}
+
+/// Show the line of `contents` at which a problem was found, with a marker under the column
+fn snippet(contents: &str, line: usize, col: usize) -> Option<String> {
+    let text = contents.lines().nth(line.checked_sub(1)?)?;
+    let gutter = line.to_string();
+    let marker_offset: String = text
+        .chars()
+        .take(col.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    Some(format!(
+        "{} |\n{} | {}\n{} | {}^",
+        " ".repeat(gutter.len()),
+        gutter,
+        text,
+        " ".repeat(gutter.len()),
+        marker_offset
+    ))
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChainError::Io { path, error } => {
+                write!(f, "couldn't access {}: {}", path.display(), error)
+            }
+            ChainError::Parse {
+                path,
+                message,
+                position,
+                snippet,
+            } => {
+                match position {
+                    Some((line, col)) => write!(
+                        f,
+                        "couldn't parse {} (line {}, column {}): {}",
+                        path.display(),
+                        line,
+                        col,
+                        message
+                    )?,
+                    None => write!(f, "couldn't parse {}: {}", path.display(), message)?,
+                }
+
+                if let Some(snippet) = snippet {
+                    write!(f, "\n{}", snippet)?;
+                }
+
+                Ok(())
+            }
+            ChainError::Validation { path, problems } => {
+                write!(f, "{} contains invalid data:", path.display())?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+
+                Ok(())
+            }
+            ChainError::Task(e) => e.fmt(f),
+            ChainError::Profile(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for ChainError {}
+
+impl From<TaskError> for ChainError {
+    fn from(e: TaskError) -> Self {
+        ChainError::Task(e)
+    }
+}
+
+impl From<ProfileError> for ChainError {
+    fn from(e: ProfileError) -> Self {
+        ChainError::Profile(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChainError;
+    use std::path::Path;
+
+    #[test]
+    fn parse_error_has_position_and_snippet() {
+        let contents = "TaskListing(\n    all_tasks: [\n        Task(,\n    ],\n)\n";
+        let error = ron::de::from_str::<crate::structs::TaskListing>(contents)
+            .err()
+            .unwrap();
+        let error = ChainError::parse(Path::new("taskdata.ron"), contents, error);
+
+        match &error {
+            ChainError::Parse {
+                position, snippet, ..
+            } => {
+                assert_eq!(position.unwrap().0, 3);
+                assert!(snippet.as_ref().unwrap().contains("3 |         Task(,"));
+            }
+            _ => panic!("expected a parse error, got {:?}", error),
+        }
+        assert_eq!(error.exit_code(), super::EXIT_PARSE);
+    }
+}
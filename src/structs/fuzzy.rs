@@ -0,0 +1,103 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/// How well a query matches a task's description. Later variants are better matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Match {
+    /// The query's characters appear in the description in order, with gaps (e.g. `rdbk` in
+    /// `read a book`)
+    Scattered,
+    /// Every word of the query appears in the description, in any order (e.g. `book read`)
+    AllWords,
+    /// The query appears somewhere in the description (e.g. `ym` in `go to the gym`)
+    Substring,
+    /// The query appears at the start of a word (e.g. `gym` in `go to the gym`)
+    WordStart,
+    /// The description starts with the query (e.g. `go to`)
+    Prefix,
+    /// The description is the query
+    Exact,
+}
+
+/// Find out how well `query` matches `text`, ignoring case and surrounding whitespace, or `None`
+/// if it doesn't match at all
+pub fn rank(query: &str, text: &str) -> Option<Match> {
+    let query = query.trim().to_lowercase();
+    let text = text.trim().to_lowercase();
+
+    if query.is_empty() {
+        return None;
+    }
+
+    if text == query {
+        Some(Match::Exact)
+    } else if text.starts_with(&query) {
+        Some(Match::Prefix)
+    } else if text
+        .match_indices(&query)
+        .any(|(start, _)| !text[..start].ends_with(char::is_alphanumeric))
+    {
+        Some(Match::WordStart)
+    } else if text.contains(&query) {
+        Some(Match::Substring)
+    } else if query.split_whitespace().all(|word| text.contains(word)) {
+        Some(Match::AllWords)
+    } else if is_subsequence(&query, &text) {
+        Some(Match::Scattered)
+    } else {
+        None
+    }
+}
+
+/// Returns true if every character of `query` appears in `text`, in the same order (whitespace
+/// in `query` is ignored)
+fn is_subsequence(query: &str, text: &str) -> bool {
+    let mut text = text.chars();
+    query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .all(|c| text.any(|t| t == c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rank, Match};
+
+    #[test]
+    fn ranking() {
+        assert_eq!(rank("Go to the gym", "go to the gym"), Some(Match::Exact));
+        assert_eq!(rank("go to", "Go to the gym"), Some(Match::Prefix));
+        assert_eq!(rank("gym", "Go to the gym"), Some(Match::WordStart));
+        assert_eq!(rank("the gym", "Go to the gym"), Some(Match::WordStart));
+        assert_eq!(
+            rank("gym", "Go to the gym, 30 minutes"),
+            Some(Match::WordStart)
+        );
+        assert_eq!(rank("ym", "Go to the gym"), Some(Match::Substring));
+        assert_eq!(rank("gym go", "Go to the gym"), Some(Match::AllWords));
+        assert_eq!(rank("gtg", "Go to the gym"), Some(Match::Scattered));
+        assert_eq!(rank("swim", "Go to the gym"), None);
+        assert_eq!(rank("  ", "Go to the gym"), None);
+    }
+
+    #[test]
+    fn better_matches_rank_higher() {
+        assert!(Match::Exact > Match::Prefix);
+        assert!(Match::Prefix > Match::WordStart);
+        assert!(Match::Substring > Match::Scattered);
+    }
+}
@@ -0,0 +1,101 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::TaskOperation;
+
+/// Maximum number of operations which can be undone, older entries are discarded
+const MAX_ENTRIES: usize = 100;
+
+/// An operation which was applied to a `TaskListing`, along with the operation which reverses it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// When the operation was applied
+    pub applied: DateTime<Utc>,
+    /// The operation as it was originally requested
+    pub operation: TaskOperation,
+    /// An operation which reverses the effects of `operation`
+    pub inverse: TaskOperation,
+    /// An operation which exactly re-applies `operation` after it has been undone (e.g. restoring
+    /// a completion with its original timestamp, rather than completing the task again)
+    pub reapply: TaskOperation,
+}
+
+/// A record of operations applied to a `TaskListing`, used to implement undo/redo. It's stored
+/// along with the listing so that operations can be undone across invocations.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Journal {
+    /// Operations which can be undone (most recent last)
+    undo: Vec<JournalEntry>,
+    /// Operations which were undone and can be redone (most recently undone last)
+    redo: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// Record an operation newly applied at `applied`. This invalidates anything which could be
+    /// redone.
+    pub fn record(
+        &mut self,
+        operation: TaskOperation,
+        inverse: TaskOperation,
+        applied: DateTime<Utc>,
+    ) {
+        self.redo.clear();
+        self.push_undo(JournalEntry {
+            applied,
+            reapply: operation.clone(),
+            operation,
+            inverse,
+        });
+    }
+
+    /// Get the most recent operation which can be undone, without taking it
+    pub fn peek_undo(&self) -> Option<&JournalEntry> {
+        self.undo.last()
+    }
+
+    /// Get the most recently undone operation, without taking it
+    pub fn peek_redo(&self) -> Option<&JournalEntry> {
+        self.redo.last()
+    }
+
+    /// Take the most recent operation which can be undone
+    pub fn pop_undo(&mut self) -> Option<JournalEntry> {
+        self.undo.pop()
+    }
+
+    /// Take the most recently undone operation
+    pub fn pop_redo(&mut self) -> Option<JournalEntry> {
+        self.redo.pop()
+    }
+
+    /// Add an entry which can be undone
+    pub fn push_undo(&mut self, entry: JournalEntry) {
+        self.undo.push(entry);
+
+        if self.undo.len() > MAX_ENTRIES {
+            self.undo.remove(0);
+        }
+    }
+
+    /// Add an entry which can be redone
+    pub fn push_redo(&mut self, entry: JournalEntry) {
+        self.redo.push(entry);
+    }
+}
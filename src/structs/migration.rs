@@ -0,0 +1,283 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use serde::Deserialize;
+
+use super::TaskError;
+use super::TaskListing;
+
+/// Version of the task file format written by this version of chain. Files written before the
+/// format was versioned are version 0.
+pub const CURRENT_VERSION: u32 = 2;
+
+/// Just enough of a `TaskListing` to find out which version of the format it was written in,
+/// without having to understand the rest of it
+#[derive(Deserialize)]
+#[serde(rename = "TaskListing")]
+struct VersionProbe {
+    #[serde(default)]
+    version: u32,
+}
+
+/// A step in the migration pipeline, which upgrades a listing from one version of the format to
+/// the next.
+///
+/// Older files are deserialized into the current structs (fields added since then have serde
+/// defaults), and each step then fixes up whatever the defaults can't express.
+struct Step {
+    /// Version this step upgrades from (it upgrades to the next version)
+    from: u32,
+    /// Summary of what changes between the two versions
+    description: &'static str,
+    /// Upgrades the listing, returning a description of each change made
+    apply: fn(&mut TaskListing) -> Vec<String>,
+}
+
+/// Every migration step, in order
+const STEPS: &[Step] = &[
+    Step {
+        from: 0,
+        description: "record the format version in the task file",
+        apply: v0_to_v1,
+    },
+    Step {
+        from: 1,
+        description: "give every task a stable ID",
+        apply: v1_to_v2,
+    },
+];
+
+/// A migration step which was applied to a listing
+#[derive(Debug)]
+pub struct Migration {
+    /// Version the listing was upgraded from
+    pub from: u32,
+    /// Version the listing was upgraded to
+    pub to: u32,
+    /// Summary of what changes between the two versions
+    pub description: &'static str,
+    /// Each change made to the listing
+    pub changes: Vec<String>,
+}
+
+/// Find out which version of the format some serialized task data was written in
+pub fn detect_version(contents: &str) -> Result<u32, ron::de::Error> {
+    let probe: VersionProbe = ron::de::from_str(contents)?;
+    Ok(probe.version)
+}
+
+/// Upgrade a listing to `CURRENT_VERSION`, one version at a time, returning the steps which were
+/// applied (if any). This only changes the listing in memory.
+pub fn upgrade(listing: &mut TaskListing) -> Result<Vec<Migration>, TaskError> {
+    if listing.version() > CURRENT_VERSION {
+        return Err(TaskError::UnsupportedVersion(listing.version()));
+    }
+
+    let mut applied = Vec::new();
+
+    let version = listing.version();
+    for step in STEPS.iter().filter(|step| step.from >= version) {
+        let changes = (step.apply)(listing);
+        listing.set_version(step.from + 1);
+
+        applied.push(Migration {
+            from: step.from,
+            to: step.from + 1,
+            description: step.description,
+            changes,
+        });
+    }
+
+    Ok(applied)
+}
+
+/// Version 0 files are either from before remarks, schedules, archiving and undo existed, or have
+/// them all, and serde defaults cover the difference. All that's missing is the version itself.
+fn v0_to_v1(_listing: &mut TaskListing) -> Vec<String> {
+    Vec::new()
+}
+
+/// Tasks were only known by their index before version 2
+fn v1_to_v2(listing: &mut TaskListing) -> Vec<String> {
+    listing
+        .assign_ids()
+        .into_iter()
+        .map(|(index, id)| {
+            let description = listing.task_iter().nth(index).unwrap().description();
+            format!("gave task {} (\"{}\") the ID {}", index, description, id)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect_version, upgrade, CURRENT_VERSION};
+    use crate::structs::{TaskListing, TaskOperation};
+    use chrono::FixedOffset;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Tests run in parallel and may upgrade the same fixture, so each upgrade stores to its own
+    /// folder
+    static STORE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    /// Every task file written by an older version of chain, named after the version that wrote it
+    const FIXTURES: &[(&str, &str)] = &[
+        (
+            "v0-remarkless.ron",
+            include_str!("../../tests/fixtures/v0-remarkless.ron"),
+        ),
+        (
+            "v0-baseline.ron",
+            include_str!("../../tests/fixtures/v0-baseline.ron"),
+        ),
+        (
+            "v0-journal.ron",
+            include_str!("../../tests/fixtures/v0-journal.ron"),
+        ),
+        (
+            "v1-journal.ron",
+            include_str!("../../tests/fixtures/v1-journal.ron"),
+        ),
+    ];
+
+    /// Load a fixture the way chain loads the task file, check that it upgrades to the current
+    /// version, and that the result survives being stored and loaded again
+    fn upgrade_fixture(name: &str) -> TaskListing {
+        let (_, contents) = FIXTURES.iter().find(|(n, _)| *n == name).unwrap();
+        let version = detect_version(contents).unwrap();
+        assert!(name.starts_with(&format!("v{}-", version)));
+
+        let path = Path::new("tests/fixtures").join(name);
+        let mut listing = TaskListing::parse(&path, contents).unwrap();
+        let migrations = upgrade(&mut listing).unwrap();
+        assert_eq!(migrations.len(), (CURRENT_VERSION - version) as usize);
+        assert_eq!(listing.version(), CURRENT_VERSION);
+        assert!(listing.problems().is_empty());
+
+        let dir = std::env::temp_dir().join(format!(
+            "chain-migrate-{}-{}-{}",
+            name,
+            std::process::id(),
+            STORE_COUNT.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let stored = dir.join("taskdata.ron");
+        listing.store(stored.clone()).unwrap();
+        let serialized = std::fs::read_to_string(&stored).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(detect_version(&serialized).unwrap(), CURRENT_VERSION);
+
+        let mut reloaded = TaskListing::parse(&stored, &serialized).unwrap();
+        assert!(upgrade(&mut reloaded).unwrap().is_empty());
+        assert_eq!(reloaded.total_tasks(), listing.total_tasks());
+
+        // Every task has its own ID, which survives the round trip
+        for (task, reloaded_task) in listing.task_iter().zip(reloaded.task_iter()) {
+            assert!(!task.id().is_empty());
+            assert_eq!(task.id(), reloaded_task.id());
+            for (completion, reloaded) in task.completions().iter().zip(reloaded_task.completions())
+            {
+                assert_eq!(completion.datetime(), reloaded.datetime());
+                assert_eq!(completion.offset(), reloaded.offset());
+            }
+        }
+
+        listing
+    }
+
+    #[test]
+    fn every_fixture_upgrades() {
+        for (name, _) in FIXTURES {
+            upgrade_fixture(name);
+        }
+    }
+
+    #[test]
+    fn upgrade_remarkless() {
+        let listing = upgrade_fixture("v0-remarkless.ron");
+
+        let task = listing.task_iter().next().unwrap();
+        assert_eq!(task.description(), "practice guitar");
+        assert_eq!(task.completions().len(), 2);
+        assert!(task.remarks().is_empty());
+    }
+
+    #[test]
+    fn upgrade_baseline() {
+        let listing = upgrade_fixture("v0-baseline.ron");
+
+        let task = listing.task_iter().next().unwrap();
+        assert_eq!(task.description(), "read for 30 minutes");
+        assert_eq!(task.completions().len(), 1);
+        assert_eq!(task.remarks().len(), 1);
+    }
+
+    #[test]
+    fn upgrade_journal() {
+        let listing = upgrade_fixture("v0-journal.ron");
+
+        let mut tasks = listing.task_iter();
+        assert_eq!(tasks.next().unwrap().description(), "write in journal");
+        assert_eq!(tasks.next().unwrap().schedule().to_string(), "mon,wed,fri");
+        assert!(tasks.next().unwrap().is_archived());
+        assert!(listing.journal().peek_undo().is_some());
+    }
+
+    #[test]
+    fn upgrade_v1_assigns_ids() {
+        let contents = FIXTURES[3].1;
+        let mut listing = TaskListing::parse(Path::new("v1-journal.ron"), contents).unwrap();
+        assert!(listing.task_iter().all(|task| task.id().is_empty()));
+
+        let migrations = upgrade(&mut listing).unwrap();
+        assert_eq!(migrations.len(), 1);
+        assert_eq!(migrations[0].to, 2);
+        assert_eq!(migrations[0].changes.len(), listing.total_tasks());
+        assert!(listing.problems().is_empty());
+
+        // Completions keep the offset they were recorded at
+        let offsets: Vec<_> = listing
+            .task_iter()
+            .flat_map(|task| task.completions().iter().map(|c| c.offset()))
+            .collect();
+        assert_eq!(
+            offsets,
+            vec![
+                Some(FixedOffset::east(3600)),
+                Some(FixedOffset::west(5 * 3600))
+            ]
+        );
+
+        // The journal written by v1 still applies: redo the completion that was undone, then undo
+        // it and the archive before it
+        assert!(listing.handle_operation(&TaskOperation::Redo).is_ok());
+        assert_eq!(listing.task_iter().next().unwrap().completions().len(), 2);
+        assert!(listing.handle_operation(&TaskOperation::Undo).is_ok());
+        assert!(listing.handle_operation(&TaskOperation::Undo).is_ok());
+        assert!(listing.task_iter().all(|task| !task.is_archived()));
+    }
+
+    #[test]
+    fn newer_version_rejected() {
+        let mut listing = TaskListing::new();
+        listing.set_version(CURRENT_VERSION + 1);
+
+        assert!(upgrade(&mut listing).is_err());
+    }
+}
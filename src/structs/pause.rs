@@ -0,0 +1,123 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::TaskError;
+
+/// A range of days on which a task (or every task) is excused, e.g. for a vacation or a sick day.
+/// Excused days are skipped, so they neither break a chain nor extend it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pause {
+    /// First day of the pause
+    from: NaiveDate,
+    /// Last day of the pause (inclusive)
+    to: NaiveDate,
+    /// Why the pause was taken (e.g. `vacation`), if a reason was given
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+impl Pause {
+    /// Create a pause from `from` to `to` (inclusive), which can't end before it starts. An empty
+    /// reason is the same as none.
+    pub fn new(from: NaiveDate, to: NaiveDate, reason: Option<String>) -> Result<Pause, TaskError> {
+        if to < from {
+            return Err(TaskError::InvalidPause(from, to));
+        }
+
+        Ok(Pause {
+            from,
+            to,
+            reason: reason.filter(|reason| !reason.trim().is_empty()),
+        })
+    }
+
+    /// Get the first day of this pause
+    pub fn from(&self) -> NaiveDate {
+        self.from
+    }
+
+    /// Get the last day of this pause
+    pub fn to(&self) -> NaiveDate {
+        self.to
+    }
+
+    /// Get the reason for this pause, if one was given
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    /// Returns true if `date` is one of the days of this pause
+    pub fn covers(&self, date: Date<Local>) -> bool {
+        let date = date.naive_local();
+
+        self.from <= date && date <= self.to
+    }
+
+    /// Returns true if this pause makes sense (e.g. after being edited by hand)
+    pub fn is_valid(&self) -> bool {
+        self.from <= self.to
+    }
+}
+
+/// Shown as the range of days followed by the reason, e.g. `2019-12-01 to 2019-12-07 (vacation)`
+impl fmt::Display for Pause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.from == self.to {
+            write!(f, "{}", self.from)?;
+        } else {
+            write!(f, "{} to {}", self.from, self.to)?;
+        }
+        if let Some(reason) = &self.reason {
+            write!(f, " ({})", reason)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pause;
+    use crate::structs::TaskError;
+    use chrono::prelude::*;
+
+    #[test]
+    fn pause_covers_its_days() {
+        let from = NaiveDate::from_ymd(2019, 12, 1);
+        let to = NaiveDate::from_ymd(2019, 12, 7);
+        let pause = Pause::new(from, to, Some("vacation".to_string())).unwrap();
+
+        assert!(!pause.covers(Local.ymd(2019, 11, 30)));
+        assert!(pause.covers(Local.ymd(2019, 12, 1)));
+        assert!(pause.covers(Local.ymd(2019, 12, 7)));
+        assert!(!pause.covers(Local.ymd(2019, 12, 8)));
+        assert_eq!(pause.to_string(), "2019-12-01 to 2019-12-07 (vacation)");
+
+        let sick = Pause::new(from, from, Some(" ".to_string())).unwrap();
+        assert_eq!(sick.reason(), None);
+        assert_eq!(sick.to_string(), "2019-12-01");
+
+        assert_eq!(
+            Pause::new(to, from, None),
+            Err(TaskError::InvalidPause(to, from))
+        );
+    }
+}
@@ -0,0 +1,255 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::tasklisting::TASK_FILE;
+use super::ChainError;
+
+/// Environment variable which overrides the default data directory
+pub const DATA_DIR_VAR: &str = "CHAIN_DATA_DIR";
+
+/// Name of the profile used when none is given. Its task file lives directly in the data
+/// directory, where it always has.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Directory (within the data directory) holding a directory for each named profile
+const PROFILES_DIR: &str = "profiles";
+
+/// Get the data directory to use when none is given explicitly: `CHAIN_DATA_DIR` if it's set,
+/// otherwise a `chain` directory in the platform's data directory
+pub fn default_data_dir() -> PathBuf {
+    match std::env::var_os(DATA_DIR_VAR) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let mut data_dir = dirs::data_dir().unwrap();
+            data_dir.push("chain");
+            data_dir
+        }
+    }
+}
+
+/// Errors for managing profiles
+#[derive(Debug, PartialEq)]
+pub enum ProfileError {
+    /// Profile names can only contain letters, numbers, `-` and `_`
+    InvalidName(String),
+    /// The profile hasn't been created
+    NotFound(String),
+    /// A profile with the name already exists
+    AlreadyExists(String),
+    /// The default profile can't be deleted
+    DeleteDefault,
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProfileError::InvalidName(name) => write!(
+                f,
+                "\"{}\" isn't a valid profile name (use letters, numbers, - and _)",
+                name
+            ),
+            ProfileError::NotFound(name) => write!(
+                f,
+                "profile \"{}\" doesn't exist (see `chain profile create`)",
+                name
+            ),
+            ProfileError::AlreadyExists(name) => {
+                write!(f, "profile \"{}\" already exists", name)
+            }
+            ProfileError::DeleteDefault => f.write_str("the default profile can't be deleted"),
+        }
+    }
+}
+
+impl Error for ProfileError {}
+
+/// Check that `name` can be used as a profile name
+fn validate_name(name: &str) -> Result<(), ProfileError> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(())
+    } else {
+        Err(ProfileError::InvalidName(name.to_string()))
+    }
+}
+
+/// The profiles kept in a data directory, each of which has its own task file (along with its own
+/// backups and lock)
+#[derive(Debug, Clone)]
+pub struct Profiles {
+    data_dir: PathBuf,
+}
+
+impl Profiles {
+    /// Get the profiles kept in `data_dir`
+    pub fn new(data_dir: PathBuf) -> Profiles {
+        Profiles { data_dir }
+    }
+
+    /// Get the data directory holding the profiles
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    /// Get the directory holding a profile's task file
+    fn dir(&self, name: &str) -> PathBuf {
+        if name == DEFAULT_PROFILE {
+            self.data_dir.clone()
+        } else {
+            self.data_dir.join(PROFILES_DIR).join(name)
+        }
+    }
+
+    /// Returns true if the profile exists (the default profile always does)
+    pub fn exists(&self, name: &str) -> bool {
+        name == DEFAULT_PROFILE || (validate_name(name).is_ok() && self.dir(name).is_dir())
+    }
+
+    /// Get the path of a profile's task file, which must already exist
+    pub fn tasks_path(&self, name: &str) -> Result<PathBuf, ProfileError> {
+        validate_name(name)?;
+
+        if !self.exists(name) {
+            return Err(ProfileError::NotFound(name.to_string()));
+        }
+
+        Ok(self.dir(name).join(TASK_FILE))
+    }
+
+    /// Get the name of every profile, with the default profile first and the rest in alphabetical
+    /// order
+    pub fn list(&self) -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+
+        match std::fs::read_dir(self.data_dir.join(PROFILES_DIR)) {
+            Ok(entries) => {
+                for entry in entries {
+                    let entry = entry?;
+                    if !entry.file_type()?.is_dir() {
+                        continue;
+                    }
+                    if let Some(name) = entry.file_name().to_str() {
+                        if validate_name(name).is_ok() {
+                            names.push(name.to_string());
+                        }
+                    }
+                }
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e),
+        }
+
+        names.sort();
+        names.insert(0, DEFAULT_PROFILE.to_string());
+
+        Ok(names)
+    }
+
+    /// Create a new profile, returning the path of its (empty) task file
+    pub fn create(&self, name: &str) -> Result<PathBuf, ChainError> {
+        validate_name(name)?;
+
+        if self.exists(name) {
+            return Err(ProfileError::AlreadyExists(name.to_string()).into());
+        }
+
+        let dir = self.dir(name);
+        std::fs::create_dir_all(&dir).map_err(|error| ChainError::Io {
+            path: dir.clone(),
+            error,
+        })?;
+
+        Ok(dir.join(TASK_FILE))
+    }
+
+    /// Delete a profile, along with its task file and backups. Returns the directory which was
+    /// removed.
+    pub fn delete(&self, name: &str) -> Result<PathBuf, ChainError> {
+        validate_name(name)?;
+
+        if name == DEFAULT_PROFILE {
+            return Err(ProfileError::DeleteDefault.into());
+        }
+        if !self.exists(name) {
+            return Err(ProfileError::NotFound(name.to_string()).into());
+        }
+
+        let dir = self.dir(name);
+        std::fs::remove_dir_all(&dir).map_err(|error| ChainError::Io {
+            path: dir.clone(),
+            error,
+        })?;
+
+        Ok(dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProfileError, Profiles, DEFAULT_PROFILE};
+    use crate::structs::ChainError;
+
+    #[test]
+    fn create_list_delete() {
+        let mut data_dir = std::env::temp_dir();
+        data_dir.push(format!("chain-test-profiles-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&data_dir);
+        let profiles = Profiles::new(data_dir.clone());
+
+        assert_eq!(profiles.list().unwrap(), vec![DEFAULT_PROFILE]);
+        assert!(profiles.tasks_path(DEFAULT_PROFILE).is_ok());
+        assert_eq!(
+            profiles.tasks_path("work"),
+            Err(ProfileError::NotFound("work".to_string()))
+        );
+
+        let work = profiles.create("work").unwrap();
+        profiles.create("personal").unwrap();
+        assert_eq!(profiles.tasks_path("work").unwrap(), work);
+        assert_ne!(work, profiles.tasks_path(DEFAULT_PROFILE).unwrap());
+        assert_eq!(
+            profiles.list().unwrap(),
+            vec![DEFAULT_PROFILE, "personal", "work"]
+        );
+
+        assert!(matches!(
+            profiles.create("work"),
+            Err(ChainError::Profile(ProfileError::AlreadyExists(_)))
+        ));
+        assert!(matches!(
+            profiles.create("../escape"),
+            Err(ChainError::Profile(ProfileError::InvalidName(_)))
+        ));
+        assert!(matches!(
+            profiles.delete(DEFAULT_PROFILE),
+            Err(ChainError::Profile(ProfileError::DeleteDefault))
+        ));
+
+        profiles.delete("work").unwrap();
+        assert_eq!(profiles.list().unwrap(), vec![DEFAULT_PROFILE, "personal"]);
+
+        std::fs::remove_dir_all(&data_dir).unwrap();
+    }
+}
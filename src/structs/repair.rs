@@ -0,0 +1,203 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::migration;
+use super::Task;
+use super::TaskListing;
+
+/// Recover as much as possible from the contents of a task file which can't be loaded (e.g. after
+/// a bad manual edit). Returns the recovered listing, along with a description of everything that
+/// was changed or lost along the way.
+///
+/// If the file can't be parsed as a whole, each task is parsed separately so that one broken task
+/// doesn't take the rest down with it. The undo history is lost in that case.
+pub fn salvage(contents: &str) -> (TaskListing, Vec<String>) {
+    let (mut listing, mut report) = match ron::de::from_str::<TaskListing>(contents) {
+        Ok(listing) => (listing, Vec::new()),
+        Err(_) => {
+            let mut listing = TaskListing::new();
+            // Assume the oldest format if the version can't be found, so that every migration is
+            // applied
+            listing.set_version(migration::detect_version(contents).unwrap_or(0));
+
+            let mut report = vec!["the undo history was discarded".to_string()];
+
+            for (line, chunk) in task_chunks(contents) {
+                match ron::de::from_str::<Task>(chunk) {
+                    Ok(task) => listing.push(task),
+                    Err(ron::de::Error::Parser(_, position)) => {
+                        report.push(format!(
+                            "dropped the task starting on line {}, which is broken on line {}",
+                            line,
+                            line + position.line - 1
+                        ));
+                    }
+                    Err(e) => {
+                        report.push(format!("dropped the task starting on line {}: {}", line, e));
+                    }
+                }
+            }
+
+            (listing, report)
+        }
+    };
+
+    report.extend(listing.repair());
+
+    (listing, report)
+}
+
+/// Split out the text of each element of the `all_tasks` list in (possibly broken) task data,
+/// along with the line each one starts on.
+///
+/// Elements are separated by commas which aren't nested in anything else. If a task is missing a
+/// closing bracket, the next task still starts on its own line with the same indentation (as chain
+/// always writes them that way), so that's used to get back on track.
+fn task_chunks(contents: &str) -> Vec<(usize, &str)> {
+    let start = match contents
+        .find("all_tasks")
+        .and_then(|key| contents[key..].find('[').map(|open| key + open + 1))
+    {
+        Some(start) => start,
+        None => return Vec::new(),
+    };
+
+    let bytes = contents.as_bytes();
+    let mut chunks: Vec<(usize, &str)> = Vec::new();
+
+    let mut push_chunk = |from: usize, to: usize| {
+        let chunk = &contents[from..to];
+        let trimmed = chunk.trim_start();
+        if !trimmed.trim_end().is_empty() {
+            let chunk_start = from + (chunk.len() - trimmed.len());
+            let line = contents[..chunk_start].matches('\n').count() + 1;
+            chunks.push((line, trimmed.trim_end()));
+        }
+    };
+
+    let mut chunk_start = start;
+    let mut depth: usize = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut task_indent: Option<usize> = None;
+
+    for i in start..bytes.len() {
+        let b = bytes[i];
+
+        // chain never writes strings spanning lines, so a newline means a quote is missing
+        if in_string && b != b'\n' {
+            if escaped {
+                escaped = false;
+            } else if b == b'\\' {
+                escaped = true;
+            } else if b == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match b {
+            b'"' => in_string = true,
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => {
+                if depth == 0 {
+                    // End of the list
+                    push_chunk(chunk_start, i);
+                    return chunks;
+                }
+                depth -= 1;
+            }
+            b',' if depth == 0 => {
+                push_chunk(chunk_start, i);
+                chunk_start = i + 1;
+            }
+            b'\n' => {
+                in_string = false;
+                escaped = false;
+
+                let line_start = i + 1;
+                let indent = bytes[line_start..]
+                    .iter()
+                    .take_while(|b| **b == b' ' || **b == b'\t')
+                    .count();
+
+                if contents[line_start + indent..].starts_with("Task(") {
+                    match task_indent {
+                        None => task_indent = Some(indent),
+                        Some(task_indent) if task_indent == indent && depth != 0 => {
+                            // The previous task wasn't closed properly, so start again from here
+                            push_chunk(chunk_start, line_start);
+                            chunk_start = line_start;
+                            depth = 0;
+                        }
+                        Some(_) => (),
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    push_chunk(chunk_start, contents.len());
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::salvage;
+
+    const BASELINE: &str = include_str!("../../tests/fixtures/v0-baseline.ron");
+
+    #[test]
+    fn salvage_intact_file() {
+        let (listing, report) = salvage(BASELINE);
+
+        assert_eq!(listing.total_tasks(), 2);
+        assert!(report.is_empty());
+    }
+
+    #[test]
+    fn salvage_stray_comma() {
+        let broken = BASELINE.replace(
+            "remark: \"finished chapter 3\",",
+            "remark: \"finished chapter 3\",,",
+        );
+        let (listing, report) = salvage(&broken);
+
+        // Only the broken task is lost
+        assert_eq!(listing.total_tasks(), 1);
+        assert_eq!(
+            listing.task_iter().next().unwrap().description(),
+            "no snacks after dinner"
+        );
+        assert_eq!(report.len(), 2);
+    }
+
+    #[test]
+    fn salvage_unclosed_task() {
+        // Remove the closing bracket of the first task's remarks
+        let broken = BASELINE.replacen("            ],\n        ),", "        ),", 1);
+        let (listing, _report) = salvage(&broken);
+
+        assert_eq!(listing.total_tasks(), 1);
+        assert_eq!(
+            listing.task_iter().next().unwrap().description(),
+            "no snacks after dinner"
+        );
+    }
+}
@@ -0,0 +1,514 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use chrono::prelude::*;
+
+use super::DayRules;
+use super::Pause;
+use super::Progress;
+use super::StreakStats;
+use super::Task;
+use super::TaskKind;
+
+/// What happened with a task on a single day
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DayStatus {
+    /// The task was completed (or, for a task to avoid, the day passed without a slip)
+    Completed,
+    /// Some progress was made toward the task's target, but not enough to complete it
+    Partial,
+    /// The task is due today, but hasn't been completed yet
+    Pending,
+    /// The task was due, but wasn't completed (breaking the chain)
+    Missed,
+    /// A slip was logged for a task to avoid (breaking the chain)
+    Slipped,
+    /// The task wasn't due, so skipping it doesn't break the chain
+    NotDue,
+    /// The task was excused (e.g. for a vacation), so skipping it doesn't break the chain
+    Excused,
+    /// The task hadn't been created yet
+    NotCreated,
+    /// The day hasn't happened yet
+    Future,
+}
+
+impl DayStatus {
+    /// Work out what happened with `task` on `date`, as seen on `today`. The task is excused by
+    /// its own pauses, and by the `global` ones.
+    pub fn for_task(
+        task: &Task,
+        date: Date<Local>,
+        today: Date<Local>,
+        rules: &DayRules,
+        global: &[Pause],
+    ) -> DayStatus {
+        if task.kind() == TaskKind::Avoid {
+            return DayStatus::for_avoided_task(task, date, today, rules, global);
+        }
+
+        // A completion recorded in a timezone which is ahead of this one can be on a day which
+        // hasn't started here yet
+        if task.completed_on(date, rules) {
+            DayStatus::Completed
+        } else if task
+            .progress_on(date, rules)
+            .is_some_and(|progress| progress.is_partial())
+        {
+            DayStatus::Partial
+        } else if date > today {
+            DayStatus::Future
+        } else if !task.existed_on(date, rules) {
+            DayStatus::NotCreated
+        } else if task.pause_on(date, global).is_some() {
+            DayStatus::Excused
+        } else if !task.due_on(date, rules) {
+            DayStatus::NotDue
+        } else if date == today {
+            DayStatus::Pending
+        } else {
+            DayStatus::Missed
+        }
+    }
+
+    /// Work out what happened with a task to avoid on `date`, as seen on `today`. Only slips on
+    /// due days which weren't excused break the chain, and a day without one only counts once
+    /// it's over.
+    fn for_avoided_task(
+        task: &Task,
+        date: Date<Local>,
+        today: Date<Local>,
+        rules: &DayRules,
+        global: &[Pause],
+    ) -> DayStatus {
+        let excused = task.pause_on(date, global).is_some();
+
+        if task.slipped_on(date, rules) && task.due_on(date, rules) && !excused {
+            DayStatus::Slipped
+        } else if date > today {
+            DayStatus::Future
+        } else if !task.existed_on(date, rules) {
+            DayStatus::NotCreated
+        } else if excused {
+            DayStatus::Excused
+        } else if !task.due_on(date, rules) {
+            DayStatus::NotDue
+        } else if date == today {
+            DayStatus::Pending
+        } else {
+            DayStatus::Completed
+        }
+    }
+}
+
+/// Status of a single task for today, as shown by `chain today`
+#[derive(Debug, Clone)]
+pub struct TodayEntry {
+    /// Index of the task in the `TaskListing`
+    pub task_index: usize,
+    /// The task's current description
+    pub description: String,
+    /// Whether the task is something to do or something to avoid
+    pub kind: TaskKind,
+    /// Whether the task is due today (tasks aren't due on days they're excused)
+    pub due: bool,
+    /// The pause which excuses the task today (if there is one)
+    pub pause: Option<Pause>,
+    /// When the task was completed today (if it was)
+    pub completed: Option<DateTime<Local>>,
+    /// For tasks to avoid, when a slip was first logged today (if one was)
+    pub slipped: Option<DateTime<Local>>,
+    /// Progress toward the task's target today (if it has one)
+    pub progress: Option<Progress>,
+    /// Length of the current streak, in days
+    pub streak: u32,
+    /// Whether this is the first task which is still due today
+    pub next: bool,
+}
+
+/// A task's status on each day of some range, as shown by `chain history`
+#[derive(Debug, Clone)]
+pub struct HistoryRow {
+    /// Index of the task in the `TaskListing`
+    pub task_index: usize,
+    /// The task's current description
+    pub description: String,
+    /// Status of the task on each day of the range, in order
+    pub days: Vec<(Date<Local>, DayStatus)>,
+}
+
+/// Build the status of every active task for `today`, where the `global` pauses excuse every task
+pub fn today<'a>(
+    tasks: impl Iterator<Item = (usize, &'a Task)>,
+    today: Date<Local>,
+    rules: &DayRules,
+    global: &[Pause],
+) -> Vec<TodayEntry> {
+    let mut next_marked = false;
+
+    tasks
+        .map(|(task_index, task)| {
+            let pause = task.pause_on(today, global).cloned();
+            let due = task.due_on(today, rules) && pause.is_none();
+            let completed = task.completed_today(today, rules);
+
+            // There's nothing to do for tasks to avoid
+            let next = !next_marked && due && completed.is_none() && task.kind() == TaskKind::Do;
+            next_marked |= next;
+
+            TodayEntry {
+                task_index,
+                description: task.description().clone(),
+                kind: task.kind(),
+                due,
+                pause,
+                completed,
+                slipped: task.slipped_today(today, rules),
+                progress: task.progress_on(today, rules),
+                streak: StreakStats::for_task(task, today, rules, global).current_length(),
+                next,
+            }
+        })
+        .collect()
+}
+
+/// Build the status of each task on every day from `start` to `end` (inclusive), where the
+/// `global` pauses excuse every task
+pub fn history<'a>(
+    tasks: impl Iterator<Item = (usize, &'a Task)>,
+    start: Date<Local>,
+    end: Date<Local>,
+    today: Date<Local>,
+    rules: &DayRules,
+    global: &[Pause],
+) -> Vec<HistoryRow> {
+    let mut dates: Vec<Date<Local>> = Vec::new();
+    let mut date_at = start;
+
+    while date_at <= end {
+        dates.push(date_at);
+        date_at = date_at.succ();
+    }
+
+    tasks
+        .map(|(task_index, task)| HistoryRow {
+            task_index,
+            description: task.description().clone(),
+            days: dates
+                .iter()
+                .map(|date| {
+                    (
+                        *date,
+                        DayStatus::for_task(task, *date, today, rules, global),
+                    )
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{history, today, DayStatus};
+    use crate::structs::clock::{output_in_zone, ZONE_OUTPUT};
+    use crate::structs::dayrules::local_date;
+    use crate::structs::{
+        Clock, Completion, DayRules, FixedClock, Pause, Task, TaskKind, TaskListing,
+    };
+    use chrono::prelude::*;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    /// A clock stopped at midday
+    fn clock() -> FixedClock {
+        let now = Local.ymd(2019, 12, 1).and_hms(12, 0, 0);
+        FixedClock::new(now.with_timezone(&now.offset().fix()))
+    }
+
+    #[test]
+    fn today_marks_next_task() {
+        let clock = clock();
+        let rules = DayRules::default();
+        let created = clock.now();
+
+        let mut done = Task::new("done".to_string(), created);
+        done.mark_complete(&None, None, None, &rules, clock.now())
+            .unwrap();
+        let tasks = [
+            done,
+            Task::new("a".to_string(), created),
+            Task::new("b".to_string(), created),
+        ];
+
+        let entries = today(tasks.iter().enumerate(), clock.today(&rules), &rules, &[]);
+
+        assert!(entries[0].completed.is_some());
+        assert!(!entries[0].next);
+        assert!(entries[1].next);
+        assert!(!entries[2].next);
+    }
+
+    #[test]
+    fn history_statuses() {
+        let clock = clock();
+        let task = Task::new("a".to_string(), clock.now());
+        let today_date = clock.today(&DayRules::default());
+
+        let rows = history(
+            std::iter::once((0, &task)),
+            today_date.pred(),
+            today_date.succ(),
+            today_date,
+            &DayRules::default(),
+            &[],
+        );
+        let statuses: Vec<DayStatus> = rows[0].days.iter().map(|(_, status)| *status).collect();
+
+        assert_eq!(
+            statuses,
+            vec![DayStatus::NotCreated, DayStatus::Pending, DayStatus::Future]
+        );
+    }
+
+    #[test]
+    fn late_night_completion_counts_for_previous_day() {
+        let created = Local.ymd(2019, 12, 1).and_hms(12, 0, 0);
+        let created = created.with_timezone(&created.offset().fix());
+        let mut task = Task::new("a".to_string(), created);
+        let night = Local.ymd(2019, 12, 3).and_hms(0, 30, 0);
+        task.restore_completion(Completion::new(night.with_timezone(&Utc), None, None));
+
+        let today_date = Local.ymd(2019, 12, 10);
+        let late = DayRules {
+            day_start: NaiveTime::from_hms(4, 0, 0),
+            ..DayRules::default()
+        };
+        let midnight = DayRules::default();
+
+        let status = |date, rules| DayStatus::for_task(&task, date, today_date, rules, &[]);
+        assert_eq!(status(Local.ymd(2019, 12, 2), &late), DayStatus::Completed);
+        assert_ne!(status(Local.ymd(2019, 12, 3), &late), DayStatus::Completed);
+        assert_ne!(
+            status(Local.ymd(2019, 12, 2), &midnight),
+            DayStatus::Completed
+        );
+        assert_eq!(
+            status(Local.ymd(2019, 12, 3), &midnight),
+            DayStatus::Completed
+        );
+
+        // A completion exactly when the day starts counts for that day
+        let mut task = Task::new("b".to_string(), created);
+        let start = Local.ymd(2019, 12, 5).and_hms(4, 0, 0);
+        task.restore_completion(Completion::new(start.with_timezone(&Utc), None, None));
+        assert!(task.completed_on(Local.ymd(2019, 12, 5), &late));
+        assert!(!task.completed_on(Local.ymd(2019, 12, 4), &late));
+    }
+
+    #[test]
+    fn partial_progress_toward_target() {
+        let clock = clock();
+        let rules = DayRules::default();
+        let today_date = clock.today(&rules);
+        let created = clock.now() - chrono::Duration::days(1);
+
+        let mut task = Task::new("read".to_string(), created);
+        task.set_target(Some("20 pages".parse().unwrap()), created)
+            .unwrap();
+
+        let record = |task: &mut Task, amount: f64| {
+            task.mark_complete(&None, Some(amount), None, &rules, clock.now())
+                .unwrap();
+            clock.advance(chrono::Duration::minutes(1));
+        };
+
+        record(&mut task, 5.0);
+        record(&mut task, 7.0);
+        let status = DayStatus::for_task(&task, today_date, today_date, &rules, &[]);
+        assert_eq!(status, DayStatus::Partial);
+
+        let entries = today(std::iter::once((0, &task)), today_date, &rules, &[]);
+        assert!(entries[0].completed.is_none());
+        assert_eq!(
+            entries[0].progress.as_ref().unwrap().to_string(),
+            "12/20 pages"
+        );
+        assert!(entries[0].next);
+
+        // The day is only completed once the entries add up to the target
+        record(&mut task, 8.0);
+        let status = DayStatus::for_task(&task, today_date, today_date, &rules, &[]);
+        assert_eq!(status, DayStatus::Completed);
+        let entries = today(std::iter::once((0, &task)), today_date, &rules, &[]);
+        assert!(entries[0].completed.is_some());
+
+        // Yesterday nothing was recorded, so it was missed
+        let status = DayStatus::for_task(&task, today_date.pred(), today_date, &rules, &[]);
+        assert_eq!(status, DayStatus::Missed);
+    }
+
+    #[test]
+    fn avoided_task_statuses() {
+        let clock = clock();
+        let rules = DayRules::default();
+        let created = clock.now() - chrono::Duration::days(3);
+        let mut task = Task::new("sugar".to_string(), created).with_kind(TaskKind::Avoid);
+        task.mark_complete(
+            &Some("cake".to_string()),
+            None,
+            None,
+            &rules,
+            clock.now() - chrono::Duration::days(1),
+        )
+        .unwrap();
+        let today_date = clock.today(&rules);
+
+        let rows = history(
+            std::iter::once((0, &task)),
+            today_date - chrono::Duration::days(4),
+            today_date.succ(),
+            today_date,
+            &rules,
+            &[],
+        );
+        let statuses: Vec<DayStatus> = rows[0].days.iter().map(|(_, status)| *status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                DayStatus::NotCreated,
+                DayStatus::Completed,
+                DayStatus::Completed,
+                DayStatus::Slipped,
+                DayStatus::Pending,
+                DayStatus::Future,
+            ]
+        );
+
+        // A task to avoid is never next, and a slip today is reported
+        let entries = today(std::iter::once((0, &task)), today_date, &rules, &[]);
+        assert!(!entries[0].next);
+        assert!(entries[0].slipped.is_none());
+        task.mark_complete(&None, None, None, &rules, clock.now())
+            .unwrap();
+        let entries = today(std::iter::once((0, &task)), today_date, &rules, &[]);
+        assert!(entries[0].completed.is_none());
+        assert_eq!(entries[0].slipped, Some(clock.now().with_timezone(&Local)));
+        assert_eq!(entries[0].streak, 0);
+    }
+
+    #[test]
+    fn paused_days_are_excused() {
+        let clock = clock();
+        let rules = DayRules::default();
+        let created = clock.now() - chrono::Duration::days(3);
+        let mut task = Task::new("gym".to_string(), created);
+        let today_date = clock.today(&rules);
+        let yesterday = today_date.pred().naive_local();
+        task.add_pause(Pause::new(yesterday, yesterday, Some("sick".to_string())).unwrap());
+        let global =
+            [Pause::new(today_date.naive_local(), today_date.naive_local(), None).unwrap()];
+
+        let rows = history(
+            std::iter::once((0, &task)),
+            today_date - chrono::Duration::days(2),
+            today_date,
+            today_date,
+            &rules,
+            &global,
+        );
+        let statuses: Vec<DayStatus> = rows[0].days.iter().map(|(_, status)| *status).collect();
+        assert_eq!(
+            statuses,
+            vec![DayStatus::Missed, DayStatus::Excused, DayStatus::Excused]
+        );
+
+        // Today's pause is reported, and the task isn't due
+        let entries = today(std::iter::once((0, &task)), today_date, &rules, &global);
+        assert!(!entries[0].due);
+        assert!(!entries[0].next);
+        assert_eq!(entries[0].pause.as_ref(), Some(&global[0]));
+    }
+
+    /// Print the history of a task file written while travelling from Tokyo to New York, with
+    /// everything recorded around midnight (when the day differs between the two), for
+    /// `history_is_the_same_in_every_timezone`
+    #[test]
+    #[ignore = "run in other timezones by history_is_the_same_in_every_timezone"]
+    fn print_travel_history() {
+        let path = Path::new("tests/fixtures/v2-travel.ron");
+        let contents = include_str!("../../tests/fixtures/v2-travel.ron");
+        let mut list = TaskListing::parse(path, contents).unwrap();
+        let now = FixedOffset::east(0).ymd(2019, 12, 10).and_hms(12, 0, 0);
+        list.set_clock(Arc::new(FixedClock::new(now)));
+        let rules = list.rules();
+
+        let noon = Local.ymd(2019, 12, 3).and_hms(12, 0, 0);
+        println!("{}local offset {}", ZONE_OUTPUT, noon.offset());
+
+        let start = local_date(NaiveDate::from_ymd(2019, 12, 1));
+        let end = local_date(NaiveDate::from_ymd(2019, 12, 7));
+        for row in list.history_for_range(start, end) {
+            let task = list.task_iter().nth(row.task_index).unwrap();
+            for (date, status) in row.days {
+                let remarks: Vec<&str> = task
+                    .remarks_on(date, rules)
+                    .iter()
+                    .map(|remark| remark.remark())
+                    .collect();
+                println!(
+                    "{}{} {} {:?} \"{}\" {:?}",
+                    ZONE_OUTPUT,
+                    row.task_index,
+                    date.format("%F"),
+                    status,
+                    task.details_on(date, rules).unwrap().description(),
+                    remarks
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn history_is_the_same_in_every_timezone() {
+        let path = "structs::report::tests::print_travel_history";
+        let tokyo = output_in_zone(path, "Asia/Tokyo");
+        let new_york = output_in_zone(path, "America/New_York");
+
+        assert_eq!(tokyo[0], "local offset +09:00");
+        assert_eq!(new_york[0], "local offset -05:00");
+        assert_eq!(tokyo[1..], new_york[1..]);
+
+        // Each thing stays on the day it was recorded, in the timezone it was recorded in
+        let lines = &tokyo[1..];
+        assert_eq!(lines.len(), 14);
+        for expected in &[
+            "0 2019-12-01 NotCreated",
+            "0 2019-12-02 Completed \"read a chapter\" []",
+            "0 2019-12-03 Missed \"read a chapter\" [\"on the plane\"]",
+            "0 2019-12-04 Completed \"read two chapters\" [\"finished the book\"]",
+            "1 2019-12-05 Missed",
+            "1 2019-12-06 NotDue",
+        ] {
+            assert!(
+                lines.iter().any(|line| line.starts_with(expected)),
+                "{:?} not in {:#?}",
+                expected,
+                lines
+            );
+        }
+    }
+}
@@ -0,0 +1,255 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::DayRules;
+
+/// Describes which days a `Task` is due on. Days on which a task isn't due can't break its chain.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum Schedule {
+    /// Due every day
+    #[default]
+    Daily,
+    /// Due on specific days of the week
+    Weekdays(Vec<Weekday>),
+    /// Due every `n` days, counting from the day the task was created
+    EveryNDays(u32),
+    /// Due some number of times per week (weeks start on the day given by `DayRules`), on
+    /// whichever days the user chooses
+    TimesPerWeek(u32),
+}
+
+impl Schedule {
+    /// Returns true if a task following this schedule is due on `date`.
+    ///
+    /// `created` is the date the task was created (used as the starting point for `EveryNDays`),
+    /// `rules` say when weeks start, and `completed_on` reports whether the task was completed on
+    /// a given date (used to work out how many completions are still needed for `TimesPerWeek`).
+    pub fn is_due<F>(
+        &self,
+        date: NaiveDate,
+        created: NaiveDate,
+        rules: &DayRules,
+        completed_on: F,
+    ) -> bool
+    where
+        F: Fn(NaiveDate) -> bool,
+    {
+        if date < created {
+            return false;
+        }
+
+        match self {
+            Schedule::Daily => true,
+            Schedule::Weekdays(days) => days.contains(&date.weekday()),
+            Schedule::EveryNDays(n) => {
+                *n > 0 && date.signed_duration_since(created).num_days() % i64::from(*n) == 0
+            }
+            Schedule::TimesPerWeek(n) => {
+                // Completions always count towards the quota
+                if completed_on(date) {
+                    return true;
+                }
+
+                // Otherwise the day is only due once there's no slack left in the week, i.e. the
+                // remaining days (including this one) are all needed to meet the quota
+                let week_start = rules.week_of(date);
+                let week_end = week_start + chrono::Duration::days(6);
+
+                // The quota is reduced for the week in which the task was created
+                let first_day = if week_start < created {
+                    created
+                } else {
+                    week_start
+                };
+                let days_available = week_end.signed_duration_since(first_day).num_days() + 1;
+                let quota = std::cmp::min(i64::from(*n), days_available);
+
+                let mut done = 0;
+                let mut day = first_day;
+                while day < date {
+                    if completed_on(day) {
+                        done += 1;
+                    }
+                    day = day.succ();
+                }
+
+                let days_left = week_end.signed_duration_since(date).num_days() + 1;
+
+                done < quota && days_left <= quota - done
+            }
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Schedule::Daily => f.write_str("daily"),
+            Schedule::Weekdays(days) => {
+                let days: Vec<String> = days
+                    .iter()
+                    .map(|day| day.to_string().to_lowercase())
+                    .collect();
+                f.write_str(&days.join(","))
+            }
+            Schedule::EveryNDays(n) => write!(f, "every:{}", n),
+            Schedule::TimesPerWeek(n) => write!(f, "{}/week", n),
+        }
+    }
+}
+
+/// Parses the formats produced by `Display`:
+///
+/// - `daily`
+/// - a comma separated list of weekdays, e.g. `mon,wed,fri`
+/// - `every:N` for every N days
+/// - `N/week` for N times per week
+impl std::str::FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+
+        if s == "daily" {
+            return Ok(Schedule::Daily);
+        }
+
+        if let Some(n) = s.strip_prefix("every:") {
+            return match n.parse::<u32>() {
+                Ok(n) if n > 0 => Ok(Schedule::EveryNDays(n)),
+                _ => Err(format!("expected a positive number of days, got \"{}\"", n)),
+            };
+        }
+
+        if let Some(n) = s.strip_suffix("/week") {
+            return match n.parse::<u32>() {
+                Ok(n) if n > 0 && n <= 7 => Ok(Schedule::TimesPerWeek(n)),
+                _ => Err(format!(
+                    "expected between 1 and 7 times per week, got \"{}\"",
+                    n
+                )),
+            };
+        }
+
+        let mut days: Vec<Weekday> = Vec::new();
+        for day in s.split(',') {
+            match day.trim().parse::<Weekday>() {
+                Ok(day) if !days.contains(&day) => days.push(day),
+                Ok(_) => (),
+                Err(_) => {
+                    return Err(format!(
+                        "unrecognized schedule \"{}\" (try daily, mon,wed,fri, every:3 or 3/week)",
+                        s
+                    ))
+                }
+            }
+        }
+        days.sort_by_key(|day| day.num_days_from_monday());
+
+        Ok(Schedule::Weekdays(days))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DayRules, Schedule};
+    use chrono::prelude::*;
+
+    fn never(_date: NaiveDate) -> bool {
+        false
+    }
+
+    #[test]
+    fn parse_round_trip() {
+        for s in &["daily", "mon,wed,fri", "every:3", "2/week"] {
+            let schedule: Schedule = s.parse().unwrap();
+            assert_eq!(&schedule.to_string(), s);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_invalid() {
+        assert!("every:0".parse::<Schedule>().is_err());
+        assert!("8/week".parse::<Schedule>().is_err());
+        assert!("mon,someday".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn weekdays_due() {
+        // 2019-12-02 was a Monday
+        let created = NaiveDate::from_ymd(2019, 12, 2);
+        let schedule: Schedule = "mon,wed,fri".parse().unwrap();
+        let rules = DayRules::default();
+
+        let due: Vec<bool> = (2..9)
+            .map(|day| schedule.is_due(NaiveDate::from_ymd(2019, 12, day), created, &rules, never))
+            .collect();
+
+        assert_eq!(due, vec![true, false, true, false, true, false, false]);
+    }
+
+    #[test]
+    fn every_n_days_due() {
+        let created = NaiveDate::from_ymd(2019, 12, 2);
+        let schedule = Schedule::EveryNDays(3);
+        let rules = DayRules::default();
+
+        assert!(schedule.is_due(created, created, &rules, never));
+        assert!(!schedule.is_due(NaiveDate::from_ymd(2019, 12, 3), created, &rules, never));
+        assert!(schedule.is_due(NaiveDate::from_ymd(2019, 12, 5), created, &rules, never));
+        assert!(!schedule.is_due(NaiveDate::from_ymd(2019, 12, 1), created, &rules, never));
+    }
+
+    #[test]
+    fn times_per_week_due() {
+        let created = NaiveDate::from_ymd(2019, 12, 2);
+        let schedule = Schedule::TimesPerWeek(2);
+        let rules = DayRules::default();
+
+        // With no completions, only the last two days of the week are due
+        assert!(!schedule.is_due(NaiveDate::from_ymd(2019, 12, 6), created, &rules, never));
+        assert!(schedule.is_due(NaiveDate::from_ymd(2019, 12, 7), created, &rules, never));
+        assert!(schedule.is_due(NaiveDate::from_ymd(2019, 12, 8), created, &rules, never));
+
+        // Once the quota is met early in the week, nothing else is due
+        let done = |date: NaiveDate| date.day() == 2 || date.day() == 3;
+        assert!(schedule.is_due(NaiveDate::from_ymd(2019, 12, 2), created, &rules, done));
+        assert!(!schedule.is_due(NaiveDate::from_ymd(2019, 12, 8), created, &rules, done));
+    }
+
+    #[test]
+    fn times_per_week_with_sunday_start() {
+        let created = NaiveDate::from_ymd(2019, 12, 1);
+        let schedule = Schedule::TimesPerWeek(2);
+        let rules = DayRules {
+            week_start: Weekday::Sun,
+            ..DayRules::default()
+        };
+
+        // The week runs from Sunday 2019-12-01 to Saturday 2019-12-07
+        assert!(!schedule.is_due(NaiveDate::from_ymd(2019, 12, 5), created, &rules, never));
+        assert!(schedule.is_due(NaiveDate::from_ymd(2019, 12, 6), created, &rules, never));
+        assert!(schedule.is_due(NaiveDate::from_ymd(2019, 12, 7), created, &rules, never));
+
+        // Sunday 2019-12-08 starts a new week, rather than ending one
+        assert!(!schedule.is_due(NaiveDate::from_ymd(2019, 12, 8), created, &rules, never));
+    }
+}
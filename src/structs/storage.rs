@@ -0,0 +1,294 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use chrono::prelude::*;
+use fs2::FileExt;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// Number of backups kept if `CHAIN_BACKUPS` isn't set
+const DEFAULT_BACKUP_COUNT: usize = 10;
+
+/// Name of the folder (next to the task file) which backups are kept in
+const BACKUP_DIR: &str = "backups";
+
+/// Timestamp format used in backup file names (sorts chronologically, and is safe on any
+/// filesystem)
+const BACKUP_TIMESTAMP: &str = "%Y%m%dT%H%M%S%.3f";
+
+/// How many backups to keep, as configured by the `CHAIN_BACKUPS` environment variable (0
+/// disables backups)
+pub fn backup_count() -> usize {
+    backup_count_override().unwrap_or(DEFAULT_BACKUP_COUNT)
+}
+
+/// How many backups to keep if the `CHAIN_BACKUPS` environment variable is set, which takes
+/// precedence over the configuration file
+pub fn backup_count_override() -> Option<usize> {
+    std::env::var("CHAIN_BACKUPS")
+        .ok()
+        .and_then(|count| count.trim().parse().ok())
+}
+
+/// Replace the contents of `path` such that a crash at any point leaves either the old contents
+/// or the new contents, never something in between.
+///
+/// The data is written to a temporary file in the same folder (so that it's on the same
+/// filesystem), flushed to disk, and then renamed over the original.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp_path = temp_path(path);
+
+    let result = (|| {
+        let mut tmp_file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)?;
+        tmp_file.write_all(contents)?;
+        tmp_file.sync_all()?;
+
+        fs::rename(&tmp_path, path)
+    })();
+
+    if result.is_err() {
+        // Don't leave partial files lying around, the original is untouched
+        let _ = fs::remove_file(&tmp_path);
+        return result;
+    }
+
+    // Make sure the rename itself is durable. Not every platform allows opening a directory, so
+    // this is best effort.
+    if let Some(dir) = path.parent() {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+
+    Ok(())
+}
+
+/// Temporary file used by `write_atomic` for `path`
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+/// Keep a copy of `path` next to it, with `label` added to its name (e.g. `taskdata.v0.ron`). Unlike
+/// backups, these copies are never rotated away. Returns the path of the copy.
+pub fn keep_copy(path: &Path, label: &str) -> io::Result<PathBuf> {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+
+    let copy_path = path.with_file_name(format!("{}.{}{}", stem, label, extension));
+    write_atomic(&copy_path, &fs::read(path)?)?;
+
+    Ok(copy_path)
+}
+
+/// Holds an exclusive advisory lock on a file until it's dropped
+pub struct FileLock {
+    file: File,
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // The lock is also released when the file is closed, so failure here isn't a problem
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+/// Lock `path` against modification by other instances of chain, waiting for any other instance
+/// to finish with it first. The lock is held until the returned `FileLock` is dropped.
+///
+/// The lock is taken on a separate file (next to `path`), because `write_atomic` replaces `path`
+/// itself.
+pub fn lock(path: &Path) -> io::Result<FileLock> {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path.with_file_name(format!("{}.lock", name)))?;
+    file.lock_exclusive()?;
+
+    Ok(FileLock { file })
+}
+
+/// Folder in which backups of `path` are kept
+pub fn backup_dir(path: &Path) -> PathBuf {
+    path.with_file_name(BACKUP_DIR)
+}
+
+/// Copy the current contents of `path` into a new backup named after the current time, then delete
+/// the oldest backups so that at most `keep` remain. Returns the path of the new backup, or `None`
+/// if there was nothing worth backing up.
+pub fn backup(path: &Path, keep: usize) -> io::Result<Option<PathBuf>> {
+    if keep == 0 {
+        return Ok(None);
+    }
+
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    // An empty file has no history worth keeping
+    if contents.is_empty() {
+        return Ok(None);
+    }
+
+    let dir = backup_dir(path);
+    fs::create_dir_all(&dir)?;
+
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+
+    // Backups are named after the system clock, rather than whatever time chain was told it is
+    // (with `--now`), so that they're rotated in the order they were actually made. If the name is
+    // taken (by a backup made within the same millisecond), the next millisecond is used instead.
+    let mut taken = Local::now();
+    let backup_path = loop {
+        let backup_path = dir.join(format!(
+            "{}-{}{}",
+            stem,
+            taken.format(BACKUP_TIMESTAMP),
+            extension
+        ));
+        if !backup_path.exists() {
+            break backup_path;
+        }
+        taken = taken + chrono::Duration::milliseconds(1);
+    };
+    write_atomic(&backup_path, &contents)?;
+
+    for old_backup in backups(path)?.iter().skip(keep) {
+        fs::remove_file(old_backup)?;
+    }
+
+    Ok(Some(backup_path))
+}
+
+/// List existing backups of `path`, newest first
+pub fn backups(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let dir = backup_dir(path);
+
+    let prefix = path
+        .file_stem()
+        .map(|stem| format!("{}-", stem.to_string_lossy()))
+        .unwrap_or_default();
+
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut backups: Vec<PathBuf> = Vec::new();
+    for entry in entries {
+        let entry_path = entry?.path();
+        let is_backup = entry_path
+            .file_name()
+            .map(|name| name.to_string_lossy().starts_with(&prefix))
+            .unwrap_or(false);
+
+        if is_backup && entry_path.is_file() {
+            backups.push(entry_path);
+        }
+    }
+
+    // Timestamps in the names sort chronologically
+    backups.sort();
+    backups.reverse();
+
+    Ok(backups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backup, backups, write_atomic};
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Create an empty folder for a test to work in
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chain-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn write_atomic_replaces_contents() {
+        let dir = test_dir("write-atomic");
+        let path = dir.join("taskdata.ron");
+
+        write_atomic(&path, b"a longer first version").unwrap();
+        write_atomic(&path, b"second").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        // Only the file itself remains, the temporary file was renamed over it
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn backups_are_rotated() {
+        let dir = test_dir("backup-rotation");
+        let path = dir.join("taskdata.ron");
+
+        // Nothing to back up yet
+        assert!(backup(&path, 2).unwrap().is_none());
+
+        // Backups made in quick succession don't overwrite each other
+        for n in 0..4 {
+            write_atomic(&path, format!("version {}", n).as_bytes()).unwrap();
+            assert!(backup(&path, 2).unwrap().is_some());
+        }
+
+        let backups = backups(&path).unwrap();
+        assert_eq!(backups.len(), 2);
+        assert_eq!(fs::read(&backups[0]).unwrap(), b"version 3");
+        assert_eq!(fs::read(&backups[1]).unwrap(), b"version 2");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,244 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use chrono::prelude::*;
+
+use super::DayRules;
+use super::Pause;
+use super::Task;
+use super::TaskKind;
+
+/// An unbroken run of completions (i.e. a link in the chain)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Streak {
+    /// First day the task was completed on in this streak
+    pub start: Date<Local>,
+    /// Last day the task was completed on in this streak
+    pub end: Date<Local>,
+    /// Number of days on which the task was completed during this streak
+    pub length: u32,
+}
+
+/// Statistics about the chain for a single `Task`, up to some date
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreakStats {
+    /// Every streak (oldest first), including the current one
+    pub streaks: Vec<Streak>,
+    /// The streak which is still unbroken (if any)
+    pub current: Option<Streak>,
+    /// Number of days on which the task was due
+    pub due_days: u32,
+    /// Number of days on which the task was due and completed
+    pub completed_days: u32,
+}
+
+impl StreakStats {
+    /// Walk through every day from the task's creation until `today`, comparing completions
+    /// against the days on which the task was due.
+    ///
+    /// A day on which the task was due but not completed breaks the chain, unless that day is
+    /// `today` (as it can still be completed). Days on which the task wasn't due are skipped.
+    ///
+    /// For tasks to avoid, a due day on which a slip was logged breaks the chain, and a due day
+    /// without one extends it once it's over (so `today` only counts if it breaks the chain).
+    ///
+    /// Days on which the task is excused (by one of its own pauses, or one of the `global` ones)
+    /// are treated as days on which it wasn't due.
+    pub fn for_task(
+        task: &Task,
+        today: Date<Local>,
+        rules: &DayRules,
+        global: &[Pause],
+    ) -> StreakStats {
+        let mut stats = StreakStats {
+            streaks: Vec::new(),
+            current: None,
+            due_days: 0,
+            completed_days: 0,
+        };
+
+        let mut day = task.created_on(rules);
+        while day <= today {
+            let due = task.due_on(day, rules) && task.pause_on(day, global).is_none();
+            let (completed, missed) = match task.kind() {
+                TaskKind::Do => (task.completed_on(day, rules), due && day != today),
+                TaskKind::Avoid => {
+                    let slipped = task.slipped_on(day, rules);
+                    (due && !slipped && day != today, due && slipped)
+                }
+            };
+
+            if completed {
+                if due {
+                    stats.due_days += 1;
+                    stats.completed_days += 1;
+                }
+
+                stats.current = match stats.current.take() {
+                    Some(mut streak) => {
+                        streak.end = day;
+                        streak.length += 1;
+                        Some(streak)
+                    }
+                    None => Some(Streak {
+                        start: day,
+                        end: day,
+                        length: 1,
+                    }),
+                };
+            } else if missed {
+                stats.due_days += 1;
+
+                // The chain was broken
+                if let Some(streak) = stats.current.take() {
+                    stats.streaks.push(streak);
+                }
+            }
+
+            day = day.succ();
+        }
+
+        if let Some(streak) = &stats.current {
+            stats.streaks.push(streak.clone());
+        }
+
+        stats
+    }
+
+    /// Length of the streak which is still unbroken (0 if there isn't one)
+    pub fn current_length(&self) -> u32 {
+        self.current.as_ref().map_or(0, |streak| streak.length)
+    }
+
+    /// The longest streak so far (the earliest one if there's a tie)
+    pub fn longest(&self) -> Option<&Streak> {
+        self.streaks
+            .iter()
+            .fold(None, |longest, streak| match longest {
+                Some(longest) if longest.length >= streak.length => Some(longest),
+                _ => Some(streak),
+            })
+    }
+
+    /// Fraction of due days on which the task was completed, or `None` if it was never due
+    pub fn completion_rate(&self) -> Option<f64> {
+        if self.due_days == 0 {
+            None
+        } else {
+            Some(f64::from(self.completed_days) / f64::from(self.due_days))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StreakStats;
+    use crate::structs::{Clock, DayRules, FixedClock, Pause, Task, TaskKind};
+    use chrono::prelude::*;
+
+    /// A clock stopped at midday
+    fn clock() -> FixedClock {
+        let now = Local.ymd(2019, 12, 1).and_hms(12, 0, 0);
+        FixedClock::new(now.with_timezone(&now.offset().fix()))
+    }
+
+    #[test]
+    fn new_task_has_no_streak() {
+        let clock = clock();
+        let rules = DayRules::default();
+        let task = Task::new("first".into(), clock.now());
+        let stats = StreakStats::for_task(&task, clock.today(&rules), &rules, &[]);
+
+        assert_eq!(stats.current_length(), 0);
+        assert!(stats.longest().is_none());
+        assert!(stats.streaks.is_empty());
+        assert_eq!(stats.completion_rate(), None);
+    }
+
+    #[test]
+    fn completing_today_starts_streak() {
+        let clock = clock();
+        let rules = DayRules::default();
+        let mut task = Task::new("first".into(), clock.now());
+        assert!(task
+            .mark_complete(&None, None, None, &rules, clock.now())
+            .is_ok());
+
+        let stats = StreakStats::for_task(&task, clock.today(&rules), &rules, &[]);
+
+        assert_eq!(stats.current_length(), 1);
+        assert_eq!(stats.longest().unwrap().length, 1);
+        assert_eq!(stats.streaks.len(), 1);
+        assert_eq!(stats.completion_rate(), Some(1.0));
+    }
+
+    #[test]
+    fn avoided_days_extend_streak() {
+        let clock = clock();
+        let rules = DayRules::default();
+        let created = clock.now() - chrono::Duration::days(5);
+        let mut task = Task::new("sugar".into(), created).with_kind(TaskKind::Avoid);
+
+        // Nothing logged yet: every day before today was kept, and today doesn't count yet
+        let stats = StreakStats::for_task(&task, clock.today(&rules), &rules, &[]);
+        assert_eq!(stats.current_length(), 5);
+        assert_eq!(stats.completion_rate(), Some(1.0));
+
+        // A slip breaks the chain, even today
+        let slip = clock.now() - chrono::Duration::days(2);
+        task.mark_complete(&None, None, None, &rules, slip).unwrap();
+        task.mark_complete(&None, None, None, &rules, clock.now())
+            .unwrap();
+
+        let stats = StreakStats::for_task(&task, clock.today(&rules), &rules, &[]);
+        assert_eq!(stats.current_length(), 0);
+        assert_eq!(stats.longest().unwrap().length, 3);
+        assert_eq!(stats.streaks.len(), 2);
+        assert_eq!(stats.due_days, 6);
+        assert_eq!(stats.completed_days, 4);
+    }
+
+    #[test]
+    fn paused_days_are_skipped() {
+        let clock = clock();
+        let rules = DayRules::default();
+        let created = clock.now() - chrono::Duration::days(6);
+        let mut task = Task::new("gym".into(), created);
+        for days in [6, 5, 1] {
+            let then = clock.now() - chrono::Duration::days(days);
+            task.mark_complete(&None, None, None, &rules, then).unwrap();
+        }
+
+        // Three days were missed, which breaks the chain
+        let stats = StreakStats::for_task(&task, clock.today(&rules), &rules, &[]);
+        assert_eq!(stats.current_length(), 1);
+
+        // Excusing two of them (one for this task alone) still leaves one
+        let day = |days: i64| clock.today(&rules).naive_local() - chrono::Duration::days(days);
+        task.add_pause(Pause::new(day(2), day(2), Some("sick".to_string())).unwrap());
+        let vacation = [Pause::new(day(3), day(3), None).unwrap()];
+        let stats = StreakStats::for_task(&task, clock.today(&rules), &rules, &vacation);
+        assert_eq!(stats.current_length(), 1);
+        assert_eq!(stats.due_days, 4);
+
+        // Excusing all of them leaves the chain unbroken
+        let vacation = [Pause::new(day(4), day(3), None).unwrap()];
+        let stats = StreakStats::for_task(&task, clock.today(&rules), &rules, &vacation);
+        assert_eq!(stats.current_length(), 3);
+        assert_eq!(stats.completion_rate(), Some(1.0));
+    }
+}
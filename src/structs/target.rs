@@ -0,0 +1,200 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How much of something needs to be done each day for a task to count as completed (e.g. 20
+/// pages, or 2 L). Tasks without one are completed by a single completion.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Target {
+    /// The amount needed each day
+    amount: f64,
+    /// What the amount is measured in (e.g. `pages`), which may be empty
+    unit: String,
+}
+
+impl Target {
+    /// Create a target of `amount` (which must be positive) measured in `unit`
+    pub fn new(amount: f64, unit: &str) -> Result<Target, String> {
+        check_amount(amount)?;
+
+        Ok(Target {
+            amount,
+            unit: unit.trim().to_string(),
+        })
+    }
+
+    /// Get the amount needed each day
+    pub fn amount(&self) -> f64 {
+        self.amount
+    }
+
+    /// Get what the amount is measured in (which may be empty)
+    pub fn unit(&self) -> &str {
+        &self.unit
+    }
+
+    /// Returns true if this target makes sense (e.g. after being edited by hand)
+    pub fn is_valid(&self) -> bool {
+        check_amount(self.amount).is_ok()
+    }
+}
+
+/// Shown as an amount followed by the unit, e.g. `20 pages`
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format_amount(self.amount))?;
+        if !self.unit.is_empty() {
+            write!(f, " {}", self.unit)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses an amount, optionally followed by a unit, with or without a space between them (e.g.
+/// `20 pages`, `2L` or `8000`)
+impl std::str::FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+        let (amount, unit) = s.split_at(split);
+
+        Target::new(parse_amount(amount)?, unit)
+    }
+}
+
+/// Progress made toward a task's target on some day
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    /// The total amount recorded that day
+    pub amount: f64,
+    /// The target which applied that day
+    pub target: Target,
+}
+
+impl Progress {
+    /// Returns true if enough was done to meet the target
+    pub fn is_met(&self) -> bool {
+        self.amount >= self.target.amount
+    }
+
+    /// Returns true if something was done, but not enough to meet the target
+    pub fn is_partial(&self) -> bool {
+        self.amount > 0.0 && !self.is_met()
+    }
+
+    /// How much of the target was met, from 0 to 1
+    pub fn fraction(&self) -> f64 {
+        (self.amount / self.target.amount).clamp(0.0, 1.0)
+    }
+}
+
+/// Shown as the amount out of the target, e.g. `12/20 pages`
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", format_amount(self.amount), self.target)
+    }
+}
+
+/// Parse an amount entered by the user, which must be a positive number
+pub fn parse_amount(s: &str) -> Result<f64, String> {
+    let amount = s
+        .trim()
+        .parse::<f64>()
+        .map_err(|_e| format!("expected an amount, got \"{}\"", s.trim()))?;
+    check_amount(amount)?;
+
+    Ok(amount)
+}
+
+/// Check that `amount` can be recorded or used as a target
+fn check_amount(amount: f64) -> Result<(), String> {
+    if amount.is_finite() && amount > 0.0 {
+        Ok(())
+    } else {
+        Err(format!("expected a positive amount, got {}", amount))
+    }
+}
+
+/// Format an amount without trailing zeros, to at most two decimal places (e.g. `20`, or `1.25`)
+pub fn format_amount(amount: f64) -> String {
+    let formatted = format!("{:.2}", amount);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_amount, parse_amount, Progress, Target};
+
+    #[test]
+    fn parse_targets() {
+        let target: Target = "20 pages".parse().unwrap();
+        assert_eq!(target.amount(), 20.0);
+        assert_eq!(target.unit(), "pages");
+
+        let target: Target = "2L".parse().unwrap();
+        assert_eq!(target.amount(), 2.0);
+        assert_eq!(target.unit(), "L");
+        assert_eq!(target.to_string(), "2 L");
+
+        let target: Target = " 8000 ".parse().unwrap();
+        assert_eq!(target.unit(), "");
+        assert_eq!(target.to_string(), "8000");
+
+        assert!("pages".parse::<Target>().is_err());
+        assert!("0 pages".parse::<Target>().is_err());
+        assert!("-5 pages".parse::<Target>().is_err());
+    }
+
+    #[test]
+    fn amounts() {
+        assert_eq!(parse_amount("0.5"), Ok(0.5));
+        assert!(parse_amount("0").is_err());
+        assert!(parse_amount("lots").is_err());
+        assert!(parse_amount("inf").is_err());
+
+        assert_eq!(format_amount(20.0), "20");
+        assert_eq!(format_amount(0.1 + 0.2), "0.3");
+        assert_eq!(format_amount(1.25), "1.25");
+    }
+
+    #[test]
+    fn progress() {
+        let target: Target = "20 pages".parse().unwrap();
+        let progress = |amount| Progress {
+            amount,
+            target: target.clone(),
+        };
+
+        assert!(!progress(0.0).is_partial());
+        assert!(progress(12.0).is_partial());
+        assert_eq!(progress(12.0).to_string(), "12/20 pages");
+        assert_eq!(progress(12.0).fraction(), 0.6);
+        assert!(progress(20.0).is_met());
+        assert!(!progress(25.0).is_partial());
+        assert_eq!(progress(25.0).fraction(), 1.0);
+    }
+}
@@ -20,36 +20,154 @@ use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt;
 
+use super::dayrules::local_date;
+use super::target::format_amount;
+use super::transfer::Additions;
+use super::DayRules;
+use super::Pause;
+use super::Schedule;
+use super::{Progress, Target};
+
 /// A remark on some task. It's used in two ways:
 ///
 /// 1. associated with a `Completion` (this can only be done when completing the task)
 /// 2. associated with the `Task` on some given day
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Remark {
     /// Timestamp for when remark was made
     datetime: DateTime<Utc>,
+    /// Offset from UTC (in seconds) of the timezone the remark was made in, if it's known (see
+    /// `Completion`)
+    #[serde(default)]
+    offset: Option<i32>,
     /// The remark itself
     remark: String,
 }
 
+impl Remark {
+    /// Create a remark made at `datetime`, in a timezone with the given `offset` if it's known
+    /// (e.g. when importing one)
+    pub(crate) fn new(
+        datetime: DateTime<Utc>,
+        offset: Option<FixedOffset>,
+        remark: String,
+    ) -> Remark {
+        Remark {
+            datetime,
+            offset: offset.map(|offset| offset.local_minus_utc()),
+            remark,
+        }
+    }
+
+    /// Get the timestamp for when this remark was made
+    pub fn datetime(&self) -> DateTime<Utc> {
+        self.datetime
+    }
+
+    /// Get the offset from UTC of the timezone this remark was made in, if it's known
+    pub fn offset(&self) -> Option<FixedOffset> {
+        self.offset.and_then(FixedOffset::east_opt)
+    }
+
+    /// Get the day this remark was made on
+    pub fn day(&self, rules: &DayRules) -> NaiveDate {
+        recorded_day(self.datetime, self.offset(), rules)
+    }
+
+    /// Get the text of this remark
+    pub fn remark(&self) -> &str {
+        &self.remark
+    }
+}
+
 /// Represents a `Task` being completed on a particular day.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Completion {
     /// Date and time at which this completion was recorded
     datetime: DateTime<Utc>,
 
+    /// Offset from UTC (in seconds) of the timezone this completion was recorded in, so that it
+    /// stays on the same day wherever chain is run later. Completions recorded before this was
+    /// kept don't have one, and are placed in the current local timezone instead.
+    #[serde(default)]
+    offset: Option<i32>,
+
     /// User can make an optional remark when marking a task as complete, later remarks are closer
     /// to the end of the list
     remark: Option<Remark>,
+
+    /// How much was done, for tasks with a target (several completions on the same day add up).
+    /// A completion without an amount meets the target by itself.
+    #[serde(default)]
+    amount: Option<f64>,
+}
+
+impl Completion {
+    /// Create a completion recorded at `datetime`, in a timezone with the given `offset` if it's
+    /// known (e.g. when importing one)
+    pub(crate) fn new(
+        datetime: DateTime<Utc>,
+        offset: Option<FixedOffset>,
+        remark: Option<Remark>,
+    ) -> Completion {
+        Completion {
+            datetime,
+            offset: offset.map(|offset| offset.local_minus_utc()),
+            remark,
+            amount: None,
+        }
+    }
+
+    /// Record how much was done with this completion
+    pub(crate) fn with_amount(mut self, amount: Option<f64>) -> Completion {
+        self.amount = amount;
+        self
+    }
+
+    /// Get the timestamp at which this completion was recorded
+    pub fn datetime(&self) -> DateTime<Utc> {
+        self.datetime
+    }
+
+    /// Get the offset from UTC of the timezone this completion was recorded in, if it's known
+    pub fn offset(&self) -> Option<FixedOffset> {
+        self.offset.and_then(FixedOffset::east_opt)
+    }
+
+    /// Get the time at which this completion was recorded, as it was on the clock where it was
+    /// recorded (or in the current local timezone, for completions which don't have an offset)
+    pub fn local_datetime(&self) -> DateTime<FixedOffset> {
+        recorded_datetime(self.datetime, self.offset())
+    }
+
+    /// Get the day this completion counts for
+    pub fn day(&self, rules: &DayRules) -> NaiveDate {
+        recorded_day(self.datetime, self.offset(), rules)
+    }
+
+    /// Get the remark made when completing the task (if any)
+    pub fn remark(&self) -> Option<&Remark> {
+        self.remark.as_ref()
+    }
+
+    /// Get how much was done with this completion, if an amount was recorded
+    pub fn amount(&self) -> Option<f64> {
+        self.amount
+    }
 }
 
 /// Represents the state of a task at some point in time (i.e. the user can change the
 /// description).
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskDetails {
     /// Timestamp of when these details described the Task
     revised: DateTime<Utc>,
 
+    /// Offset from UTC (in seconds) of the timezone the revision was made in, if it's known (see
+    /// `Completion`)
+    #[serde(default)]
+    offset: Option<i32>,
+
     /// A monotonically increasing revision ID
     revision_id: u64,
 
@@ -60,15 +178,139 @@ pub struct TaskDetails {
     /// time of day
     // TODO: find the best struct/library to represent this kind of value
     sync_time: Option<u32>, /* time of day */
+
+    /// Which days the task is due on
+    #[serde(default)]
+    schedule: Schedule,
+
+    /// None => a single completion completes the task for the day, else: how much needs to be
+    /// recorded each day
+    #[serde(default)]
+    target: Option<Target>,
 }
 
 impl TaskDetails {
+    /// Create a revision made at `revised`, in a timezone with the given `offset` if it's known
+    /// (e.g. when importing one)
+    pub(crate) fn revision(
+        revised: DateTime<Utc>,
+        offset: Option<FixedOffset>,
+        revision_id: u64,
+        description: String,
+        schedule: Schedule,
+    ) -> TaskDetails {
+        TaskDetails {
+            revised,
+            offset: offset.map(|offset| offset.local_minus_utc()),
+            revision_id,
+            description,
+            sync_time: None,
+            schedule,
+            target: None,
+        }
+    }
+
+    /// Set the daily target of this revision
+    pub(crate) fn with_target(mut self, target: Option<Target>) -> TaskDetails {
+        self.target = target;
+        self
+    }
+
     /// Get a reference to the `description` string for this `Task`
     pub fn description(&self) -> &String {
         &self.description
     }
+
+    /// Get a reference to the `Schedule` for this `Task`
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    /// Get the daily target for this `Task`, if it has one
+    pub fn target(&self) -> Option<&Target> {
+        self.target.as_ref()
+    }
+
+    /// Get the timestamp from which these details described the `Task`
+    pub fn revised(&self) -> DateTime<Utc> {
+        self.revised
+    }
+
+    /// Get the offset from UTC of the timezone this revision was made in, if it's known
+    pub fn offset(&self) -> Option<FixedOffset> {
+        self.offset.and_then(FixedOffset::east_opt)
+    }
+
+    /// Get the day from which these details described the `Task`
+    pub fn day(&self, rules: &DayRules) -> NaiveDate {
+        recorded_day(self.revised, self.offset(), rules)
+    }
+
+    /// Get the ID of this revision
+    pub fn revision_id(&self) -> u64 {
+        self.revision_id
+    }
+}
+
+/// Get `datetime` as it was on the clock where it was recorded, given that clock's `offset` if
+/// it's known (otherwise, it's placed in the current local timezone)
+fn recorded_datetime(
+    datetime: DateTime<Utc>,
+    offset: Option<FixedOffset>,
+) -> DateTime<FixedOffset> {
+    let offset = offset.unwrap_or_else(|| datetime.with_timezone(&Local).offset().fix());
+
+    datetime.with_timezone(&offset)
+}
+
+/// Get the day (as `rules` define days) on which something was recorded at `datetime`, on a
+/// clock with the given `offset` if it's known. Recording the offset keeps things on the same day
+/// wherever chain is run later.
+fn recorded_day(
+    datetime: DateTime<Utc>,
+    offset: Option<FixedOffset>,
+    rules: &DayRules,
+) -> NaiveDate {
+    rules
+        .day_of(&recorded_datetime(datetime, offset))
+        .naive_local()
+}
+
+/// Whether a task is something to do, or something to avoid
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum TaskKind {
+    /// Completing the task extends the chain, and a due day without a completion breaks it
+    #[default]
+    Do,
+    /// A due day without a slip extends the chain (once the day is over), and logging a slip
+    /// breaks it
+    Avoid,
+}
+
+impl fmt::Display for TaskKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TaskKind::Do => f.write_str("do"),
+            TaskKind::Avoid => f.write_str("avoid"),
+        }
+    }
 }
 
+impl std::str::FromStr for TaskKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "do" => Ok(TaskKind::Do),
+            "avoid" => Ok(TaskKind::Avoid),
+            _ => Err(format!("unknown kind of task \"{}\" (use do or avoid)", s)),
+        }
+    }
+}
+
+/// Longest slug a task can have
+pub const MAX_SLUG_LENGTH: usize = 32;
+
 /// Errors for `Task` operations
 // TODO: this mixes operations on both `Task` and `TaskListing`, and should probably be cleaned up.
 #[derive(Debug, PartialEq)]
@@ -83,36 +325,124 @@ pub enum TaskError {
     RedundantMove,
     /// Failed to store a TaskListing to disk
     StoreFailed,
+    /// Failed to reload a TaskListing from disk
+    LoadFailed,
+    /// Task data was stored in a format version newer than this version of chain understands
+    UnsupportedVersion(u32),
+    /// Another process changed the TaskListing on disk, and the operation no longer applies to
+    /// it (the reason is given by the inner error)
+    Conflict(Box<TaskError>),
+    /// User tried to complete a task on a day which hasn't happened yet
+    DateInFuture,
+    /// User tried to complete a task on a day before it was created
+    DateBeforeCreation,
+    /// User tried to un-complete a task on a day it wasn't completed
+    NotCompleted,
+    /// User tried to archive a task which was already archived
+    AlreadyArchived,
+    /// User tried to unarchive a task which wasn't archived
+    NotArchived,
+    /// User tried to undo, but there were no operations left to undo
+    NothingToUndo,
+    /// User tried to redo, but there were no undone operations left to redo
+    NothingToRedo,
+    /// User tried to give a task a slug which isn't allowed (see `Task::check_slug()`)
+    InvalidSlug(String),
+    /// User tried to give a task a slug which another task already uses
+    SlugTaken(String),
+    /// User tried to record an amount for a task which has no target (the amount is given)
+    NoTarget(f64),
+    /// User tried to record an amount which isn't positive
+    InvalidAmount(f64),
+    /// User tried to give a task to avoid something it can't have (which is described)
+    NotForAvoid(&'static str),
+    /// User tried to pause a task from some day until an earlier one
+    InvalidPause(NaiveDate, NaiveDate),
+    /// User named a task in a way which matches more than one task equally well (each match is
+    /// given by its index and description)
+    Ambiguous(String, Vec<(usize, String)>),
 }
 
 impl fmt::Display for TaskError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            TaskError::MissingDescription => f.write_str("MissingDescription"),
-            TaskError::AlreadyCompleted => f.write_str("AlreadyCompleted"),
-            TaskError::NotFound => f.write_str("NotFound"),
-            TaskError::RedundantMove => f.write_str("RedundantMove"),
-            TaskError::StoreFailed => f.write_str("StoreFailed"),
+            TaskError::MissingDescription => f.write_str("Task had no description"),
+            TaskError::AlreadyCompleted => f.write_str("Task was already completed"),
+            TaskError::NotFound => f.write_str("Couldn't find task"),
+            TaskError::RedundantMove => f.write_str("Can't move task to its own index"),
+            TaskError::StoreFailed => f.write_str("Can't store task data to disk"),
+            TaskError::LoadFailed => f.write_str("Can't load task data from disk"),
+            TaskError::UnsupportedVersion(version) => write!(
+                f,
+                "Task data is in format version {}, which needs a newer version of chain",
+                version
+            ),
+            TaskError::Conflict(e) => write!(
+                f,
+                "Task data was changed by another process, and this change no longer applies ({})",
+                e
+            ),
+            TaskError::DateInFuture => f.write_str("Date is in the future"),
+            TaskError::DateBeforeCreation => f.write_str("Date is before the task was created"),
+            TaskError::NotCompleted => f.write_str("Task wasn't completed on that date"),
+            TaskError::AlreadyArchived => f.write_str("Task was already archived"),
+            TaskError::NotArchived => f.write_str("Task isn't archived"),
+            TaskError::NothingToUndo => f.write_str("Nothing to undo"),
+            TaskError::NothingToRedo => f.write_str("Nothing to redo"),
+            TaskError::InvalidSlug(slug) => write!(
+                f,
+                "\"{}\" isn't a valid slug (use up to {} lowercase letters, digits and dashes, \
+                 including at least one letter)",
+                slug, MAX_SLUG_LENGTH
+            ),
+            TaskError::SlugTaken(slug) => {
+                write!(f, "Slug \"{}\" is already used by another task", slug)
+            }
+            TaskError::NoTarget(amount) => write!(
+                f,
+                "Task has no target, so an amount ({}) can't be recorded for it",
+                format_amount(*amount)
+            ),
+            TaskError::InvalidAmount(amount) => {
+                write!(f, "Amount must be positive, not {}", amount)
+            }
+            TaskError::NotForAvoid(what) => write!(f, "Tasks to avoid can't have {}", what),
+            TaskError::InvalidPause(from, to) => {
+                write!(f, "A pause can't end ({}) before it starts ({})", to, from)
+            }
+            TaskError::Ambiguous(selector, matches) => {
+                write!(f, "\"{}\" matches more than one task:", selector)?;
+                for (index, description) in matches {
+                    write!(f, "\n  {}: {}", index, description)?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
-impl Error for TaskError {
-    fn description(&self) -> &str {
-        match self {
-            TaskError::MissingDescription => "Task had no description",
-            TaskError::AlreadyCompleted => "Task was already completed",
-            TaskError::NotFound => "Couldn't find task",
-            TaskError::RedundantMove => "Can't move task to its own index",
-            TaskError::StoreFailed => "Can't store task data to disk",
-        }
-    }
-}
+impl Error for TaskError {}
 
 /// Represents a task. It includes a history of revisions to task details, as well as a list of
 /// dates and times on which the task was completed.
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// Revisions are kept in the order they were made, so the first revision describes the task as it
+/// was created, and the last revision describes it as it is now.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Task {
+    /// Identifies the task, and never changes (unlike its index or description). Assigned by the
+    /// `TaskListing` the task is added to.
+    #[serde(default)]
+    id: String,
+
+    /// An optional name chosen by the user, which can be used instead of the ID
+    #[serde(default)]
+    slug: Option<String>,
+
+    /// Whether this Task is something to do or something to avoid, which never changes
+    #[serde(default)]
+    kind: TaskKind,
+
     /// A record of revisions made to the TaskDetails for this Task
     detail_history: Vec<TaskDetails>,
 
@@ -122,146 +452,1083 @@ pub struct Task {
     /// A record of remarks made on tasks
     #[serde(default = "Vec::new")]
     remarks: Vec<Remark>,
+
+    /// Ranges of days on which this Task is excused, oldest first
+    #[serde(default)]
+    pauses: Vec<Pause>,
+
+    /// None => task is active, else: when the task was archived (it's no longer listed, but its
+    /// history is kept)
+    #[serde(default)]
+    archived: Option<DateTime<Utc>>,
+
+    /// Offset from UTC (in seconds) of the timezone the task was archived in, if it's known (see
+    /// `Completion`)
+    #[serde(default)]
+    archived_offset: Option<i32>,
 }
 
-impl Default for Task {
-    fn default() -> Self {
+impl Task {
+    /// Create a new Task, created at `created` (in the timezone that time is in)
+    pub fn new(description: String, created: DateTime<FixedOffset>) -> Task {
+        let details = TaskDetails::new(created, 0, description);
+
         Task {
-            detail_history: Vec::new(),
+            id: String::new(),
+            slug: None,
+            kind: TaskKind::Do,
+            detail_history: vec![details],
             completions: Vec::new(),
             remarks: Vec::new(),
+            pauses: Vec::new(),
+            archived: None,
+            archived_offset: None,
         }
     }
-}
-
-impl Task {
-    /// Create a new Task
-    pub fn new(description: String) -> Task {
-        let details = TaskDetails::new(None, 0, description);
-        let mut detail_history = Vec::new();
-        detail_history.push(details);
 
+    /// Put a Task back together from its history (e.g. when importing one)
+    pub(crate) fn from_parts(
+        detail_history: Vec<TaskDetails>,
+        completions: Vec<Completion>,
+        remarks: Vec<Remark>,
+        archived: Option<(DateTime<Utc>, Option<FixedOffset>)>,
+    ) -> Task {
         Task {
+            id: String::new(),
+            slug: None,
+            kind: TaskKind::Do,
             detail_history,
-            completions: Vec::new(),
-            remarks: Vec::new(),
+            completions,
+            remarks,
+            pauses: Vec::new(),
+            archived: archived.map(|(datetime, _)| datetime),
+            archived_offset: archived
+                .and_then(|(_, offset)| offset)
+                .map(|offset| offset.local_minus_utc()),
+        }
+    }
+
+    /// Make this Task one of the given kind (it's a task to do unless this is used)
+    pub(crate) fn with_kind(mut self, kind: TaskKind) -> Task {
+        self.kind = kind;
+        self
+    }
+
+    /// Get whether this Task is something to do or something to avoid
+    pub fn kind(&self) -> TaskKind {
+        self.kind
+    }
+
+    /// Get the stable ID of this Task (empty until it's added to a `TaskListing`)
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub(crate) fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
+
+    /// Get the slug chosen for this Task, if there is one
+    pub fn slug(&self) -> Option<&str> {
+        self.slug.as_deref()
+    }
+
+    /// Set (or clear) the slug of this Task, returning the previous one. The slug should already
+    /// have been checked with `check_slug()`.
+    pub fn set_slug(&mut self, slug: Option<String>) -> Option<String> {
+        std::mem::replace(&mut self.slug, slug)
+    }
+
+    /// Check that `slug` can be used as a slug: it's made of lowercase ASCII letters, digits and
+    /// dashes, and contains at least one letter (so it can't be mistaken for an index)
+    pub fn check_slug(slug: &str) -> Result<(), TaskError> {
+        let valid = !slug.is_empty()
+            && slug.len() <= MAX_SLUG_LENGTH
+            && slug
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            && slug.chars().any(|c| c.is_ascii_lowercase());
+
+        if valid {
+            Ok(())
+        } else {
+            Err(TaskError::InvalidSlug(slug.to_string()))
         }
     }
 
     /// Get the current details for this Task
     pub fn details(&self) -> Option<&TaskDetails> {
-        self.detail_history.first()
+        self.detail_history.last()
+    }
+
+    /// Get the details which described this Task on the given date (i.e. the latest revision made
+    /// on or before that date, as `rules` define days, or the original details if the date is
+    /// before the task was created)
+    pub fn details_on(&self, date: Date<Local>, rules: &DayRules) -> Option<&TaskDetails> {
+        self.detail_history
+            .iter()
+            .rev()
+            .find(|details| details.day(rules) <= date.naive_local())
+            .or_else(|| self.detail_history.first())
+    }
+
+    /// Get an iterator over every revision of the details for this Task, oldest first
+    pub fn revisions(&self) -> std::slice::Iter<'_, TaskDetails> {
+        self.detail_history.iter()
+    }
+
+    /// Get every completion of this Task, oldest first
+    pub fn completions(&self) -> &[Completion] {
+        &self.completions
+    }
+
+    /// Get every general remark made on this Task (i.e. not those made on completion)
+    pub fn remarks(&self) -> &[Remark] {
+        &self.remarks
+    }
+
+    /// Get every range of days on which this Task is excused, oldest first
+    pub fn pauses(&self) -> &[Pause] {
+        &self.pauses
     }
 
+    /// Get the current description of this Task
     pub fn description(&self) -> &String {
         self.details().unwrap().description()
     }
 
-    /// Returns true if task existed on the given date
-    pub fn existed_on(&self, date: Date<Local>) -> bool {
-        let dt_cmp: DateTime<Local> = Local
-            .ymd(date.year(), date.month(), date.day())
-            .and_hms(0, 0, 0);
+    /// Get the current schedule for this Task
+    pub fn schedule(&self) -> &Schedule {
+        self.details().unwrap().schedule()
+    }
 
-        let dt_cmp_utc: DateTime<Utc> = dt_cmp.with_timezone(&Utc);
-        let dt_created_utc: DateTime<Utc> = self.created().unwrap();
+    /// Get the current daily target for this Task, if it has one
+    pub fn target(&self) -> Option<&Target> {
+        self.details().unwrap().target()
+    }
 
-        let date_cmp_utc: Date<Utc> = dt_cmp_utc.date();
-        let date_created_utc: Date<Utc> = dt_created_utc.date();
+    /// Add a new revision to the details of this Task, changing its description and/or schedule
+    /// as of `now`. Returns the ID of the new revision.
+    pub fn revise(
+        &mut self,
+        description: Option<String>,
+        schedule: Option<Schedule>,
+        now: DateTime<FixedOffset>,
+    ) -> Result<u64, TaskError> {
+        let mut details: TaskDetails = self.details().ok_or(TaskError::NotFound)?.clone();
 
-        if date_cmp_utc < date_created_utc {
-            return false;
+        if let Some(description) = description {
+            if description.chars().count() == 0 {
+                return Err(TaskError::MissingDescription);
+            }
+            details.description = description;
+        }
+
+        if let Some(schedule) = schedule {
+            // Slips can't count toward a quota
+            if self.kind == TaskKind::Avoid {
+                if let Schedule::TimesPerWeek(_) = schedule {
+                    return Err(TaskError::NotForAvoid("a schedule of some times per week"));
+                }
+            }
+            details.schedule = schedule;
         }
 
-        true
+        Ok(self.push_revision(details, now))
     }
 
-    /// Returns true if completed on the given date
-    pub fn completed_on(&self, date: Date<Local>) -> bool {
-        for completion in &self.completions {
-            let completion_date_utc: DateTime<Utc> = completion.datetime;
-            let completion_date_local: DateTime<Local> = completion_date_utc.with_timezone(&Local);
+    /// Set (or remove) the amount which needs to be recorded each day for this Task to count as
+    /// completed, from `now` onward. Returns the ID of the new revision.
+    pub fn set_target(
+        &mut self,
+        target: Option<Target>,
+        now: DateTime<FixedOffset>,
+    ) -> Result<u64, TaskError> {
+        if self.kind == TaskKind::Avoid && target.is_some() {
+            return Err(TaskError::NotForAvoid("a target"));
+        }
+
+        let mut details: TaskDetails = self.details().ok_or(TaskError::NotFound)?.clone();
+        details.target = target;
+
+        Ok(self.push_revision(details, now))
+    }
+
+    /// Add `details` as the latest revision, made at `now`, returning its ID
+    fn push_revision(&mut self, mut details: TaskDetails, now: DateTime<FixedOffset>) -> u64 {
+        details.revision_id += 1;
+        details.revised = now.with_timezone(&Utc);
+        details.offset = Some(now.offset().local_minus_utc());
 
-            if date == completion_date_local.date() {
-                return true;
+        let revision_id = details.revision_id;
+        self.detail_history.push(details);
+
+        revision_id
+    }
+
+    /// Remove the latest revision of the details of this Task, returning it. The original details
+    /// can't be removed.
+    pub fn remove_revision(&mut self, revision_id: u64) -> Result<TaskDetails, TaskError> {
+        match self.detail_history.last() {
+            Some(details)
+                if details.revision_id == revision_id && self.detail_history.len() > 1 =>
+            {
+                Ok(self.detail_history.pop().unwrap())
             }
+            _ => Err(TaskError::NotFound),
         }
+    }
 
-        false
+    /// Put back a revision which was previously removed (keeping revisions in order)
+    pub fn restore_revision(&mut self, details: TaskDetails) {
+        let position = self
+            .detail_history
+            .iter()
+            .position(|d| d.revision_id > details.revision_id)
+            .unwrap_or(self.detail_history.len());
+
+        self.detail_history.insert(position, details);
+    }
+
+    /// Change which days this Task is due on (from `now` onward). Returns the ID of the new
+    /// revision.
+    pub fn set_schedule(
+        &mut self,
+        schedule: Schedule,
+        now: DateTime<FixedOffset>,
+    ) -> Result<u64, TaskError> {
+        self.revise(None, Some(schedule), now)
+    }
+
+    /// Returns true if the task has been archived
+    pub fn is_archived(&self) -> bool {
+        self.archived.is_some()
+    }
+
+    /// Get the timestamp at which the task was archived (if it was)
+    pub fn archived(&self) -> Option<DateTime<Utc>> {
+        self.archived
+    }
+
+    /// Get the offset from UTC of the timezone the task was archived in, if it was archived and
+    /// the offset is known
+    pub fn archived_offset(&self) -> Option<FixedOffset> {
+        self.archived_offset.and_then(FixedOffset::east_opt)
+    }
+
+    /// Get the day the task was archived on (if it was)
+    pub fn archived_on(&self, rules: &DayRules) -> Option<NaiveDate> {
+        self.archived
+            .map(|archived| recorded_day(archived, self.archived_offset(), rules))
     }
 
-    /// Optionally returns a `DateTime<Local>` for when this task was completed today (if it was),
-    /// otherwise `None`
+    /// Set (or clear) the timestamp at which the task was archived, along with the offset of the
+    /// timezone it was archived in if that's known. Returns the previous values.
+    pub fn set_archived(
+        &mut self,
+        archived: Option<DateTime<Utc>>,
+        offset: Option<FixedOffset>,
+    ) -> (Option<DateTime<Utc>>, Option<FixedOffset>) {
+        let previous = (self.archived, self.archived_offset());
+        self.archived = archived;
+        self.archived_offset = offset.map(|offset| offset.local_minus_utc());
+
+        previous
+    }
+
+    /// Excuse this Task on the days of `pause` (keeping pauses in order of their first day)
+    pub fn add_pause(&mut self, pause: Pause) {
+        let position = self
+            .pauses
+            .iter()
+            .position(|p| p.from() > pause.from())
+            .unwrap_or(self.pauses.len());
+
+        self.pauses.insert(position, pause);
+    }
+
+    /// Remove a pause which is the same as `pause`, returning it
+    pub fn remove_pause(&mut self, pause: &Pause) -> Result<Pause, TaskError> {
+        match self.pauses.iter().rposition(|p| p == pause) {
+            Some(position) => Ok(self.pauses.remove(position)),
+            None => Err(TaskError::NotFound),
+        }
+    }
+
+    /// Get the pause which excuses this Task on the given date, if there is one: either one of its
+    /// own, or one of the `global` pauses which apply to every task
+    pub fn pause_on<'a>(&'a self, date: Date<Local>, global: &'a [Pause]) -> Option<&'a Pause> {
+        self.pauses
+            .iter()
+            .chain(global.iter())
+            .find(|pause| pause.covers(date))
+    }
+
+    /// Returns true if task existed on the given date
+    pub fn existed_on(&self, date: Date<Local>, rules: &DayRules) -> bool {
+        date >= self.created_on(rules)
+    }
+
+    /// Returns true if the task was due on the given date (i.e. not completing it on that date
+    /// would break the chain)
+    pub fn due_on(&self, date: Date<Local>, rules: &DayRules) -> bool {
+        if !self.existed_on(date, rules) {
+            return false;
+        }
+
+        // Once archived, a task is never due again
+        if let Some(archived) = self.archived_on(rules) {
+            if date.naive_local() > archived {
+                return false;
+            }
+        }
+
+        let created: NaiveDate = self.created_on(rules).naive_local();
+
+        // The schedule may have changed since the given date
+        let schedule: &Schedule = self.details_on(date, rules).unwrap().schedule();
+
+        schedule.is_due(
+            date.naive_local(),
+            created,
+            rules,
+            |naive_date: NaiveDate| match Local.from_local_date(&naive_date).single() {
+                Some(date) => self.completed_on(date, rules),
+                None => false,
+            },
+        )
+    }
+
+    /// Returns true if completed on the given date (as `rules` define when each day starts).
+    /// Tasks to avoid are never completed (see `slipped_on()`).
+    pub fn completed_on(&self, date: Date<Local>, rules: &DayRules) -> bool {
+        self.kind == TaskKind::Do && self.completed_on_day(date.naive_local(), rules)
+    }
+
+    /// Returns true if this is a task to avoid, and a slip was logged on the given date
+    pub fn slipped_on(&self, date: Date<Local>, rules: &DayRules) -> bool {
+        self.slipped_today(date, rules).is_some()
+    }
+
+    /// Returns true if the completions which count for `day` complete the task (i.e. there's at
+    /// least one, and they meet the target if there was one that day)
+    fn completed_on_day(&self, day: NaiveDate, rules: &DayRules) -> bool {
+        self.completed_at(day, rules).is_some()
+    }
+
+    /// Get the completion which completed the task on `day` (the one which met the target, if
+    /// there was one that day), if it was completed
+    fn completed_at(&self, day: NaiveDate, rules: &DayRules) -> Option<&Completion> {
+        let target: Option<f64> = self.target_on_day(day, rules).map(Target::amount);
+        let mut total: f64 = 0.0;
+
+        self.completions
+            .iter()
+            .filter(|completion| completion.day(rules) == day)
+            .find(|completion| match (target, completion.amount) {
+                (Some(target), Some(amount)) => {
+                    total += amount;
+                    total >= target
+                }
+                _ => true,
+            })
+    }
+
+    /// Get the target which applied on `day`, if there was one
+    fn target_on_day(&self, day: NaiveDate, rules: &DayRules) -> Option<&Target> {
+        self.details_on(local_date(day), rules)
+            .and_then(|details| details.target())
+    }
+
+    /// Get the progress made toward the target on the given date, or `None` if there was no
+    /// target that day. A completion without an amount meets the target by itself.
+    pub fn progress_on(&self, date: Date<Local>, rules: &DayRules) -> Option<Progress> {
+        let day: NaiveDate = date.naive_local();
+        let target: &Target = self.target_on_day(day, rules)?;
+
+        let amount: f64 = self
+            .completions
+            .iter()
+            .filter(|completion| completion.day(rules) == day)
+            .map(|completion| completion.amount.unwrap_or_else(|| target.amount()))
+            .fold(0.0, |total, amount| total + amount);
+
+        Some(Progress {
+            amount,
+            target: target.clone(),
+        })
+    }
+
+    /// Optionally returns a `DateTime<Local>` for when this task was completed `today` (if it
+    /// was), otherwise `None`. For tasks with a target, this is when the target was met.
     // TODO: this should be `completed_today_at`, and another function `completed_today` should
     // return bool
-    pub fn completed_today(&self) -> Option<DateTime<Local>> {
-        let today: Date<Local> = Local::today();
-        for completion in &self.completions {
-            // Note to self: if you want to do timezone conversion with chrono, you have to convert
-            // as a DateTime first, then get the dates with .date()
-            let completion_dt_utc: DateTime<Utc> = completion.datetime;
-            let completion_dt_local: DateTime<Local> = completion_dt_utc.with_timezone(&Local);
-
-            if today == completion_dt_local.date() {
-                return Some(completion_dt_local);
-            }
+    pub fn completed_today(&self, today: Date<Local>, rules: &DayRules) -> Option<DateTime<Local>> {
+        if self.kind == TaskKind::Avoid {
+            return None;
         }
 
-        None
+        self.completed_at(today.naive_local(), rules)
+            .map(|completion| completion.datetime.with_timezone(&Local))
     }
 
-    /// Add a remark to a completed task (note: this isn't associated with a `Completion`)
-    pub fn add_remark(&mut self, remark: String) -> Result<(), TaskError> {
-        self.remarks.push(Remark {
-            datetime: Utc::now(),
-            remark,
-        });
+    /// For tasks to avoid, optionally returns a `DateTime<Local>` for when the first slip on
+    /// `today` was logged (if there was one), otherwise `None`
+    pub fn slipped_today(&self, today: Date<Local>, rules: &DayRules) -> Option<DateTime<Local>> {
+        if self.kind == TaskKind::Do {
+            return None;
+        }
+
+        self.completions
+            .iter()
+            .find(|completion| completion.day(rules) == today.naive_local())
+            .map(|completion| completion.datetime.with_timezone(&Local))
+    }
+
+    /// Get every remark made on the given date, whether it was made on completion or not, oldest
+    /// first
+    pub fn remarks_on(&self, date: Date<Local>, rules: &DayRules) -> Vec<&Remark> {
+        let mut remarks: Vec<&Remark> = self
+            .completions
+            .iter()
+            .filter_map(|completion| completion.remark.as_ref())
+            .chain(self.remarks.iter())
+            .filter(|remark| remark.day(rules) == date.naive_local())
+            .collect();
+        remarks.sort_by_key(|remark| remark.datetime);
 
-        Ok(())
+        remarks
     }
 
-    /// Mark a task as complete for today
-    pub fn mark_complete(&mut self, remark: &Option<String>) -> Result<(), TaskError> {
-        if self.completed_today().is_some() {
-            return Err(TaskError::AlreadyCompleted);
+    /// Add a remark made at `now` to a completed task (note: this isn't associated with a
+    /// `Completion`). Returns the timestamp of the new remark.
+    pub fn add_remark(
+        &mut self,
+        remark: String,
+        now: DateTime<FixedOffset>,
+    ) -> Result<DateTime<Utc>, TaskError> {
+        let remark = Remark::new(now.with_timezone(&Utc), Some(*now.offset()), remark);
+        let datetime = remark.datetime;
+        self.remarks.push(remark);
+
+        Ok(datetime)
+    }
+
+    /// Remove the remark made at `datetime`, returning it
+    pub fn remove_remark(&mut self, datetime: DateTime<Utc>) -> Result<Remark, TaskError> {
+        match self.remarks.iter().position(|r| r.datetime == datetime) {
+            Some(position) => Ok(self.remarks.remove(position)),
+            None => Err(TaskError::NotFound),
         }
+    }
 
-        let now = Utc::now();
+    /// Put back a remark which was previously removed (keeping remarks in chronological order)
+    pub fn restore_remark(&mut self, remark: Remark) {
+        let position = self
+            .remarks
+            .iter()
+            .position(|r| r.datetime > remark.datetime)
+            .unwrap_or(self.remarks.len());
 
-        let remark: Option<Remark> = if let Some(remark) = remark {
-            Some(Remark {
-                datetime: now,
-                remark: remark.to_string(),
-            })
+        self.remarks.insert(position, remark);
+    }
+
+    /// Mark a task as complete for today, or for some earlier `date` (e.g. if the user forgot to
+    /// record it), where it's currently `now`. The completion keeps the timezone `now` is in, so
+    /// it counts for the same day wherever it's looked at later. Returns the timestamp of the new
+    /// completion.
+    ///
+    /// For tasks with a target, `amount` records how much was done, which adds to whatever else
+    /// was recorded that day. Without an amount, the completion meets the target by itself.
+    ///
+    /// For tasks to avoid, this logs a slip instead (more than one can be logged on a day).
+    pub fn mark_complete(
+        &mut self,
+        remark: &Option<String>,
+        amount: Option<f64>,
+        date: Option<Date<Local>>,
+        rules: &DayRules,
+        now: DateTime<FixedOffset>,
+    ) -> Result<DateTime<Utc>, TaskError> {
+        let today: NaiveDate = rules.day_of(&now).naive_local();
+        let date: NaiveDate = date.map_or(today, |date| date.naive_local());
+
+        if date > today {
+            return Err(TaskError::DateInFuture);
+        }
+
+        if date < self.created_on(rules).naive_local() {
+            return Err(TaskError::DateBeforeCreation);
+        }
+
+        match amount {
+            Some(amount) if self.target_on_day(date, rules).is_none() => {
+                return Err(TaskError::NoTarget(amount));
+            }
+            Some(amount) if !amount.is_finite() || amount <= 0.0 => {
+                return Err(TaskError::InvalidAmount(amount));
+            }
+            // Partial amounts can be recorded whether or not the target has already been met
+            Some(_) => (),
+            None if self.kind == TaskKind::Do && self.completed_on_day(date, rules) => {
+                return Err(TaskError::AlreadyCompleted);
+            }
+            None => (),
+        }
+
+        // Backdated completions are recorded in the middle of the day, which is unambiguously on
+        // the right date
+        let datetime: DateTime<FixedOffset> = if date == today {
+            now
         } else {
-            None
+            rules.middle_of(&now.offset().from_local_date(&date).unwrap())
         };
 
-        self.completions.push(Completion {
-            datetime: now,
-            remark: remark,
+        let remark: Option<Remark> = remark.as_ref().map(|remark| {
+            Remark::new(now.with_timezone(&Utc), Some(*now.offset()), remark.clone())
         });
 
-        return Ok(());
+        self.restore_completion(
+            Completion::new(
+                datetime.with_timezone(&Utc),
+                Some(*datetime.offset()),
+                remark,
+            )
+            .with_amount(amount),
+        );
+
+        Ok(datetime.with_timezone(&Utc))
+    }
+
+    /// Remove the completion recorded on the given date (the latest one, if several amounts were
+    /// recorded), returning it
+    pub fn uncomplete(
+        &mut self,
+        date: Date<Local>,
+        rules: &DayRules,
+    ) -> Result<Completion, TaskError> {
+        match self
+            .completions
+            .iter()
+            .rposition(|c| c.day(rules) == date.naive_local())
+        {
+            Some(position) => Ok(self.completions.remove(position)),
+            None => Err(TaskError::NotCompleted),
+        }
+    }
+
+    /// Remove the completion of `amount` recorded at `datetime` (the latest one, if several
+    /// identical ones were recorded), returning it
+    pub fn remove_completion(
+        &mut self,
+        datetime: DateTime<Utc>,
+        amount: Option<f64>,
+    ) -> Result<Completion, TaskError> {
+        match self
+            .completions
+            .iter()
+            .rposition(|c| c.datetime == datetime && c.amount == amount)
+        {
+            Some(position) => Ok(self.completions.remove(position)),
+            None => Err(TaskError::NotFound),
+        }
+    }
+
+    /// Put back a completion which was previously removed (keeping completions in chronological
+    /// order)
+    pub fn restore_completion(&mut self, completion: Completion) {
+        let position = self
+            .completions
+            .iter()
+            .position(|c| c.datetime > completion.datetime)
+            .unwrap_or(self.completions.len());
+
+        self.completions.insert(position, completion);
+    }
+
+    /// Get the day on which the Task was first created
+    pub fn created_on(&self, rules: &DayRules) -> Date<Local> {
+        local_date(self.detail_history.first().unwrap().day(rules))
     }
 
     /// Get the timestamp at which the Task was first created
-    fn created(&self) -> Option<DateTime<Utc>> {
+    pub fn created(&self) -> Option<DateTime<Utc>> {
         // Look up the oldest revision for this task, and return its `revised` timestamp
-        match self.detail_history.last() {
-            Some(details) => Some(details.revised),
-            None => None,
+        self.detail_history.first().map(|details| details.revised)
+    }
+
+    /// Drop completions and remarks which count for days (as `rules` define them) before `date`,
+    /// keeping every revision (so that the task can still be recognised)
+    pub fn retain_since(&mut self, date: Date<Local>, rules: &DayRules) {
+        let date: NaiveDate = date.naive_local();
+        self.completions
+            .retain(|completion| completion.day(rules) >= date);
+        self.remarks.retain(|remark| remark.day(rules) >= date);
+    }
+
+    /// Add whatever `other` (a copy of this Task, e.g. from an import) has that this Task doesn't,
+    /// returning what was added.
+    ///
+    /// Revisions newer than this Task's latest revision are added. A completion is only added if
+    /// the Task wasn't already completed on that day (or, for completions with an amount and
+    /// slips, if there isn't already one of the same amount recorded at the same time), and a
+    /// remark or pause is only added if there isn't already an identical one.
+    pub fn merge(&mut self, other: Task, rules: &DayRules) -> Additions {
+        let mut additions = Additions::default();
+
+        let latest_revision = self.details().map(|details| details.revision_id);
+        for details in other.detail_history {
+            if latest_revision.is_none_or(|latest| details.revision_id > latest) {
+                self.detail_history.push(details);
+                additions.revisions += 1;
+            }
         }
+
+        for completion in other.completions {
+            let duplicate = if completion.amount.is_some() || self.kind == TaskKind::Avoid {
+                self.completions
+                    .iter()
+                    .any(|c| c.datetime == completion.datetime && c.amount == completion.amount)
+            } else {
+                self.completed_on_day(completion.day(rules), rules)
+            };
+            if !duplicate {
+                self.restore_completion(completion);
+                additions.completions += 1;
+            }
+        }
+
+        for pause in other.pauses {
+            if !self.pauses.contains(&pause) {
+                self.add_pause(pause);
+                additions.pauses += 1;
+            }
+        }
+
+        for remark in other.remarks {
+            if !self
+                .remarks
+                .iter()
+                .any(|r| r.datetime == remark.datetime && r.remark == remark.remark)
+            {
+                self.restore_remark(remark);
+                additions.remarks += 1;
+            }
+        }
+
+        additions
+    }
+
+    /// Check that this Task is consistent (e.g. after being edited by hand), returning a
+    /// description of each problem found
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.detail_history.is_empty() {
+            problems.push("has no revisions (so it has no description)".to_string());
+        }
+        if !self
+            .detail_history
+            .windows(2)
+            .all(|pair| pair[0].revision_id < pair[1].revision_id)
+        {
+            problems.push("revisions are out of order".to_string());
+        }
+        if !self
+            .completions
+            .windows(2)
+            .all(|pair| pair[0].datetime <= pair[1].datetime)
+        {
+            problems.push("completions are out of order".to_string());
+        }
+        if !self
+            .remarks
+            .windows(2)
+            .all(|pair| pair[0].datetime <= pair[1].datetime)
+        {
+            problems.push("remarks are out of order".to_string());
+        }
+        if self
+            .detail_history
+            .iter()
+            .any(|details| details.target.as_ref().is_some_and(|t| !t.is_valid()))
+        {
+            problems.push("has a target which isn't a positive amount".to_string());
+        }
+        if self.completions.iter().any(|c| {
+            c.amount
+                .is_some_and(|amount| !amount.is_finite() || amount <= 0.0)
+        }) {
+            problems.push("has a completion whose amount isn't positive".to_string());
+        }
+        if self.kind == TaskKind::Avoid {
+            if self.detail_history.iter().any(|d| d.target.is_some()) {
+                problems.push("is a task to avoid, but has a target".to_string());
+            }
+            if self
+                .detail_history
+                .iter()
+                .any(|d| matches!(d.schedule, Schedule::TimesPerWeek(_)))
+            {
+                problems.push("is a task to avoid, but is due some times per week".to_string());
+            }
+        }
+        if self.pauses.iter().any(|pause| !pause.is_valid()) {
+            problems.push("has a pause which ends before it starts".to_string());
+        }
+        if !self
+            .pauses
+            .windows(2)
+            .all(|pair| pair[0].from() <= pair[1].from())
+        {
+            problems.push("pauses are out of order".to_string());
+        }
+        if let Some(slug) = &self.slug {
+            if Task::check_slug(slug).is_err() {
+                problems.push(format!("slug \"{}\" isn't valid", slug));
+            }
+        }
+
+        problems
+    }
+
+    /// Fix whatever `problems()` can be fixed, returning a description of each fix. A Task with no
+    /// revisions can't be fixed.
+    pub fn repair(&mut self) -> Vec<String> {
+        let mut fixes = Vec::new();
+
+        for problem in self.problems() {
+            if problem.ends_with("out of order") {
+                fixes.push(problem.replace("are out of order", "were sorted"));
+            }
+        }
+
+        if let Some(slug) = self.slug.take_if(|slug| Task::check_slug(slug).is_err()) {
+            fixes.push(format!("slug \"{}\" was removed", slug));
+        }
+
+        for pause in self.pauses.extract_if(.., |pause| !pause.is_valid()) {
+            fixes.push(format!(
+                "pause from {} to {} was removed, as it ends before it starts",
+                pause.from(),
+                pause.to()
+            ));
+        }
+
+        self.detail_history
+            .sort_by_key(|details| details.revision_id);
+        self.completions
+            .sort_by_key(|completion| completion.datetime);
+        self.remarks.sort_by_key(|remark| remark.datetime);
+        self.pauses.sort_by_key(|pause| pause.from());
+
+        fixes
     }
 }
 
 impl TaskDetails {
-    fn new(time: Option<DateTime<Utc>>, revision_id: u64, description: String) -> TaskDetails {
+    fn new(time: DateTime<FixedOffset>, revision_id: u64, description: String) -> TaskDetails {
         TaskDetails {
-            revised: time.unwrap_or(Utc::now()),
+            revised: time.with_timezone(&Utc),
+            offset: Some(time.offset().local_minus_utc()),
             revision_id,
             description,
             sync_time: None,
+            schedule: Schedule::default(),
+            target: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Completion, Task, TaskDetails, TaskError, TaskKind};
+    use crate::structs::dayrules::local_date;
+    use crate::structs::{DayRules, Schedule};
+    use chrono::prelude::*;
+
+    /// Zones a completion might be recorded in, from one side of the date line to the other
+    fn zones() -> Vec<FixedOffset> {
+        vec![
+            FixedOffset::west(10 * 3600),
+            FixedOffset::west(5 * 3600),
+            FixedOffset::east(0),
+            FixedOffset::east(5 * 3600 + 1800),
+            FixedOffset::east(9 * 3600),
+            FixedOffset::east(13 * 3600),
+        ]
+    }
+
+    /// A task created well before any of the dates used in these tests
+    fn task() -> Task {
+        let created = Utc.ymd(2019, 11, 1).and_hms(12, 0, 0);
+        Task::from_parts(
+            vec![TaskDetails::revision(
+                created,
+                None,
+                0,
+                "read".to_string(),
+                Schedule::Daily,
+            )],
+            Vec::new(),
+            Vec::new(),
+            None,
+        )
+    }
+
+    #[test]
+    fn completion_keeps_its_timezone() {
+        let rules = DayRules::default();
+        let day = NaiveDate::from_ymd(2019, 12, 2);
+
+        for zone in zones() {
+            for &(hour, minute) in &[(0, 0), (0, 30), (12, 0), (23, 30), (23, 59)] {
+                let mut task = task();
+                let now = zone.ymd(2019, 12, 2).and_hms(hour, minute, 0);
+                task.mark_complete(&None, None, None, &rules, now).unwrap();
+
+                // The day is the same wherever (and in whichever zone) chain is run later
+                let completion = &task.completions()[0];
+                assert_eq!(completion.offset(), Some(zone));
+                assert_eq!(completion.day(&rules), day, "{} in {}", now, zone);
+                assert!(task.completed_on(local_date(day), &rules));
+                assert!(!task.completed_on(local_date(day.succ()), &rules));
+            }
+        }
+    }
+
+    #[test]
+    fn late_night_completion_in_another_timezone() {
+        let rules = DayRules {
+            day_start: NaiveTime::from_hms(4, 0, 0),
+            ..DayRules::default()
+        };
+
+        for zone in zones() {
+            let mut task = task();
+            let now = zone.ymd(2019, 12, 3).and_hms(1, 0, 0);
+            task.mark_complete(&None, None, None, &rules, now).unwrap();
+
+            assert_eq!(
+                task.completions()[0].day(&rules),
+                NaiveDate::from_ymd(2019, 12, 2)
+            );
+        }
+    }
+
+    #[test]
+    fn backdated_completion_in_recorded_timezone() {
+        let rules = DayRules::default();
+
+        for zone in zones() {
+            let mut task = task();
+            let now = zone.ymd(2019, 12, 3).and_hms(1, 0, 0);
+
+            let date = NaiveDate::from_ymd(2019, 12, 1);
+            task.mark_complete(&None, None, Some(local_date(date)), &rules, now)
+                .unwrap();
+
+            let completion = &task.completions()[0];
+            assert_eq!(
+                completion.local_datetime().naive_local(),
+                date.and_hms(12, 0, 0)
+            );
+            assert_eq!(completion.day(&rules), date);
+
+            // "Today" is the day it is in the zone the clock is in
+            let tomorrow = local_date(NaiveDate::from_ymd(2019, 12, 4));
+            assert_eq!(
+                task.mark_complete(&None, None, Some(tomorrow), &rules, now),
+                Err(TaskError::DateInFuture)
+            );
+            assert_eq!(
+                task.mark_complete(&None, None, Some(local_date(date)), &rules, now),
+                Err(TaskError::AlreadyCompleted)
+            );
+        }
+    }
+
+    #[test]
+    fn completion_without_offset_uses_local_timezone() {
+        let datetime = Utc.ymd(2019, 12, 2).and_hms(23, 30, 0);
+        let completion = Completion::new(datetime, None, None);
+
+        assert_eq!(completion.offset(), None);
+        assert_eq!(
+            completion.local_datetime().naive_local(),
+            datetime.with_timezone(&Local).naive_local()
+        );
+
+        // The offset survives a round trip through the task file
+        let recorded = Completion::new(datetime, Some(FixedOffset::east(9 * 3600)), None);
+        let reloaded: Completion =
+            ron::de::from_str(&ron::ser::to_string(&recorded).unwrap()).unwrap();
+        assert_eq!(reloaded.offset(), recorded.offset());
+        assert_eq!(
+            reloaded.day(&DayRules::default()),
+            NaiveDate::from_ymd(2019, 12, 3)
+        );
+    }
+
+    #[test]
+    fn amounts_add_up_to_target() {
+        let rules = DayRules::default();
+        let now = FixedOffset::east(0).ymd(2019, 12, 2).and_hms(8, 0, 0);
+        let date = local_date(now.naive_local().date());
+        let mut task = task();
+
+        // Amounts can't be recorded until there's a target
+        assert_eq!(
+            task.mark_complete(&None, Some(5.0), None, &rules, now),
+            Err(TaskError::NoTarget(5.0))
+        );
+
+        task.set_target(Some("20 pages".parse().unwrap()), now)
+            .unwrap();
+        assert_eq!(
+            task.mark_complete(&None, Some(-1.0), None, &rules, now),
+            Err(TaskError::InvalidAmount(-1.0))
+        );
+        assert_eq!(
+            task.progress_on(date, &rules).unwrap().to_string(),
+            "0/20 pages"
+        );
+
+        for (minutes, amount) in [(0, 5.0), (10, 10.0), (20, 10.0)].iter() {
+            let later = now + chrono::Duration::minutes(*minutes);
+            task.mark_complete(&None, Some(*amount), None, &rules, later)
+                .unwrap();
+        }
+        assert!(task.completed_on(date, &rules));
+        assert_eq!(task.progress_on(date, &rules).unwrap().amount, 25.0);
+
+        // The day is completed by the entry which met the target
+        assert_eq!(
+            task.completed_today(date, &rules).unwrap(),
+            (now + chrono::Duration::minutes(20)).with_timezone(&Local)
+        );
+
+        // Removing the latest entry leaves the day short of the target
+        assert_eq!(task.uncomplete(date, &rules).unwrap().amount(), Some(10.0));
+        assert!(!task.completed_on(date, &rules));
+        assert!(task.progress_on(date, &rules).unwrap().is_partial());
+
+        // A completion without an amount meets the target by itself
+        task.mark_complete(&None, None, None, &rules, now + chrono::Duration::hours(1))
+            .unwrap();
+        assert!(task.completed_on(date, &rules));
+        assert_eq!(
+            task.mark_complete(&None, None, None, &rules, now + chrono::Duration::hours(2)),
+            Err(TaskError::AlreadyCompleted)
+        );
+    }
+
+    #[test]
+    fn target_applies_from_when_it_was_set() {
+        let rules = DayRules::default();
+        let before = FixedOffset::east(0).ymd(2019, 12, 1).and_hms(8, 0, 0);
+        let after = before + chrono::Duration::days(2);
+        let mut task = task();
+
+        task.mark_complete(&None, None, None, &rules, before)
+            .unwrap();
+        task.set_target(Some("2L".parse().unwrap()), after).unwrap();
+        task.mark_complete(&None, Some(1.5), None, &rules, after)
+            .unwrap();
+
+        let before = local_date(before.naive_local().date());
+        let after = local_date(after.naive_local().date());
+        assert!(task.completed_on(before, &rules));
+        assert!(task.progress_on(before, &rules).is_none());
+        assert!(!task.completed_on(after, &rules));
+        assert_eq!(
+            task.progress_on(after, &rules).unwrap().to_string(),
+            "1.5/2 L"
+        );
+    }
+
+    #[test]
+    fn revisions_and_trimming_follow_day_start() {
+        let rules = DayRules {
+            day_start: NaiveTime::from_hms(4, 0, 0),
+            ..DayRules::default()
+        };
+        let night = Local.ymd(2019, 12, 2).and_hms(1, 0, 0);
+        let night = night.with_timezone(&night.offset().fix());
+        let mut task = task();
+
+        // Done after midnight, but before the day starts, so both count for the 1st
+        task.revise(Some("run".to_string()), None, night).unwrap();
+        task.mark_complete(&None, None, None, &rules, night)
+            .unwrap();
+        assert_eq!(
+            task.details_on(Local.ymd(2019, 12, 1), &rules)
+                .unwrap()
+                .description(),
+            "run"
+        );
+
+        task.retain_since(Local.ymd(2019, 12, 2), &rules);
+        assert!(task.completions().is_empty());
+    }
+
+    #[test]
+    fn avoided_task_logs_slips() {
+        let rules = DayRules::default();
+        let now = FixedOffset::east(0).ymd(2019, 12, 2).and_hms(8, 0, 0);
+        let date = local_date(now.naive_local().date());
+        let mut task = task().with_kind(TaskKind::Avoid);
+
+        // Several slips can be logged on the same day, and none of them complete the task
+        for minutes in 0..2 {
+            let later = now + chrono::Duration::minutes(minutes);
+            task.mark_complete(&None, None, None, &rules, later)
+                .unwrap();
+        }
+        assert!(!task.completed_on(date, &rules));
+        assert!(task.completed_today(date, &rules).is_none());
+        assert!(task.slipped_on(date, &rules));
+        assert_eq!(
+            task.slipped_today(date, &rules).unwrap(),
+            now.with_timezone(&Local)
+        );
+
+        // Slips can't add up to anything
+        assert_eq!(
+            task.set_target(Some("20 pages".parse().unwrap()), now),
+            Err(TaskError::NotForAvoid("a target"))
+        );
+        assert!(task.set_schedule(Schedule::TimesPerWeek(3), now).is_err());
+        assert!(task.set_schedule(Schedule::EveryNDays(2), now).is_ok());
+
+        // Merging only skips slips which were already logged
+        let mut copy = task.clone();
+        copy.mark_complete(&None, None, None, &rules, now + chrono::Duration::hours(1))
+            .unwrap();
+        assert_eq!(task.merge(copy, &rules).completions, 1);
+        assert_eq!(task.completions().len(), 3);
+    }
+
+    #[test]
+    fn merge_tells_apart_amounts_recorded_together() {
+        let rules = DayRules::default();
+        let now = FixedOffset::east(0).ymd(2019, 12, 2).and_hms(8, 0, 0);
+        let yesterday = Local.ymd(2019, 12, 1);
+        let mut task = task();
+        task.set_target(
+            Some("20 pages".parse().unwrap()),
+            FixedOffset::east(0).ymd(2019, 11, 30).and_hms(12, 0, 0),
+        )
+        .unwrap();
+        task.mark_complete(&None, Some(5.0), Some(yesterday), &rules, now)
+            .unwrap();
+
+        // Backdated amounts are recorded at the same time, so only the amount tells them apart
+        let mut copy = task.clone();
+        copy.mark_complete(&None, Some(12.0), Some(yesterday), &rules, now)
+            .unwrap();
+        assert_eq!(task.merge(copy.clone(), &rules).completions, 1);
+        assert_eq!(task.merge(copy, &rules).completions, 0);
+        assert_eq!(task.progress_on(yesterday, &rules).unwrap().amount, 17.0);
+    }
+}
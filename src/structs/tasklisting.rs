@@ -18,25 +18,72 @@
 use chrono::prelude::*;
 use ron::ser::{PrettyConfig, Serializer};
 use serde::{Deserialize, Serialize};
-use std::fs::OpenOptions;
-use std::io::prelude::*;
-use std::path::PathBuf;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+use super::clock::{self, Clock};
+use super::dayrules::local_date;
+use super::fuzzy;
+use super::migration;
+use super::profile;
+use super::report::{self, HistoryRow, TodayEntry};
+use super::storage;
+use super::ChainError;
+use super::DayRules;
+use super::Journal;
+use super::Pause;
+use super::StreakStats;
 use super::Task;
+use super::TaskDetails;
 use super::TaskError;
 use super::TaskOperation;
 
 /// name of file in which task data is stored
-const TASK_FILE: &'static str = "taskdata.ron";
+pub(crate) const TASK_FILE: &str = "taskdata.ron";
 
+/// Get the path of the file in which task data is stored for the default profile (see `Profiles`
+/// for the others)
 pub fn get_tasks_path() -> PathBuf {
-    let mut tasks_path = dirs::data_dir().unwrap();
-    tasks_path.push("chain");
+    let mut tasks_path = profile::default_data_dir();
     tasks_path.push(TASK_FILE);
 
     tasks_path
 }
 
+/// Characters task IDs are made of (leaving out those easily confused with others, like `l` and
+/// `1`)
+const ID_CHARS: &[u8] = b"23456789abcdefghjkmnpqrstuvwxyz";
+
+/// Number of characters in a task ID
+const ID_LENGTH: usize = 6;
+
+/// Spell `hash` as a task ID, or return None if it wouldn't contain a letter (as IDs which could be
+/// mistaken for an index aren't allowed)
+pub(crate) fn id_from_hash(mut hash: u64) -> Option<String> {
+    let id: String = (0..ID_LENGTH)
+        .map(|_| {
+            let c = ID_CHARS[(hash % ID_CHARS.len() as u64) as usize];
+            hash /= ID_CHARS.len() as u64;
+            c as char
+        })
+        .collect();
+
+    if id.chars().any(|c| c.is_ascii_lowercase()) {
+        Some(id)
+    } else {
+        None
+    }
+}
+
+/// A cheap way to tell whether the task file has changed
+fn fingerprint(contents: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
 /// This struct exists so that the RON output used to store tasks between invocations can be
 /// prefixed with the type name when serialized. (it was previously just a vector, but this made it
 /// impossible to output human-readable RON).
@@ -45,67 +92,731 @@ pub fn get_tasks_path() -> PathBuf {
 /// vector)
 #[derive(Serialize, Deserialize)]
 pub struct TaskListing {
+    /// Version of the format this listing was stored in (see `migration`)
+    #[serde(default)]
+    version: u32,
+
     all_tasks: Vec<Task>,
+
+    /// Ranges of days on which every task is excused (e.g. vacations), oldest first
+    #[serde(default)]
+    pauses: Vec<Pause>,
+
+    /// A record of applied operations, so that they can be undone
+    #[serde(default)]
+    journal: Journal,
+
+    /// Fingerprint of the task file when this listing was last loaded or stored, used to detect
+    /// changes made by other processes
+    #[serde(skip)]
+    on_disk: Option<u64>,
+
+    /// The user's preferences for how days are grouped
+    #[serde(skip)]
+    rules: DayRules,
+
+    /// How many backups of the task file to keep
+    #[serde(skip, default = "storage::backup_count")]
+    backup_count: usize,
+
+    /// Where the current time comes from
+    #[serde(skip, default = "clock::system")]
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for TaskListing {
+    fn default() -> Self {
+        TaskListing::new()
+    }
 }
 
 impl TaskListing {
     /// Create a new `TaskListing`
     pub fn new() -> TaskListing {
         TaskListing {
+            version: migration::CURRENT_VERSION,
             all_tasks: Vec::new(),
+            pauses: Vec::new(),
+            journal: Journal::default(),
+            on_disk: None,
+            rules: DayRules::default(),
+            backup_count: storage::backup_count(),
+            clock: clock::system(),
         }
     }
 
-    /// Handle an operation and store the result to disk
-    pub fn handle_and_store(&mut self, op: &TaskOperation) -> Result<(), TaskError> {
-        self.handle_operation(op)?;
-        self.store(get_tasks_path())?;
+    /// Load a listing from the task file at `path`. A missing or empty file is treated as an empty
+    /// listing.
+    pub fn load(path: &Path) -> Result<TaskListing, ChainError> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(error) => {
+                return Err(ChainError::Io {
+                    path: path.to_path_buf(),
+                    error,
+                })
+            }
+        };
 
-        // TODO: reload from disk, as another command from CLI may have modified TaskListing
-        // TODO: maybe there should be some kind of locking mechanism to avoid race conditions
+        TaskListing::parse(path, &contents)
+    }
 
-        Ok(())
+    /// Parse and validate the contents of the task file at `path` (which is only used to describe
+    /// errors)
+    pub fn parse(path: &Path, contents: &str) -> Result<TaskListing, ChainError> {
+        let mut listing = if contents.trim().is_empty() {
+            TaskListing::new()
+        } else {
+            // Files written by newer versions of chain may not parse, and if they do, they may be
+            // missing data, so check before trying
+            if let Ok(version) = migration::detect_version(contents) {
+                if version > migration::CURRENT_VERSION {
+                    return Err(TaskError::UnsupportedVersion(version).into());
+                }
+            }
+
+            let listing: TaskListing =
+                ron::de::from_str(contents).map_err(|e| ChainError::parse(path, contents, e))?;
+
+            let problems = listing.problems();
+            if !problems.is_empty() {
+                return Err(ChainError::Validation {
+                    path: path.to_path_buf(),
+                    problems,
+                });
+            }
+
+            listing
+        };
+
+        listing.track_file_contents(contents);
+
+        Ok(listing)
+    }
+
+    /// Check that every task is consistent, returning a description of each problem found
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems: Vec<String> = self
+            .task_iter()
+            .enumerate()
+            .flat_map(|(index, task)| {
+                task.problems()
+                    .into_iter()
+                    .map(move |problem| format!("task {}: {}", index, problem))
+            })
+            .collect();
+
+        for (index, task) in self.task_iter().enumerate() {
+            let earlier = &self.all_tasks[..index];
+
+            // Tasks only have IDs from version 2 on (older files get them when upgraded)
+            if task.id().is_empty() {
+                if self.version >= 2 {
+                    problems.push(format!("task {}: has no ID", index));
+                }
+            } else if let Some(other) = earlier.iter().position(|t| t.id() == task.id()) {
+                problems.push(format!(
+                    "task {}: has the same ID as task {} ({})",
+                    index,
+                    other,
+                    task.id()
+                ));
+            }
+
+            if let Some(slug) = task.slug() {
+                if let Some(other) = self
+                    .task_iter()
+                    .position(|t| t.slug() == Some(slug) || t.id() == slug)
+                    .filter(|other| *other != index)
+                {
+                    problems.push(format!(
+                        "task {}: slug \"{}\" is also used by task {}",
+                        index, slug, other
+                    ));
+                }
+            }
+        }
+
+        for pause in self.pauses.iter().filter(|pause| !pause.is_valid()) {
+            problems.push(format!(
+                "the pause for every task from {} to {} ends before it starts",
+                pause.from(),
+                pause.to()
+            ));
+        }
+
+        problems
+    }
+
+    /// Fix whatever `problems()` can be fixed, dropping any task which can't be, and returning a
+    /// description of each change
+    pub fn repair(&mut self) -> Vec<String> {
+        let mut changes = Vec::new();
+
+        let tasks = std::mem::take(&mut self.all_tasks);
+        let n_tasks = tasks.len();
+        for (index, mut task) in tasks.into_iter().enumerate() {
+            if task.created().is_none() {
+                changes.push(format!("task {}: dropped, as it has no revisions", index));
+                continue;
+            }
+
+            for fix in task.repair() {
+                changes.push(format!("task {}: {}", index, fix));
+            }
+            self.all_tasks.push(task);
+        }
+
+        for (index, slug) in self.clear_duplicate_slugs() {
+            changes.push(format!(
+                "task {}: slug \"{}\" was removed, as another task uses it",
+                index, slug
+            ));
+        }
+
+        for pause in self.pauses.extract_if(.., |pause| !pause.is_valid()) {
+            changes.push(format!(
+                "the pause for every task from {} to {} was removed, as it ends before it starts",
+                pause.from(),
+                pause.to()
+            ));
+        }
+        self.pauses.sort_by_key(|pause| pause.from());
+
+        // Older files get IDs when they're upgraded
+        if self.version >= 2 {
+            for (index, id) in self.assign_ids() {
+                changes.push(format!("task {}: given the ID {}", index, id));
+            }
+        }
+
+        // The journal refers to tasks by index, so it's no use if any were dropped
+        if self.all_tasks.len() != n_tasks && self.journal.peek_undo().is_some() {
+            self.journal = Journal::default();
+            changes.push("the undo history was discarded".to_string());
+        }
+
+        changes
+    }
+
+    /// Get the version of the format this listing is in
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Change the version of the format this listing is in (used by migrations)
+    pub fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
+
+    /// Get the user's preferences for how days are grouped
+    pub fn rules(&self) -> &DayRules {
+        &self.rules
+    }
+
+    /// Change the user's preferences for how days are grouped
+    pub fn set_rules(&mut self, rules: DayRules) {
+        self.rules = rules;
+    }
+
+    /// Get the clock this listing takes the current time from
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        Arc::clone(&self.clock)
+    }
+
+    /// Take the current time from `clock` instead (e.g. to run at some other time)
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Get the current time, in the timezone the clock is in
+    pub fn now(&self) -> DateTime<FixedOffset> {
+        self.clock.now()
+    }
+
+    /// Get the day it is now, as the user's preferences define days
+    pub fn today(&self) -> Date<Local> {
+        self.clock.today(&self.rules)
+    }
+
+    /// Change how many backups of the task file are kept when it's stored (0 disables backups)
+    pub fn set_backup_count(&mut self, count: usize) {
+        self.backup_count = count;
+    }
+
+    /// Remember what the task file contained when this listing was loaded from it, so that changes
+    /// made by other processes can be detected
+    pub fn track_file_contents(&mut self, contents: &str) {
+        self.on_disk = Some(fingerprint(contents));
+    }
+
+    /// Reload the listing if the task file was changed (e.g. by another process) since it was last
+    /// loaded or stored. Returns true if it was reloaded.
+    pub fn reload_if_changed(&mut self, path: &Path) -> Result<bool, TaskError> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(_e) => return Err(TaskError::LoadFailed),
+        };
+
+        if self.on_disk == Some(fingerprint(&contents)) {
+            return Ok(false);
+        }
+
+        let mut reloaded = TaskListing::parse(path, &contents).map_err(|e| match e {
+            ChainError::Task(e) => e,
+            _ => TaskError::LoadFailed,
+        })?;
+        migration::upgrade(&mut reloaded)?;
+
+        self.version = reloaded.version;
+        self.all_tasks = reloaded.all_tasks;
+        self.pauses = reloaded.pauses;
+        self.journal = reloaded.journal;
+        self.track_file_contents(&contents);
+
+        Ok(true)
+    }
+
+    /// Handle an operation and store the result to disk, keeping a backup of what was there
+    /// before.
+    ///
+    /// The task file is locked while this happens. If another process changed it since this
+    /// listing was loaded, the listing is reloaded and the operation is applied to the new data
+    /// instead, failing with `TaskError::Conflict` if that's no longer possible.
+    pub fn handle_and_store(&mut self, op: &TaskOperation, path: &Path) -> Result<(), TaskError> {
+        let _lock = storage::lock(path).map_err(|_e| TaskError::StoreFailed)?;
+
+        // Remember which task the operation was meant for, in case it moves
+        let target = op
+            .task_index()
+            .and_then(|index| self.all_tasks.get(index))
+            .map(|task| task.id().to_string());
+
+        if self.reload_if_changed(path)? {
+            let reloaded_target = op
+                .task_index()
+                .and_then(|index| self.all_tasks.get(index))
+                .map(|task| task.id().to_string());
+
+            if target.is_some() && reloaded_target != target {
+                return Err(TaskError::Conflict(Box::new(TaskError::NotFound)));
+            }
+
+            self.handle_operation(op)
+                .map_err(|e| TaskError::Conflict(Box::new(e)))?;
+        } else {
+            self.handle_operation(op)?;
+        }
+
+        storage::backup(path, self.backup_count).map_err(|_e| TaskError::StoreFailed)?;
+        self.store(path.to_path_buf())
+    }
+
+    /// Make a change which isn't a `TaskOperation` (so it isn't recorded in the journal), and store
+    /// the result to disk, keeping a backup of what was there before.
+    ///
+    /// The task file is locked while this happens, and the listing is reloaded first if another
+    /// process changed it.
+    pub fn modify_and_store<T, F>(&mut self, path: &Path, modify: F) -> Result<T, TaskError>
+    where
+        F: FnOnce(&mut TaskListing) -> T,
+    {
+        let _lock = storage::lock(path).map_err(|_e| TaskError::StoreFailed)?;
+
+        self.reload_if_changed(path)?;
+        let result = modify(self);
+
+        storage::backup(path, self.backup_count).map_err(|_e| TaskError::StoreFailed)?;
+        self.store(path.to_path_buf())?;
+
+        Ok(result)
     }
 
     /// Handle an operation on the TaskListing. This will only update the listing in memory, it's
     /// the caller's responsibility to ensure it gets updated in persistent storage.
+    ///
+    /// Every operation (other than `Undo` and `Redo` themselves) is recorded in the journal so
+    /// that it can be undone later.
     pub fn handle_operation(&mut self, op: &TaskOperation) -> Result<(), TaskError> {
         match op {
-            TaskOperation::Add { description } if description.chars().count() == 0 => {
+            TaskOperation::Undo => self.undo(),
+            TaskOperation::Redo => self.redo(),
+            _ => {
+                let inverse = self.apply(op)?;
+                let applied = self.clock.now().with_timezone(&Utc);
+                self.journal.record(op.clone(), inverse, applied);
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Reverse the most recently applied operation
+    fn undo(&mut self) -> Result<(), TaskError> {
+        let mut entry = self.journal.pop_undo().ok_or(TaskError::NothingToUndo)?;
+
+        match self.apply(&entry.inverse) {
+            Ok(reapply) => {
+                entry.reapply = reapply;
+                self.journal.push_redo(entry);
+                Ok(())
+            }
+            Err(e) => {
+                self.journal.push_undo(entry);
+                Err(e)
+            }
+        }
+    }
+
+    /// Re-apply the most recently undone operation
+    fn redo(&mut self) -> Result<(), TaskError> {
+        let mut entry = self.journal.pop_redo().ok_or(TaskError::NothingToRedo)?;
+
+        match self.apply(&entry.reapply) {
+            Ok(inverse) => {
+                entry.inverse = inverse;
+                self.journal.push_undo(entry);
+                Ok(())
+            }
+            Err(e) => {
+                self.journal.push_redo(entry);
+                Err(e)
+            }
+        }
+    }
+
+    /// Apply an operation to the listing, returning an operation which reverses it
+    fn apply(&mut self, op: &TaskOperation) -> Result<TaskOperation, TaskError> {
+        let now = self.clock.now();
+        let rules = self.rules.clone();
+
+        let inverse = match op {
+            TaskOperation::Add { description, .. } if description.chars().count() == 0 => {
                 return Err(TaskError::MissingDescription);
             }
-            TaskOperation::Add { description } => {
-                let new_task = Task::new(description.to_string());
+            TaskOperation::Add { description, kind } => {
+                let new_task = Task::new(description.to_string(), now).with_kind(*kind);
                 self.push(new_task);
+
+                TaskOperation::Delete {
+                    task_index: self.total_tasks() - 1,
+                }
             }
-            TaskOperation::MarkComplete { task_index, remark } => {
-                // TODO: refactor everything up to "let matching_task" as self.task_from_index()?
-                if *task_index >= self.all_tasks.iter().count() {
-                    return Err(TaskError::NotFound);
+            TaskOperation::MarkComplete {
+                task_index,
+                remark,
+                date,
+                amount,
+            } => {
+                let matching_task: &mut Task = self
+                    .task_from_index(*task_index)
+                    .ok_or(TaskError::NotFound)?;
+
+                TaskOperation::RemoveCompletion {
+                    task_index: *task_index,
+                    datetime: matching_task.mark_complete(
+                        remark,
+                        *amount,
+                        date.map(local_date),
+                        &rules,
+                        now,
+                    )?,
+                    amount: *amount,
                 }
+            }
+            TaskOperation::Uncomplete { task_index, date } => {
+                let matching_task: &mut Task = self
+                    .task_from_index(*task_index)
+                    .ok_or(TaskError::NotFound)?;
 
-                let matching_task: &mut Task = self.task_iter_mut().nth(*task_index).unwrap();
+                TaskOperation::RestoreCompletion {
+                    task_index: *task_index,
+                    completion: matching_task.uncomplete(local_date(*date), &rules)?,
+                }
+            }
+            TaskOperation::Reorder { from, to } => {
+                self.move_task(*from, *to)?;
 
-                matching_task.mark_complete(remark)?
+                TaskOperation::Reorder {
+                    from: *to,
+                    to: *from,
+                }
             }
-            TaskOperation::Reorder { from, to } => self.move_task(*from, *to)?,
             TaskOperation::AddRemark { task_index, remark } => {
-                // TODO: refactor everything up to "let matching_task" as self.task_from_index()?
-                if *task_index >= self.all_tasks.iter().count() {
+                let matching_task: &mut Task = self
+                    .task_from_index(*task_index)
+                    .ok_or(TaskError::NotFound)?;
+
+                TaskOperation::RemoveRemark {
+                    task_index: *task_index,
+                    datetime: matching_task.add_remark(remark.to_string(), now)?,
+                }
+            }
+            TaskOperation::SetSchedule {
+                task_index,
+                schedule,
+            } => {
+                let matching_task: &mut Task = self
+                    .task_from_index(*task_index)
+                    .ok_or(TaskError::NotFound)?;
+
+                TaskOperation::RemoveRevision {
+                    task_index: *task_index,
+                    revision_id: matching_task.set_schedule(schedule.clone(), now)?,
+                }
+            }
+            TaskOperation::SetTarget { task_index, target } => {
+                let matching_task: &mut Task = self
+                    .task_from_index(*task_index)
+                    .ok_or(TaskError::NotFound)?;
+
+                TaskOperation::RemoveRevision {
+                    task_index: *task_index,
+                    revision_id: matching_task.set_target(target.clone(), now)?,
+                }
+            }
+            TaskOperation::Undo | TaskOperation::Redo => {
+                // These only make sense in the context of the journal
+                unreachable!("{} can't be applied directly", op)
+            }
+            TaskOperation::Delete { task_index } => {
+                if *task_index >= self.total_tasks() {
+                    return Err(TaskError::NotFound);
+                }
+
+                TaskOperation::Insert {
+                    task_index: *task_index,
+                    task: self.all_tasks.remove(*task_index),
+                }
+            }
+            TaskOperation::Insert { task_index, task } => {
+                if *task_index > self.total_tasks() {
                     return Err(TaskError::NotFound);
                 }
 
-                let matching_task: &mut Task = self.task_iter_mut().nth(*task_index).unwrap();
+                self.all_tasks.insert(*task_index, task.clone());
 
-                matching_task.add_remark(remark.to_string())?
+                TaskOperation::Delete {
+                    task_index: *task_index,
+                }
             }
-        }
+            TaskOperation::RemoveCompletion {
+                task_index,
+                datetime,
+                amount,
+            } => {
+                let matching_task: &mut Task = self
+                    .task_from_index(*task_index)
+                    .ok_or(TaskError::NotFound)?;
 
-        Ok(())
+                TaskOperation::RestoreCompletion {
+                    task_index: *task_index,
+                    completion: matching_task.remove_completion(*datetime, *amount)?,
+                }
+            }
+            TaskOperation::RestoreCompletion {
+                task_index,
+                completion,
+            } => {
+                let matching_task: &mut Task = self
+                    .task_from_index(*task_index)
+                    .ok_or(TaskError::NotFound)?;
+
+                matching_task.restore_completion(completion.clone());
+
+                TaskOperation::RemoveCompletion {
+                    task_index: *task_index,
+                    datetime: completion.datetime(),
+                    amount: completion.amount(),
+                }
+            }
+            TaskOperation::RemoveRemark {
+                task_index,
+                datetime,
+            } => {
+                let matching_task: &mut Task = self
+                    .task_from_index(*task_index)
+                    .ok_or(TaskError::NotFound)?;
+
+                TaskOperation::RestoreRemark {
+                    task_index: *task_index,
+                    remark: matching_task.remove_remark(*datetime)?,
+                }
+            }
+            TaskOperation::RestoreRemark { task_index, remark } => {
+                let matching_task: &mut Task = self
+                    .task_from_index(*task_index)
+                    .ok_or(TaskError::NotFound)?;
+
+                matching_task.restore_remark(remark.clone());
+
+                TaskOperation::RemoveRemark {
+                    task_index: *task_index,
+                    datetime: remark.datetime(),
+                }
+            }
+            TaskOperation::Revise {
+                task_index,
+                description,
+            } => {
+                let matching_task: &mut Task = self
+                    .task_from_index(*task_index)
+                    .ok_or(TaskError::NotFound)?;
+
+                TaskOperation::RemoveRevision {
+                    task_index: *task_index,
+                    revision_id: matching_task.revise(Some(description.to_string()), None, now)?,
+                }
+            }
+            TaskOperation::Archive { task_index } => {
+                let matching_task: &mut Task = self
+                    .task_from_index(*task_index)
+                    .ok_or(TaskError::NotFound)?;
+
+                if matching_task.is_archived() {
+                    return Err(TaskError::AlreadyArchived);
+                }
+
+                let (archived, offset) =
+                    matching_task.set_archived(Some(now.with_timezone(&Utc)), Some(*now.offset()));
+                TaskOperation::SetArchived {
+                    task_index: *task_index,
+                    archived,
+                    offset: offset.map(|offset| offset.local_minus_utc()),
+                }
+            }
+            TaskOperation::Unarchive { task_index } => {
+                let matching_task: &mut Task = self
+                    .task_from_index(*task_index)
+                    .ok_or(TaskError::NotFound)?;
+
+                if !matching_task.is_archived() {
+                    return Err(TaskError::NotArchived);
+                }
+
+                let (archived, offset) = matching_task.set_archived(None, None);
+                TaskOperation::SetArchived {
+                    task_index: *task_index,
+                    archived,
+                    offset: offset.map(|offset| offset.local_minus_utc()),
+                }
+            }
+            TaskOperation::SetArchived {
+                task_index,
+                archived,
+                offset,
+            } => {
+                let matching_task: &mut Task = self
+                    .task_from_index(*task_index)
+                    .ok_or(TaskError::NotFound)?;
+
+                let (archived, offset) =
+                    matching_task.set_archived(*archived, offset.and_then(FixedOffset::east_opt));
+                TaskOperation::SetArchived {
+                    task_index: *task_index,
+                    archived,
+                    offset: offset.map(|offset| offset.local_minus_utc()),
+                }
+            }
+            TaskOperation::RemoveRevision {
+                task_index,
+                revision_id,
+            } => {
+                let matching_task: &mut Task = self
+                    .task_from_index(*task_index)
+                    .ok_or(TaskError::NotFound)?;
+
+                TaskOperation::RestoreRevision {
+                    task_index: *task_index,
+                    details: matching_task.remove_revision(*revision_id)?,
+                }
+            }
+            TaskOperation::RestoreRevision {
+                task_index,
+                details,
+            } => {
+                let matching_task: &mut Task = self
+                    .task_from_index(*task_index)
+                    .ok_or(TaskError::NotFound)?;
+
+                matching_task.restore_revision(details.clone());
+
+                TaskOperation::RemoveRevision {
+                    task_index: *task_index,
+                    revision_id: details.revision_id(),
+                }
+            }
+            TaskOperation::AddPause { task_index, pause } => {
+                if !pause.is_valid() {
+                    return Err(TaskError::InvalidPause(pause.from(), pause.to()));
+                }
+
+                match task_index {
+                    Some(index) => self
+                        .task_from_index(*index)
+                        .ok_or(TaskError::NotFound)?
+                        .add_pause(pause.clone()),
+                    None => self.insert_pause(pause.clone()),
+                }
+
+                TaskOperation::RemovePause {
+                    task_index: *task_index,
+                    pause: pause.clone(),
+                }
+            }
+            TaskOperation::RemovePause { task_index, pause } => {
+                let removed = match task_index {
+                    Some(index) => self
+                        .task_from_index(*index)
+                        .ok_or(TaskError::NotFound)?
+                        .remove_pause(pause)?,
+                    None => {
+                        let position = self
+                            .pauses
+                            .iter()
+                            .rposition(|p| p == pause)
+                            .ok_or(TaskError::NotFound)?;
+                        self.pauses.remove(position)
+                    }
+                };
+
+                TaskOperation::AddPause {
+                    task_index: *task_index,
+                    pause: removed,
+                }
+            }
+            TaskOperation::SetSlug { task_index, slug } => {
+                if *task_index >= self.total_tasks() {
+                    return Err(TaskError::NotFound);
+                }
+
+                if let Some(slug) = slug {
+                    Task::check_slug(slug)?;
+
+                    let taken = self.task_iter().enumerate().any(|(index, task)| {
+                        index != *task_index
+                            && (task.slug() == Some(slug.as_str()) || task.id() == slug)
+                    });
+                    if taken {
+                        return Err(TaskError::SlugTaken(slug.clone()));
+                    }
+                }
+
+                TaskOperation::SetSlug {
+                    task_index: *task_index,
+                    slug: self.all_tasks[*task_index].set_slug(slug.clone()),
+                }
+            }
+        };
+
+        Ok(inverse)
     }
 
-    /// Serialize listing and write to disk
-    pub fn store(&self, path: std::path::PathBuf) -> Result<(), TaskError> {
+    /// Serialize listing and write to disk. The file is replaced atomically, so a crash part way
+    /// through can't leave it truncated.
+    pub fn store(&mut self, path: std::path::PathBuf) -> Result<(), TaskError> {
         let ron_config = PrettyConfig {
             ..Default::default()
         };
@@ -114,54 +825,211 @@ impl TaskListing {
         // Run the serializer on our task data, get back a string
         // TODO: maybe the file should have a checksum so that we can detect corruption from manual
         // editing
-        match self.serialize(&mut serializer) {
-            Err(e) => match e {
-                ron::ser::Error::Message(s) => panic!("RON serialization error: {}", s),
-            },
-            Ok(_) => {}
+        if let Err(ron::ser::Error::Message(s)) = self.serialize(&mut serializer) {
+            panic!("RON serialization error: {}", s);
         }
         let serialized = serializer.into_output_string();
 
         // Write the serialized data to chain's data folder
-        let task_file_open = OpenOptions::new()
-            .write(true)
-            .truncate(true) // truncate, or else the file will be appended to
-            .open(&path);
-
-        match task_file_open {
-            Err(_e) => {
-                return Err(TaskError::StoreFailed);
-            }
-            Ok(mut file) => match file.write_all(serialized.as_bytes()) {
-                Ok(_) => return Ok(()),
-                Err(_e) => return Err(TaskError::StoreFailed),
-            },
-        }
+        storage::write_atomic(&path, serialized.as_bytes()).map_err(|_e| TaskError::StoreFailed)?;
+        self.track_file_contents(&serialized);
+
+        Ok(())
+    }
+
+    /// Get the journal of operations applied to this listing
+    pub fn journal(&self) -> &Journal {
+        &self.journal
     }
 
-    /// Push a new `Task` into the `TaskListing`
-    pub fn push(&mut self, task: Task) {
+    /// Push a new `Task` into the `TaskListing`, giving it an ID if it doesn't have one (or has one
+    /// another task already uses)
+    pub fn push(&mut self, mut task: Task) {
+        if task.id().is_empty() || self.is_id_taken(task.id()) {
+            let id = self.new_id(&task);
+            task.set_id(id);
+        }
+        if task.slug().is_some_and(|slug| self.is_id_taken(slug)) {
+            task.set_slug(None);
+        }
         self.all_tasks.push(task);
     }
 
+    /// Replace every task, returning the old ones. The journal refers to tasks by index, so it's
+    /// discarded.
+    pub(crate) fn replace_tasks(&mut self, tasks: Vec<Task>) -> Vec<Task> {
+        self.journal = Journal::default();
+        let old = std::mem::replace(&mut self.all_tasks, tasks);
+        self.clear_duplicate_slugs();
+        self.assign_ids();
+        old
+    }
+
+    /// Add a pause for every task, keeping them in order of their first day
+    pub(crate) fn insert_pause(&mut self, pause: Pause) {
+        let position = self
+            .pauses
+            .iter()
+            .position(|p| p.from() > pause.from())
+            .unwrap_or(self.pauses.len());
+        self.pauses.insert(position, pause);
+    }
+
+    /// Replace every pause for every task (e.g. when importing), returning the old ones
+    pub(crate) fn replace_pauses(&mut self, mut pauses: Vec<Pause>) -> Vec<Pause> {
+        pauses.sort_by_key(|pause| pause.from());
+        std::mem::replace(&mut self.pauses, pauses)
+    }
+
+    /// Remove the slug of every task which goes by the same name as another task's ID, or an
+    /// earlier task's slug, returning the index and removed slug of each
+    fn clear_duplicate_slugs(&mut self) -> Vec<(usize, String)> {
+        let mut cleared = Vec::new();
+
+        for index in 0..self.all_tasks.len() {
+            // IDs take precedence over slugs, wherever the task with the ID is
+            let clash = self.all_tasks[index].slug().is_some_and(|slug| {
+                self.all_tasks.iter().enumerate().any(|(other, t)| {
+                    (other < index && t.slug() == Some(slug)) || (other != index && t.id() == slug)
+                })
+            });
+            if clash {
+                let slug = self.all_tasks[index].set_slug(None).unwrap_or_default();
+                cleared.push((index, slug));
+            }
+        }
+
+        cleared
+    }
+
+    /// Give an ID to every task which doesn't have one, or which has the same ID as an earlier
+    /// task, returning the index and new ID of each
+    pub(crate) fn assign_ids(&mut self) -> Vec<(usize, String)> {
+        let mut assigned = Vec::new();
+
+        for index in 0..self.all_tasks.len() {
+            let task = &self.all_tasks[index];
+            let taken = self.all_tasks[..index].iter().any(|t| t.id() == task.id());
+
+            if task.id().is_empty() || taken {
+                let id = self.new_id(task);
+                self.all_tasks[index].set_id(id.clone());
+                assigned.push((index, id));
+            }
+        }
+
+        assigned
+    }
+
+    /// Returns true if some task already goes by `id` (as its ID or slug)
+    fn is_id_taken(&self, id: &str) -> bool {
+        self.task_iter()
+            .any(|task| task.id() == id || task.slug() == Some(id))
+    }
+
+    /// Come up with an ID for `task` which no other task goes by. IDs are derived from when the
+    /// task was created and its description, and always contain a letter, so they can't be
+    /// mistaken for an index.
+    fn new_id(&self, task: &Task) -> String {
+        let mut attempt: u64 = 0;
+        loop {
+            let mut hasher = DefaultHasher::new();
+            task.created().hash(&mut hasher);
+            task.description().hash(&mut hasher);
+            attempt.hash(&mut hasher);
+
+            if let Some(id) = id_from_hash(hasher.finish()) {
+                if !self.is_id_taken(&id) {
+                    return id;
+                }
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Rank every task whose description matches `query` (see `fuzzy::rank()`), best match first.
+    /// Tasks which match equally well are kept in order.
+    pub fn matches(&self, query: &str) -> Vec<(usize, fuzzy::Match)> {
+        let mut matches: Vec<(usize, fuzzy::Match)> = self
+            .task_iter()
+            .enumerate()
+            .filter_map(|(index, task)| {
+                fuzzy::rank(query, task.description()).map(|rank| (index, rank))
+            })
+            .collect();
+        matches.sort_by_key(|(_, rank)| std::cmp::Reverse(*rank));
+
+        matches
+    }
+
+    /// Find the task named by `selector`, which can be its index, ID or slug, or part of its
+    /// description. If several descriptions match equally well, and none better, the selector is
+    /// ambiguous.
+    ///
+    /// A number is always taken as an index (IDs and slugs always contain a letter), so one which
+    /// is out of range isn't found, rather than matching a description which contains it.
+    pub fn find(&self, selector: &str) -> Result<usize, TaskError> {
+        let selector = selector.trim();
+        if selector.is_empty() {
+            return Err(TaskError::NotFound);
+        }
+
+        if let Ok(index) = selector.parse::<usize>() {
+            return if index < self.total_tasks() {
+                Ok(index)
+            } else {
+                Err(TaskError::NotFound)
+            };
+        }
+
+        if let Some(index) = self
+            .task_iter()
+            .position(|task| task.id() == selector || task.slug() == Some(selector))
+        {
+            return Ok(index);
+        }
+
+        let matches = self.matches(selector);
+        let best_rank = matches.first().map(|(_, rank)| *rank);
+        let best: Vec<usize> = matches
+            .iter()
+            .filter(|(_, rank)| Some(*rank) == best_rank)
+            .map(|(index, _)| *index)
+            .collect();
+
+        match best.as_slice() {
+            [] => Err(TaskError::NotFound),
+            [index] => Ok(*index),
+            _ => Err(TaskError::Ambiguous(
+                selector.to_string(),
+                best.iter()
+                    .map(|index| (*index, self.all_tasks[*index].description().clone()))
+                    .collect(),
+            )),
+        }
+    }
+
     /// Get an iterator of non-mutable references to `Task` items in the `TaskListing`
-    pub fn task_iter(&self) -> std::slice::Iter<Task> {
+    pub fn task_iter(&self) -> std::slice::Iter<'_, Task> {
         self.all_tasks.iter()
     }
 
+    /// Get an iterator over tasks which haven't been archived, along with their index in the
+    /// `TaskListing`
+    pub fn active_task_iter(&self) -> impl Iterator<Item = (usize, &Task)> {
+        self.task_iter()
+            .enumerate()
+            .filter(|(_, task)| !task.is_archived())
+    }
+
     /// Get an iterator of mutable references to `Task` items in the `TaskListing`
-    pub fn task_iter_mut(&mut self) -> std::slice::IterMut<Task> {
+    pub fn task_iter_mut(&mut self) -> std::slice::IterMut<'_, Task> {
         self.all_tasks.iter_mut()
     }
 
     /// Get a reference to a task by index
-    #[allow(dead_code)]
     pub fn task_from_index(&mut self, index: usize) -> Option<&mut Task> {
-        if index >= self.total_tasks() {
-            return None;
-        }
-
-        self.all_tasks.iter_mut().nth(index)
+        self.all_tasks.get_mut(index)
     }
 
     /// Move a task from one index to another. This will cause the element that came after `to` to
@@ -186,148 +1054,62 @@ impl TaskListing {
         self.task_iter().count()
     }
 
-    /// List all tasks for today (with completion status, times, and note on which task is next)
-    pub fn list_for_today(&self) {
-        // Calculate some field widths
-        let indent_size = 4;
-        let description_width = ((self.task_iter().fold(0, |max, task| {
-            let curr_len = task.details().unwrap().description().chars().count();
-            if max > curr_len {
-                max
-            } else {
-                curr_len
-            }
-        }) / indent_size)
-            + 1)
-            * indent_size;
-        let id_width = ((self.task_iter().count().to_string().chars().count() / indent_size) + 1)
-            * indent_size;
-        let mut next_marked = false;
-
-        // Display tasks
-        for (n, task) in self.task_iter().enumerate() {
-            // Check box
-            if task.completed_today().is_some() {
-                print!("{:<4}", "[x]");
-            } else {
-                print!("{:<4}", "[ ]")
-            }
-
-            // Numeric ID (used for "order" subcommand)
-            print!("{:<width$}", n, width = id_width);
-
-            // Description
-            print!(
-                "{:<width$}",
-                task.details().unwrap().description(),
-                width = description_width,
-            );
-
-            // Completion time
-            let timestamp_display: String;
-            if task.completed_today().is_some() {
-                let datetime = task.completed_today().unwrap();
-                timestamp_display = format!("{:02}:{:02}", datetime.hour(), datetime.minute());
-            } else {
-                timestamp_display = "--:--".into();
-            }
-            print!(
-                "{:<width$}",
-                timestamp_display,
-                width = ((timestamp_display.chars().count() / indent_size) + 1) * indent_size
-            );
+    /// Get every range of days on which every task is excused, oldest first
+    pub fn pauses(&self) -> &[Pause] {
+        &self.pauses
+    }
 
-            // Mark next task to be done
-            if !next_marked && task.completed_today().is_none() {
-                next_marked = true;
-                print!("(next)");
-            }
+    /// Get the status of each active task for today (with completion status, times, and which
+    /// task is next)
+    pub fn list_for_today(&self) -> Vec<TodayEntry> {
+        report::today(
+            self.active_task_iter(),
+            self.today(),
+            &self.rules,
+            &self.pauses,
+        )
+    }
 
-            println!();
-        }
+    /// Get the status of every task on each day from `start` to `end` (inclusive)
+    pub fn history_for_range(&self, start: Date<Local>, end: Date<Local>) -> Vec<HistoryRow> {
+        report::history(
+            self.task_iter().enumerate(),
+            start,
+            end,
+            self.today(),
+            &self.rules,
+            &self.pauses,
+        )
     }
 
-    pub fn history_for_range(&self, start: Date<Local>, end: Date<Local>) {
-        // Calculate some field widths
-        let indent_size = 4;
-        let description_width = ((self.task_iter().fold(0, |max, task| {
-            let curr_len = task.details().unwrap().description().chars().count();
-            if max > curr_len {
-                max
-            } else {
-                curr_len
+    /// Get streak statistics for a single task (or for every task if `index` is `None`), along
+    /// with the index of each task
+    pub fn stats(&self, index: Option<usize>) -> Result<Vec<(usize, StreakStats)>, TaskError> {
+        if let Some(index) = index {
+            if index >= self.total_tasks() {
+                return Err(TaskError::NotFound);
             }
-        }) / indent_size)
-            + 1)
-            * indent_size;
-        let id_width = ((self.task_iter().count().to_string().chars().count() / indent_size) + 1)
-            * indent_size;
-
-        // Print header row
-        // TODO: break up rendering into multiple rows once this gets to the point that we care
-        // about terminal width
-        print!("{}{}", " ".repeat(id_width), " ".repeat(description_width));
+        }
 
-        let mut dates: Vec<Date<Local>> = Vec::new();
-        let mut date_at = start.clone();
+        let today = self.today();
 
-        while date_at != end.succ() {
-            dates.push(date_at);
-            date_at = date_at.succ();
-        }
+        Ok(self
+            .task_iter()
+            .enumerate()
+            .filter(|(n, _)| index.is_none() || index == Some(*n))
+            .map(|(n, task)| {
+                (
+                    n,
+                    StreakStats::for_task(task, today, &self.rules, &self.pauses),
+                )
+            })
+            .collect())
+    }
 
-        for date in dates.iter() {
-            print!(
-                "{:<width$}",
-                format!("|{:<02}", date.day()),
-                width = indent_size + 1
-            );
-        }
-        println!();
-
-        for (n, task) in self.task_iter().enumerate() {
-            // Numeric ID
-            print!("{:<width$}", n, width = id_width);
-            // Description
-            print!(
-                "{:<width$}",
-                task.details().unwrap().description(),
-                width = description_width
-            );
-
-            // TODO: break the renderer out into a separate module, going to need a state machine
-            // to get the kind of rendering desired
-            let mut any_done = false;
-            let mut last_complete = false;
-            for date in dates.iter() {
-                print!("|");
-
-                if date <= &Local::today() {
-                    if task.completed_on(*date) {
-                        print!("o");
-                        last_complete = true;
-                        any_done = true;
-                    } else if (date != &Local::today()) && !any_done {
-                        print!(" ");
-                    } else if date == &Local::today() {
-                        print!("?");
-                    } else if any_done && last_complete {
-                        print!("x");
-                        last_complete = false;
-                    }
+    /// Get every revision made to a task's details, oldest first
+    pub fn log(&self, index: usize) -> Result<std::slice::Iter<'_, TaskDetails>, TaskError> {
+        let task = self.task_iter().nth(index).ok_or(TaskError::NotFound)?;
 
-                    if date != dates.last().unwrap() {
-                        if last_complete && (date != &Local::today()) {
-                            print!("-o-");
-                        } else {
-                            print!("   ");
-                        }
-                    }
-                } else {
-                    print!("    ");
-                }
-            }
-            println!();
-        }
+        Ok(task.revisions())
     }
 }
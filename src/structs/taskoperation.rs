@@ -17,25 +17,57 @@
 
 // TODO: this mixes operations on both `Task` and `TaskListing`, and should probably be cleaned up.
 
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::target::format_amount;
+use super::task::{Completion, Remark, TaskDetails};
+use super::Pause;
+use super::Schedule;
+use super::Target;
+use super::Task;
+use super::TaskKind;
+
 /// Represents an operation to perform on a TaskListing
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TaskOperation {
+    /// Create a new task
     Add {
         /// Description of the task being added
         description: String,
+        /// Whether the task is something to do or something to avoid
+        #[serde(default)]
+        kind: TaskKind,
     },
+    /// Mark a task as complete for some day
     MarkComplete {
         /// Index of task to mark complete
         task_index: usize,
         /// Optional remark on task completion
         remark: Option<String>,
+        /// Day on which the task was completed (`None` => today)
+        #[serde(default)]
+        date: Option<NaiveDate>,
+        /// How much was done, for tasks with a target (`None` => the whole target)
+        #[serde(default)]
+        amount: Option<f64>,
+    },
+    /// Mark a task as no longer complete on some day
+    Uncomplete {
+        /// Index of task to un-complete
+        task_index: usize,
+        /// Day on which the task should no longer be considered complete
+        date: NaiveDate,
     },
+    /// Remark on a task
     AddRemark {
         /// Index of task to add remark to
         task_index: usize,
         /// Remark to add to task
         remark: String,
     },
+    /// Move a task to another position in the listing
     Reorder {
         /// Index of task being moved
         from: usize,
@@ -43,20 +75,356 @@ pub enum TaskOperation {
         /// higher index
         to: usize,
     },
+    /// Change the days on which a task is due
+    SetSchedule {
+        /// Index of task being rescheduled
+        task_index: usize,
+        /// Days on which the task will be due
+        schedule: Schedule,
+    },
+    /// Change how much needs to be recorded each day for a task to count as completed
+    SetTarget {
+        /// Index of task to set the target of
+        task_index: usize,
+        /// New target for the task (`None` => a single completion completes it)
+        target: Option<Target>,
+    },
+    /// Reverse the most recently applied operation
+    Undo,
+    /// Re-apply the most recently undone operation
+    Redo,
+    /// Permanently remove a task
+    Delete {
+        /// Index of task to remove (along with its history)
+        task_index: usize,
+    },
+    /// Put a task back into the listing (used to undo `Delete`)
+    Insert {
+        /// Index at which the task will be inserted
+        task_index: usize,
+        /// The task being inserted
+        task: Task,
+    },
+    /// Remove a single completion (used to undo `MarkComplete`)
+    RemoveCompletion {
+        /// Index of task to remove completion from
+        task_index: usize,
+        /// Timestamp of the completion being removed
+        datetime: DateTime<Utc>,
+        /// Amount recorded by the completion being removed, which tells it apart from others
+        /// recorded at the same time (backdated amounts are all recorded in the middle of the day)
+        #[serde(default)]
+        amount: Option<f64>,
+    },
+    /// Put back a completion which was removed
+    RestoreCompletion {
+        /// Index of task to restore completion to
+        task_index: usize,
+        /// The completion being restored
+        completion: Completion,
+    },
+    /// Remove a single remark (used to undo `AddRemark`)
+    RemoveRemark {
+        /// Index of task to remove remark from
+        task_index: usize,
+        /// Timestamp of the remark being removed
+        datetime: DateTime<Utc>,
+    },
+    /// Put back a remark which was removed
+    RestoreRemark {
+        /// Index of task to restore remark to
+        task_index: usize,
+        /// The remark being restored
+        remark: Remark,
+    },
+    /// Change the description of a task
+    Revise {
+        /// Index of task being revised
+        task_index: usize,
+        /// New description for the task
+        description: String,
+    },
+    /// Hide a task without losing its history
+    Archive {
+        /// Index of task to archive
+        task_index: usize,
+    },
+    /// Restore an archived task
+    Unarchive {
+        /// Index of task to unarchive
+        task_index: usize,
+    },
+    /// Set when a task was archived (used to undo `Archive` and `Unarchive`)
+    SetArchived {
+        /// Index of task being archived or unarchived
+        task_index: usize,
+        /// When the task was archived, or `None` if it's active
+        archived: Option<DateTime<Utc>>,
+        /// Offset from UTC (in seconds) of the timezone the task was archived in, if it's known
+        #[serde(default)]
+        offset: Option<i32>,
+    },
+    /// Remove the latest revision of a task's details (used to undo `Revise`, `SetSchedule` and
+    /// `SetTarget`)
+    RemoveRevision {
+        /// Index of task to remove revision from
+        task_index: usize,
+        /// ID of the revision being removed (must be the latest revision)
+        revision_id: u64,
+    },
+    /// Put back a revision which was removed
+    RestoreRevision {
+        /// Index of task to restore revision to
+        task_index: usize,
+        /// The revision being restored
+        details: TaskDetails,
+    },
+    /// Give a task a slug, or remove its slug (`None`)
+    SetSlug {
+        /// Index of task to set the slug of
+        task_index: usize,
+        /// New slug for the task
+        slug: Option<String>,
+    },
+    /// Excuse a task (or every task) on a range of days, so that they don't break its chain
+    AddPause {
+        /// Index of task to pause (`None` => every task)
+        task_index: Option<usize>,
+        /// The days being excused, and why
+        pause: Pause,
+    },
+    /// Remove a pause (used to undo `AddPause`)
+    RemovePause {
+        /// Index of task to remove the pause from (`None` => the pause is for every task)
+        task_index: Option<usize>,
+        /// The pause being removed
+        pause: Pause,
+    },
+}
+
+impl TaskOperation {
+    /// Index of the existing task this operation acts on (if any)
+    pub fn task_index(&self) -> Option<usize> {
+        match self {
+            TaskOperation::Add { .. }
+            | TaskOperation::Undo
+            | TaskOperation::Redo
+            | TaskOperation::Insert { .. } => None,
+            TaskOperation::Reorder { from, .. } => Some(*from),
+            TaskOperation::MarkComplete { task_index, .. }
+            | TaskOperation::Uncomplete { task_index, .. }
+            | TaskOperation::AddRemark { task_index, .. }
+            | TaskOperation::SetSchedule { task_index, .. }
+            | TaskOperation::SetTarget { task_index, .. }
+            | TaskOperation::Delete { task_index }
+            | TaskOperation::RemoveCompletion { task_index, .. }
+            | TaskOperation::RestoreCompletion { task_index, .. }
+            | TaskOperation::RemoveRemark { task_index, .. }
+            | TaskOperation::RestoreRemark { task_index, .. }
+            | TaskOperation::Revise { task_index, .. }
+            | TaskOperation::Archive { task_index }
+            | TaskOperation::Unarchive { task_index }
+            | TaskOperation::SetArchived { task_index, .. }
+            | TaskOperation::RemoveRevision { task_index, .. }
+            | TaskOperation::RestoreRevision { task_index, .. }
+            | TaskOperation::SetSlug { task_index, .. } => Some(*task_index),
+            TaskOperation::AddPause { task_index, .. }
+            | TaskOperation::RemovePause { task_index, .. } => *task_index,
+        }
+    }
+}
+
+impl fmt::Display for TaskOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TaskOperation::Add {
+                description,
+                kind: TaskKind::Do,
+            } => write!(f, "add task \"{}\"", description),
+            TaskOperation::Add {
+                description,
+                kind: TaskKind::Avoid,
+            } => write!(f, "add task to avoid \"{}\"", description),
+            TaskOperation::MarkComplete {
+                task_index,
+                date,
+                amount: Some(amount),
+                ..
+            } => {
+                write!(
+                    f,
+                    "record {} for task {}",
+                    format_amount(*amount),
+                    task_index
+                )?;
+                match date {
+                    Some(date) => write!(f, " on {}", date),
+                    None => Ok(()),
+                }
+            }
+            TaskOperation::MarkComplete {
+                task_index,
+                date: None,
+                ..
+            } => write!(f, "mark task {} complete", task_index),
+            TaskOperation::MarkComplete {
+                task_index,
+                date: Some(date),
+                ..
+            } => write!(f, "mark task {} complete on {}", task_index, date),
+            TaskOperation::Uncomplete { task_index, date } => {
+                write!(f, "mark task {} incomplete on {}", task_index, date)
+            }
+            TaskOperation::AddRemark { task_index, .. } => {
+                write!(f, "remark on task {}", task_index)
+            }
+            TaskOperation::Reorder { from, to } => write!(f, "move task {} to {}", from, to),
+            TaskOperation::SetSchedule {
+                task_index,
+                schedule,
+            } => write!(f, "schedule task {} as {}", task_index, schedule),
+            TaskOperation::SetTarget {
+                task_index,
+                target: Some(target),
+            } => write!(f, "set target of task {} to {}", task_index, target),
+            TaskOperation::SetTarget {
+                task_index,
+                target: None,
+            } => write!(f, "remove target of task {}", task_index),
+            TaskOperation::Undo => f.write_str("undo"),
+            TaskOperation::Redo => f.write_str("redo"),
+            TaskOperation::Delete { task_index } => write!(f, "delete task {}", task_index),
+            TaskOperation::Insert { task_index, task } => {
+                write!(
+                    f,
+                    "insert task \"{}\" at {}",
+                    task.description(),
+                    task_index
+                )
+            }
+            TaskOperation::RemoveCompletion { task_index, .. } => {
+                write!(f, "remove completion from task {}", task_index)
+            }
+            TaskOperation::RestoreCompletion { task_index, .. } => {
+                write!(f, "restore completion to task {}", task_index)
+            }
+            TaskOperation::RemoveRemark { task_index, .. } => {
+                write!(f, "remove remark from task {}", task_index)
+            }
+            TaskOperation::RestoreRemark { task_index, .. } => {
+                write!(f, "restore remark to task {}", task_index)
+            }
+            TaskOperation::Revise {
+                task_index,
+                description,
+            } => write!(f, "rename task {} to \"{}\"", task_index, description),
+            TaskOperation::Archive { task_index } => write!(f, "archive task {}", task_index),
+            TaskOperation::Unarchive { task_index } => {
+                write!(f, "unarchive task {}", task_index)
+            }
+            TaskOperation::SetArchived {
+                task_index,
+                archived: Some(_),
+                ..
+            } => write!(f, "archive task {}", task_index),
+            TaskOperation::SetArchived {
+                task_index,
+                archived: None,
+                ..
+            } => write!(f, "unarchive task {}", task_index),
+            TaskOperation::RemoveRevision {
+                task_index,
+                revision_id,
+            } => write!(
+                f,
+                "remove revision {} from task {}",
+                revision_id, task_index
+            ),
+            TaskOperation::RestoreRevision {
+                task_index,
+                details,
+            } => write!(
+                f,
+                "restore revision {} to task {}",
+                details.revision_id(),
+                task_index
+            ),
+            TaskOperation::SetSlug {
+                task_index,
+                slug: Some(slug),
+            } => write!(f, "set slug of task {} to \"{}\"", task_index, slug),
+            TaskOperation::SetSlug {
+                task_index,
+                slug: None,
+            } => write!(f, "remove slug of task {}", task_index),
+            TaskOperation::AddPause {
+                task_index: Some(task_index),
+                pause,
+            } => write!(f, "pause task {} for {}", task_index, pause),
+            TaskOperation::AddPause {
+                task_index: None,
+                pause,
+            } => write!(f, "pause every task for {}", pause),
+            TaskOperation::RemovePause {
+                task_index: Some(task_index),
+                pause,
+            } => write!(f, "remove pause for {} from task {}", pause, task_index),
+            TaskOperation::RemovePause {
+                task_index: None,
+                pause,
+            } => write!(f, "remove pause for {} from every task", pause),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::TaskOperation;
+    use crate::structs::FixedClock;
+    use crate::structs::Pause;
+    use crate::structs::Schedule;
     use crate::structs::TaskError;
+    use crate::structs::TaskKind;
     use crate::structs::TaskListing;
+    use chrono::prelude::*;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    /// A clock stopped a second before midnight, where a test which takes more than a moment to
+    /// run would cross into another day with the system clock
+    fn clock() -> Arc<FixedClock> {
+        let now = Local.ymd(2019, 12, 1).and_hms(23, 59, 59);
+        Arc::new(FixedClock::new(now.with_timezone(&now.offset().fix())))
+    }
+
+    /// An empty listing which takes the time from `clock`
+    fn listing_with(clock: &Arc<FixedClock>) -> TaskListing {
+        let mut list = TaskListing::new();
+        list.set_clock(clock.clone());
+        list
+    }
+
+    /// An empty listing whose clock is stopped just before midnight
+    fn listing() -> TaskListing {
+        listing_with(&clock())
+    }
+
+    /// Path to a task file in an empty folder, so that tests can store listings without touching
+    /// the user's data
+    fn test_tasks_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chain-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("taskdata.ron")
+    }
 
     #[test]
     fn add_requires_description() {
-        let mut list = TaskListing::new();
+        let mut list = listing();
 
         let add = TaskOperation::Add {
             description: "".into(),
+            kind: TaskKind::Do,
         };
 
         let result = list.handle_operation(&add);
@@ -66,10 +434,11 @@ mod tests {
 
     #[test]
     fn adds_with_description() {
-        let mut list = TaskListing::new();
+        let mut list = listing();
 
         let add = TaskOperation::Add {
             description: "non-zero length".into(),
+            kind: TaskKind::Do,
         };
 
         let result = list.handle_operation(&add);
@@ -78,7 +447,7 @@ mod tests {
 
     #[test]
     fn reorder_no_tasks() {
-        let mut list = TaskListing::new();
+        let mut list = listing();
 
         let reorder = TaskOperation::Reorder { from: 0, to: 0 };
 
@@ -89,10 +458,11 @@ mod tests {
 
     #[test]
     fn reorder_same_indexes() {
-        let mut list = TaskListing::new();
+        let mut list = listing();
 
         let add = TaskOperation::Add {
             description: "first".into(),
+            kind: TaskKind::Do,
         };
         assert!(list.handle_operation(&add).is_ok());
 
@@ -105,7 +475,7 @@ mod tests {
 
     #[test]
     fn reorder_same_indexes_no_tasks() {
-        let mut list = TaskListing::new();
+        let mut list = listing();
 
         let reorder = TaskOperation::Reorder { from: 0, to: 0 };
 
@@ -116,10 +486,11 @@ mod tests {
 
     #[test]
     fn reorder_out_of_bounds() {
-        let mut list = TaskListing::new();
+        let mut list = listing();
 
         let add = TaskOperation::Add {
             description: "first".into(),
+            kind: TaskKind::Do,
         };
         assert!(list.handle_operation(&add).is_ok());
 
@@ -133,12 +504,14 @@ mod tests {
     #[test]
     fn mark_complete_oob() {
         // Both with and without a remark
-        let mut list = TaskListing::new();
+        let mut list = listing();
 
         // No Remark
         let complete = TaskOperation::MarkComplete {
             task_index: 0,
             remark: None,
+            date: None,
+            amount: None,
         };
 
         let result = list.handle_operation(&complete);
@@ -149,6 +522,8 @@ mod tests {
         let complete = TaskOperation::MarkComplete {
             task_index: 0,
             remark: Some("with a remark".into()),
+            date: None,
+            amount: None,
         };
 
         let result = list.handle_operation(&complete);
@@ -158,16 +533,19 @@ mod tests {
 
     #[test]
     fn mark_complete_no_remark() {
-        let mut list = TaskListing::new();
+        let mut list = listing();
 
         let add = TaskOperation::Add {
             description: "first".into(),
+            kind: TaskKind::Do,
         };
         assert!(list.handle_operation(&add).is_ok());
 
         let complete = TaskOperation::MarkComplete {
             task_index: 0,
             remark: None,
+            date: None,
+            amount: None,
         };
 
         let result = list.handle_operation(&complete);
@@ -176,16 +554,19 @@ mod tests {
 
     #[test]
     fn mark_complete_with_remark() {
-        let mut list = TaskListing::new();
+        let mut list = listing();
 
         let add = TaskOperation::Add {
             description: "first".into(),
+            kind: TaskKind::Do,
         };
         assert!(list.handle_operation(&add).is_ok());
 
         let complete = TaskOperation::MarkComplete {
             task_index: 0,
             remark: Some("with some remark".into()),
+            date: None,
+            amount: None,
         };
 
         let result = list.handle_operation(&complete);
@@ -194,18 +575,20 @@ mod tests {
 
     #[test]
     fn mark_complete_twice() {
-        // NOTE: this test could potentially fail if the two commands to mark the task complete
-        // happen on opposite sides of the "midnight" boundary. Unlikely, but possible.
-        let mut list = TaskListing::new();
+        let clock = clock();
+        let mut list = listing_with(&clock);
 
         let add = TaskOperation::Add {
             description: "first".into(),
+            kind: TaskKind::Do,
         };
         assert!(list.handle_operation(&add).is_ok());
 
         let complete = TaskOperation::MarkComplete {
             task_index: 0,
             remark: Some("with some remark".into()),
+            date: None,
+            amount: None,
         };
 
         let result = list.handle_operation(&complete);
@@ -214,11 +597,16 @@ mod tests {
         let result = list.handle_operation(&complete);
         assert!(result.is_err());
         assert!(result.unwrap_err() == TaskError::AlreadyCompleted);
+
+        // Once midnight passes, it's a new day
+        clock.advance(chrono::Duration::seconds(1));
+        assert!(list.handle_operation(&complete).is_ok());
+        assert_eq!(list.task_iter().next().unwrap().completions().len(), 2);
     }
 
     #[test]
     fn remark_oob() {
-        let mut list = TaskListing::new();
+        let mut list = listing();
 
         let remark = TaskOperation::AddRemark {
             task_index: 0,
@@ -232,10 +620,11 @@ mod tests {
 
     #[test]
     fn remark_on_incomplete() {
-        let mut list = TaskListing::new();
+        let mut list = listing();
 
         let add = TaskOperation::Add {
             description: "first".into(),
+            kind: TaskKind::Do,
         };
         assert!(list.handle_operation(&add).is_ok());
 
@@ -250,16 +639,19 @@ mod tests {
 
     #[test]
     fn remark_on_completed() {
-        let mut list = TaskListing::new();
+        let mut list = listing();
 
         let add = TaskOperation::Add {
             description: "first".into(),
+            kind: TaskKind::Do,
         };
         assert!(list.handle_operation(&add).is_ok());
 
         let complete = TaskOperation::MarkComplete {
             task_index: 0,
             remark: Some("with some remark".into()),
+            date: None,
+            amount: None,
         };
 
         let result = list.handle_operation(&complete);
@@ -273,4 +665,812 @@ mod tests {
         let result = list.handle_operation(&remark);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn set_schedule_oob() {
+        let mut list = listing();
+
+        let schedule = TaskOperation::SetSchedule {
+            task_index: 0,
+            schedule: Schedule::EveryNDays(2),
+        };
+
+        let result = list.handle_operation(&schedule);
+        assert!(result.is_err());
+        assert!(result.unwrap_err() == TaskError::NotFound);
+    }
+
+    #[test]
+    fn set_schedule() {
+        let mut list = listing();
+
+        let add = TaskOperation::Add {
+            description: "first".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(list.handle_operation(&add).is_ok());
+
+        let schedule = TaskOperation::SetSchedule {
+            task_index: 0,
+            schedule: "mon,wed,fri".parse().unwrap(),
+        };
+
+        let result = list.handle_operation(&schedule);
+        assert!(result.is_ok());
+
+        let task = list.task_iter().next().unwrap();
+        assert!(task.schedule().to_string() == "mon,wed,fri");
+    }
+
+    #[test]
+    fn undo_nothing() {
+        let mut list = listing();
+
+        let result = list.handle_operation(&TaskOperation::Undo);
+        assert!(result.is_err());
+        assert!(result.unwrap_err() == TaskError::NothingToUndo);
+
+        let result = list.handle_operation(&TaskOperation::Redo);
+        assert!(result.is_err());
+        assert!(result.unwrap_err() == TaskError::NothingToRedo);
+    }
+
+    #[test]
+    fn undo_redo_add() {
+        let mut list = listing();
+
+        let add = TaskOperation::Add {
+            description: "first".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(list.handle_operation(&add).is_ok());
+        assert!(list.total_tasks() == 1);
+
+        assert!(list.handle_operation(&TaskOperation::Undo).is_ok());
+        assert!(list.total_tasks() == 0);
+
+        assert!(list.handle_operation(&TaskOperation::Redo).is_ok());
+        assert!(list.total_tasks() == 1);
+        assert!(list.task_iter().next().unwrap().description() == "first");
+    }
+
+    #[test]
+    fn undo_redo_mark_complete() {
+        let mut list = listing();
+
+        let add = TaskOperation::Add {
+            description: "first".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(list.handle_operation(&add).is_ok());
+
+        let complete = TaskOperation::MarkComplete {
+            task_index: 0,
+            remark: None,
+            date: None,
+            amount: None,
+        };
+        assert!(list.handle_operation(&complete).is_ok());
+        let completed_at = list
+            .task_iter()
+            .next()
+            .unwrap()
+            .completed_today(list.today(), list.rules());
+        assert!(completed_at.is_some());
+
+        assert!(list.handle_operation(&TaskOperation::Undo).is_ok());
+        assert!(list
+            .task_iter()
+            .next()
+            .unwrap()
+            .completed_today(list.today(), list.rules())
+            .is_none());
+
+        // Redo restores the original completion rather than completing the task again
+        assert!(list.handle_operation(&TaskOperation::Redo).is_ok());
+        assert!(
+            list.task_iter()
+                .next()
+                .unwrap()
+                .completed_today(list.today(), list.rules())
+                == completed_at
+        );
+    }
+
+    #[test]
+    fn undo_redo_backdated_amounts() {
+        let clock = clock();
+        let mut list = listing_with(&clock);
+        let yesterday = list.today().naive_local();
+
+        for op in [
+            TaskOperation::Add {
+                description: "read".into(),
+                kind: TaskKind::Do,
+            },
+            TaskOperation::SetTarget {
+                task_index: 0,
+                target: Some("20 pages".parse().unwrap()),
+            },
+        ]
+        .iter()
+        {
+            assert!(list.handle_operation(op).is_ok());
+        }
+        clock.advance(chrono::Duration::days(1));
+
+        // Both amounts are recorded in the middle of the same day
+        for amount in [5.0, 12.0] {
+            let complete = TaskOperation::MarkComplete {
+                task_index: 0,
+                remark: None,
+                date: Some(yesterday),
+                amount: Some(amount),
+            };
+            assert!(list.handle_operation(&complete).is_ok());
+        }
+        let amounts = |list: &TaskListing| -> Vec<Option<f64>> {
+            let task = list.task_iter().next().unwrap();
+            task.completions().iter().map(|c| c.amount()).collect()
+        };
+
+        // Undo removes the amount which was recorded last, and redo puts back the same one
+        assert!(list.handle_operation(&TaskOperation::Undo).is_ok());
+        assert_eq!(amounts(&list), vec![Some(5.0)]);
+        assert!(list.handle_operation(&TaskOperation::Redo).is_ok());
+        assert_eq!(amounts(&list), vec![Some(5.0), Some(12.0)]);
+
+        assert!(list.handle_operation(&TaskOperation::Undo).is_ok());
+        assert!(list.handle_operation(&TaskOperation::Undo).is_ok());
+        assert!(amounts(&list).is_empty());
+    }
+
+    #[test]
+    fn mark_complete_future() {
+        let mut list = listing();
+
+        let add = TaskOperation::Add {
+            description: "first".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(list.handle_operation(&add).is_ok());
+
+        let complete = TaskOperation::MarkComplete {
+            task_index: 0,
+            remark: None,
+            date: Some(list.today().succ().naive_local()),
+            amount: None,
+        };
+        let result = list.handle_operation(&complete);
+        assert!(result.is_err());
+        assert!(result.unwrap_err() == TaskError::DateInFuture);
+    }
+
+    #[test]
+    fn mark_complete_before_creation() {
+        let mut list = listing();
+
+        let add = TaskOperation::Add {
+            description: "first".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(list.handle_operation(&add).is_ok());
+
+        let complete = TaskOperation::MarkComplete {
+            task_index: 0,
+            remark: None,
+            date: Some(list.today().pred().naive_local()),
+            amount: None,
+        };
+        let result = list.handle_operation(&complete);
+        assert!(result.is_err());
+        assert!(result.unwrap_err() == TaskError::DateBeforeCreation);
+    }
+
+    #[test]
+    fn uncomplete_not_completed() {
+        let mut list = listing();
+
+        let add = TaskOperation::Add {
+            description: "first".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(list.handle_operation(&add).is_ok());
+
+        let uncomplete = TaskOperation::Uncomplete {
+            task_index: 0,
+            date: list.today().naive_local(),
+        };
+        let result = list.handle_operation(&uncomplete);
+        assert!(result.is_err());
+        assert!(result.unwrap_err() == TaskError::NotCompleted);
+    }
+
+    #[test]
+    fn undo_uncomplete() {
+        let mut list = listing();
+
+        let add = TaskOperation::Add {
+            description: "first".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(list.handle_operation(&add).is_ok());
+
+        let complete = TaskOperation::MarkComplete {
+            task_index: 0,
+            remark: Some("remark".into()),
+            date: None,
+            amount: None,
+        };
+        assert!(list.handle_operation(&complete).is_ok());
+        let completed_at = list
+            .task_iter()
+            .next()
+            .unwrap()
+            .completed_today(list.today(), list.rules());
+
+        let uncomplete = TaskOperation::Uncomplete {
+            task_index: 0,
+            date: list.today().naive_local(),
+        };
+        assert!(list.handle_operation(&uncomplete).is_ok());
+        assert!(list
+            .task_iter()
+            .next()
+            .unwrap()
+            .completed_today(list.today(), list.rules())
+            .is_none());
+
+        // Undo restores the original completion, remark included
+        assert!(list.handle_operation(&TaskOperation::Undo).is_ok());
+        assert!(
+            list.task_iter()
+                .next()
+                .unwrap()
+                .completed_today(list.today(), list.rules())
+                == completed_at
+        );
+    }
+
+    #[test]
+    fn undo_reorder() {
+        let mut list = listing();
+
+        for description in &["first", "second", "third"] {
+            let add = TaskOperation::Add {
+                description: description.to_string(),
+                kind: TaskKind::Do,
+            };
+            assert!(list.handle_operation(&add).is_ok());
+        }
+
+        let reorder = TaskOperation::Reorder { from: 0, to: 2 };
+        assert!(list.handle_operation(&reorder).is_ok());
+        assert!(list.task_iter().nth(2).unwrap().description() == "first");
+
+        assert!(list.handle_operation(&TaskOperation::Undo).is_ok());
+        let descriptions: Vec<&String> = list.task_iter().map(|t| t.description()).collect();
+        assert!(descriptions == vec!["first", "second", "third"]);
+    }
+
+    #[test]
+    fn new_operation_clears_redo() {
+        let mut list = listing();
+
+        let add = TaskOperation::Add {
+            description: "first".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(list.handle_operation(&add).is_ok());
+        assert!(list.handle_operation(&TaskOperation::Undo).is_ok());
+        assert!(list.handle_operation(&add).is_ok());
+
+        let result = list.handle_operation(&TaskOperation::Redo);
+        assert!(result.is_err());
+        assert!(result.unwrap_err() == TaskError::NothingToRedo);
+    }
+
+    #[test]
+    fn revise_oob() {
+        let mut list = listing();
+
+        let revise = TaskOperation::Revise {
+            task_index: 0,
+            description: "renamed".into(),
+        };
+
+        let result = list.handle_operation(&revise);
+        assert!(result.is_err());
+        assert!(result.unwrap_err() == TaskError::NotFound);
+    }
+
+    #[test]
+    fn revise_requires_description() {
+        let mut list = listing();
+
+        let add = TaskOperation::Add {
+            description: "first".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(list.handle_operation(&add).is_ok());
+
+        let revise = TaskOperation::Revise {
+            task_index: 0,
+            description: "".into(),
+        };
+
+        let result = list.handle_operation(&revise);
+        assert!(result.is_err());
+        assert!(result.unwrap_err() == TaskError::MissingDescription);
+    }
+
+    #[test]
+    fn revise_appends_revision() {
+        let mut list = listing();
+
+        let add = TaskOperation::Add {
+            description: "first".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(list.handle_operation(&add).is_ok());
+
+        let revise = TaskOperation::Revise {
+            task_index: 0,
+            description: "renamed".into(),
+        };
+        assert!(list.handle_operation(&revise).is_ok());
+
+        let task = list.task_iter().next().unwrap();
+
+        // The current details are the latest revision
+        assert!(task.description() == "renamed");
+        assert!(task.details().unwrap().revision_id() == 1);
+
+        // Revisions are kept oldest first, so the task's creation time doesn't change
+        let revisions: Vec<(u64, &String)> = task
+            .revisions()
+            .map(|d| (d.revision_id(), d.description()))
+            .collect();
+        assert!(revisions == vec![(0, &"first".to_string()), (1, &"renamed".to_string())]);
+        assert!(task.revisions().next().unwrap().revised() <= task.details().unwrap().revised());
+    }
+
+    #[test]
+    fn undo_redo_revise() {
+        let mut list = listing();
+
+        let add = TaskOperation::Add {
+            description: "first".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(list.handle_operation(&add).is_ok());
+
+        let revise = TaskOperation::Revise {
+            task_index: 0,
+            description: "renamed".into(),
+        };
+        assert!(list.handle_operation(&revise).is_ok());
+
+        assert!(list.handle_operation(&TaskOperation::Undo).is_ok());
+        let task = list.task_iter().next().unwrap();
+        assert!(task.description() == "first");
+        assert!(task.revisions().count() == 1);
+
+        assert!(list.handle_operation(&TaskOperation::Redo).is_ok());
+        let task = list.task_iter().next().unwrap();
+        assert!(task.description() == "renamed");
+        assert!(task.revisions().count() == 2);
+    }
+
+    #[test]
+    fn archive_twice() {
+        let mut list = listing();
+
+        let add = TaskOperation::Add {
+            description: "first".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(list.handle_operation(&add).is_ok());
+
+        let archive = TaskOperation::Archive { task_index: 0 };
+        assert!(list.handle_operation(&archive).is_ok());
+        assert!(list.active_task_iter().count() == 0);
+
+        // Archived tasks are kept with their history
+        assert!(list.total_tasks() == 1);
+
+        let result = list.handle_operation(&archive);
+        assert!(result.is_err());
+        assert!(result.unwrap_err() == TaskError::AlreadyArchived);
+    }
+
+    #[test]
+    fn unarchive_active() {
+        let mut list = listing();
+
+        let add = TaskOperation::Add {
+            description: "first".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(list.handle_operation(&add).is_ok());
+
+        let unarchive = TaskOperation::Unarchive { task_index: 0 };
+        let result = list.handle_operation(&unarchive);
+        assert!(result.is_err());
+        assert!(result.unwrap_err() == TaskError::NotArchived);
+    }
+
+    #[test]
+    fn undo_archive() {
+        let mut list = listing();
+
+        let add = TaskOperation::Add {
+            description: "first".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(list.handle_operation(&add).is_ok());
+
+        let archive = TaskOperation::Archive { task_index: 0 };
+        assert!(list.handle_operation(&archive).is_ok());
+        assert!(list.handle_operation(&TaskOperation::Undo).is_ok());
+        assert!(list.active_task_iter().count() == 1);
+
+        // Unarchiving and undoing it keeps the timezone the task was archived in
+        assert!(list.handle_operation(&TaskOperation::Redo).is_ok());
+        let unarchive = TaskOperation::Unarchive { task_index: 0 };
+        assert!(list.handle_operation(&unarchive).is_ok());
+        assert!(list.handle_operation(&TaskOperation::Undo).is_ok());
+        let task = list.task_iter().next().unwrap();
+        assert_eq!(task.archived_offset(), Some(*list.now().offset()));
+    }
+
+    #[test]
+    fn delete_oob() {
+        let mut list = listing();
+
+        let delete = TaskOperation::Delete { task_index: 0 };
+        let result = list.handle_operation(&delete);
+        assert!(result.is_err());
+        assert!(result.unwrap_err() == TaskError::NotFound);
+    }
+
+    #[test]
+    fn undo_delete() {
+        let mut list = listing();
+
+        let add = TaskOperation::Add {
+            description: "first".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(list.handle_operation(&add).is_ok());
+
+        let complete = TaskOperation::MarkComplete {
+            task_index: 0,
+            remark: None,
+            date: None,
+            amount: None,
+        };
+        assert!(list.handle_operation(&complete).is_ok());
+
+        let delete = TaskOperation::Delete { task_index: 0 };
+        assert!(list.handle_operation(&delete).is_ok());
+        assert!(list.total_tasks() == 0);
+
+        // The task comes back along with its history
+        assert!(list.handle_operation(&TaskOperation::Undo).is_ok());
+        assert!(list.total_tasks() == 1);
+        assert!(list
+            .task_iter()
+            .next()
+            .unwrap()
+            .completed_today(list.today(), list.rules())
+            .is_some());
+    }
+
+    #[test]
+    fn store_reloads_changes_from_other_process() {
+        let path = test_tasks_path("store-reloads");
+
+        let mut first = listing();
+        let mut second = listing();
+
+        let add = TaskOperation::Add {
+            description: "first".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(first.handle_and_store(&add, &path).is_ok());
+
+        // `second` hasn't seen the new task yet, but completes it after reloading
+        let complete = TaskOperation::MarkComplete {
+            task_index: 0,
+            remark: None,
+            date: None,
+            amount: None,
+        };
+        assert!(second.handle_and_store(&complete, &path).is_ok());
+
+        // `first` doesn't overwrite the completion when it stores its next change
+        let add = TaskOperation::Add {
+            description: "second".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(first.handle_and_store(&add, &path).is_ok());
+        assert!(first.total_tasks() == 2);
+        assert!(first
+            .task_iter()
+            .next()
+            .unwrap()
+            .completed_today(first.today(), first.rules())
+            .is_some());
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn store_reloads_pauses_from_other_process() {
+        let path = test_tasks_path("store-reloads-pauses");
+
+        let mut first = listing();
+        let mut second = listing();
+
+        let add = TaskOperation::Add {
+            description: "first".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(first.handle_and_store(&add, &path).is_ok());
+
+        // `second` pauses every task after reloading
+        let today = second.today().naive_local();
+        let pause = Pause::new(today, today, Some("sick".into())).unwrap();
+        let add_pause = TaskOperation::AddPause {
+            task_index: None,
+            pause: pause.clone(),
+        };
+        assert!(second.handle_and_store(&add_pause, &path).is_ok());
+
+        // `first` sees the pause, and doesn't drop it when it stores its next change
+        let add = TaskOperation::Add {
+            description: "second".into(),
+            kind: TaskKind::Do,
+        };
+        assert!(first.handle_and_store(&add, &path).is_ok());
+        assert_eq!(first.pauses(), std::slice::from_ref(&pause));
+
+        let mut third = listing();
+        assert!(third.reload_if_changed(&path).unwrap());
+        assert_eq!(third.pauses(), &[pause]);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn store_conflict() {
+        let path = test_tasks_path("store-conflict");
+
+        let clock = clock();
+        let mut first = listing_with(&clock);
+        let mut second = listing_with(&clock);
+
+        for description in &["first", "second"] {
+            let add = TaskOperation::Add {
+                description: description.to_string(),
+                kind: TaskKind::Do,
+            };
+            assert!(first.handle_and_store(&add, &path).is_ok());
+        }
+        assert!(second.reload_if_changed(&path).unwrap());
+
+        let delete = TaskOperation::Delete { task_index: 0 };
+        assert!(first.handle_and_store(&delete, &path).is_ok());
+
+        // Task 0 isn't the task `second` thinks it is any more
+        let complete = TaskOperation::MarkComplete {
+            task_index: 0,
+            remark: None,
+            date: None,
+            amount: None,
+        };
+        let result = second.handle_and_store(&complete, &path);
+        assert!(result.is_err());
+        assert!(result.unwrap_err() == TaskError::Conflict(Box::new(TaskError::NotFound)));
+        assert!(second
+            .task_iter()
+            .next()
+            .unwrap()
+            .completed_today(second.today(), second.rules())
+            .is_none());
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    /// A listing with a task for each description
+    fn listing_of(descriptions: &[&str]) -> TaskListing {
+        let mut list = listing();
+        for description in descriptions {
+            let add = TaskOperation::Add {
+                description: description.to_string(),
+                kind: TaskKind::Do,
+            };
+            assert!(list.handle_operation(&add).is_ok());
+        }
+        list
+    }
+
+    #[test]
+    fn tasks_get_unique_ids() {
+        let list = listing_of(&["gym", "gym", "read"]);
+
+        let ids: Vec<&str> = list.task_iter().map(|task| task.id()).collect();
+        assert!(ids.iter().all(|id| id.len() == 6));
+        assert!(ids.iter().all(|id| id.parse::<usize>().is_err()));
+        assert_ne!(ids[0], ids[1]);
+        assert_ne!(ids[1], ids[2]);
+        assert!(list.problems().is_empty());
+    }
+
+    #[test]
+    fn find_task() {
+        let mut list = listing_of(&["Go to the gym", "Read a book", "Read the news"]);
+        let id = list.task_iter().nth(1).unwrap().id().to_string();
+
+        assert_eq!(list.find("2"), Ok(2));
+        assert_eq!(list.find(&id), Ok(1));
+        assert_eq!(list.find("go"), Ok(0));
+        assert_eq!(list.find("read the"), Ok(2));
+        assert_eq!(list.find("3"), Err(TaskError::NotFound));
+        assert_eq!(list.find("swim"), Err(TaskError::NotFound));
+        assert!(matches!(
+            list.find("read"),
+            Err(TaskError::Ambiguous(_, matches)) if matches.len() == 2
+        ));
+
+        let set_slug = TaskOperation::SetSlug {
+            task_index: 2,
+            slug: Some("news".into()),
+        };
+        assert!(list.handle_operation(&set_slug).is_ok());
+        assert_eq!(list.find("news"), Ok(2));
+
+        // IDs and slugs stay with their task when it moves
+        assert!(list
+            .handle_operation(&TaskOperation::Reorder { from: 2, to: 0 })
+            .is_ok());
+        assert_eq!(list.find("news"), Ok(0));
+        assert_eq!(list.find(&id), Ok(2));
+    }
+
+    #[test]
+    fn find_task_by_out_of_range_index() {
+        let list = listing_of(&["meditate", "read 12 pages", "gym"]);
+
+        // A mistyped index doesn't fall back to a description which happens to contain it
+        assert_eq!(list.find("1"), Ok(1));
+        assert_eq!(list.find("12"), Err(TaskError::NotFound));
+        assert_eq!(list.find("12 pages"), Ok(1));
+    }
+
+    #[test]
+    fn find_task_by_fuzzy_description() {
+        let list = listing_of(&[
+            "Go to the gym",
+            "Gym class",
+            "Read a book",
+            "Practice guitar",
+        ]);
+
+        // The better match wins
+        assert_eq!(list.find("gym"), Ok(1));
+        assert_eq!(list.find("the gym"), Ok(0));
+        assert_eq!(list.find("book"), Ok(2));
+        assert_eq!(list.find("guitar practice"), Ok(3));
+        assert_eq!(list.find("rdbk"), Ok(2));
+
+        // Equally good matches are ambiguous, and listed in order
+        assert_eq!(
+            list.find("g"),
+            Err(TaskError::Ambiguous(
+                "g".into(),
+                vec![(0, "Go to the gym".into()), (1, "Gym class".into())]
+            ))
+        );
+    }
+
+    #[test]
+    fn set_slug() {
+        let mut list = listing_of(&["first", "second"]);
+        let id = list.task_iter().next().unwrap().id().to_string();
+
+        let set_slug = |task_index: usize, slug: &str| TaskOperation::SetSlug {
+            task_index,
+            slug: Some(slug.into()),
+        };
+
+        assert!(list.handle_operation(&set_slug(0, "one")).is_ok());
+        assert_eq!(list.task_iter().next().unwrap().slug(), Some("one"));
+
+        for invalid in &["", "One", "12", "no spaces", &"x".repeat(33)] {
+            assert_eq!(
+                list.handle_operation(&set_slug(1, invalid)),
+                Err(TaskError::InvalidSlug(invalid.to_string()))
+            );
+        }
+        assert_eq!(
+            list.handle_operation(&set_slug(1, "one")),
+            Err(TaskError::SlugTaken("one".into()))
+        );
+        assert_eq!(
+            list.handle_operation(&set_slug(1, &id)),
+            Err(TaskError::SlugTaken(id.clone()))
+        );
+
+        // Setting a task's slug again is fine, and undoing it puts the old one back
+        assert!(list.handle_operation(&set_slug(0, "first-task")).is_ok());
+        assert!(list.handle_operation(&TaskOperation::Undo).is_ok());
+        assert_eq!(list.task_iter().next().unwrap().slug(), Some("one"));
+
+        let clear = TaskOperation::SetSlug {
+            task_index: 0,
+            slug: None,
+        };
+        assert!(list.handle_operation(&clear).is_ok());
+        assert_eq!(list.task_iter().next().unwrap().slug(), None);
+    }
+
+    #[test]
+    fn repair_duplicate_ids_and_slugs() {
+        let mut list = listing_of(&["first", "second"]);
+        let id = list.task_iter().next().unwrap().id().to_string();
+        for task in list.task_iter_mut() {
+            task.set_id(id.clone());
+            task.set_slug(Some("same".into()));
+        }
+
+        assert_eq!(list.problems().len(), 2);
+        assert_eq!(list.repair().len(), 2);
+        assert!(list.problems().is_empty());
+        assert_eq!(list.task_iter().next().unwrap().id(), id);
+        assert_eq!(list.task_iter().next().unwrap().slug(), Some("same"));
+        assert_eq!(list.task_iter().nth(1).unwrap().slug(), None);
+    }
+
+    #[test]
+    fn undo_redo_pause() {
+        let mut list = listing_of(&["first", "second"]);
+        let today = list.today().naive_local();
+        let pause = Pause::new(today, today, Some("sick".into())).unwrap();
+
+        // A pause for one task
+        let add = TaskOperation::AddPause {
+            task_index: Some(1),
+            pause: pause.clone(),
+        };
+        assert!(list.handle_operation(&add).is_ok());
+        assert_eq!(list.task_iter().nth(1).unwrap().pauses().len(), 1);
+        assert!(list.task_iter().next().unwrap().pauses().is_empty());
+        assert!(!list.list_for_today()[1].due);
+
+        assert!(list.handle_operation(&TaskOperation::Undo).is_ok());
+        assert!(list.task_iter().nth(1).unwrap().pauses().is_empty());
+        assert!(list.list_for_today()[1].due);
+
+        // A pause for every task
+        let add_all = TaskOperation::AddPause {
+            task_index: None,
+            pause: pause.clone(),
+        };
+        assert!(list.handle_operation(&add_all).is_ok());
+        assert_eq!(list.pauses().len(), 1);
+        assert!(list.list_for_today().iter().all(|entry| !entry.due));
+
+        let remove_all = TaskOperation::RemovePause {
+            task_index: None,
+            pause: pause.clone(),
+        };
+        assert!(list.handle_operation(&remove_all).is_ok());
+        assert!(list.pauses().is_empty());
+        assert_eq!(list.handle_operation(&remove_all), Err(TaskError::NotFound));
+
+        assert!(list.handle_operation(&TaskOperation::Undo).is_ok());
+        assert_eq!(list.pauses(), &[pause]);
+    }
 }
@@ -0,0 +1,991 @@
+/*
+ * Copyright (c) 2019 John Ferguson
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use chrono::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use super::ChainError;
+use super::DayRules;
+use super::Pause;
+use super::Schedule;
+use super::TaskError;
+use super::TaskKind;
+use super::TaskListing;
+use super::{Completion, Remark, Task, TaskDetails};
+
+/// Version of the export format written by this version of chain
+pub const EXPORT_VERSION: u32 = 1;
+
+/// A copy of every task, which can be imported again elsewhere. Each task is kept whole, with
+/// every revision, completion and remark (and their original timestamps), along with the pauses
+/// for every task.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Export {
+    /// Version of the export format
+    pub version: u32,
+    /// When the export was made
+    pub exported: DateTime<Utc>,
+    /// Every task, in the order they're listed
+    pub tasks: Vec<Task>,
+    /// Ranges of days on which every task is excused, oldest first. Older exports don't have
+    /// these.
+    #[serde(default)]
+    pub pauses: Vec<Pause>,
+}
+
+/// The kind of record held by a row of CSV export
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RecordKind {
+    /// A revision of a task's details
+    Revision,
+    /// A completion, with an optional remark
+    Completion,
+    /// A remark which isn't attached to a completion
+    Remark,
+    /// When a task was archived
+    Archived,
+    /// Days on which a task is excused
+    Pause,
+    /// Days on which every task is excused
+    GlobalPause,
+}
+
+/// A single row of CSV export. Rows for the same task have the same `task` number, and are in the
+/// same order as in the task itself.
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvRecord {
+    /// Position of the task in the export (for pauses of every task, the number of tasks)
+    task: usize,
+    kind: RecordKind,
+    /// Timestamp of the revision, completion, remark or archiving (for pauses, midnight UTC at the
+    /// start of the first day)
+    datetime: DateTime<Utc>,
+    /// Only for revisions
+    revision_id: Option<u64>,
+    /// Only for revisions
+    description: Option<String>,
+    /// Only for revisions
+    schedule: Option<String>,
+    /// For remarks, completions which had one, and the reasons for pauses
+    remark: Option<String>,
+    /// Only for completions with a remark
+    remark_datetime: Option<DateTime<Utc>>,
+    /// For revisions, completions, remarks and archiving: offset from UTC (in seconds) of the
+    /// timezone they were recorded in, if it's known. Older exports don't have this column, and
+    /// only had it for completions for a while.
+    #[serde(default)]
+    utc_offset: Option<i32>,
+    /// Only for revisions: the task's ID. Older exports don't have this column.
+    #[serde(default)]
+    id: Option<String>,
+    /// Only for revisions: the task's slug, if it has one. Older exports don't have this column.
+    #[serde(default)]
+    slug: Option<String>,
+    /// Only for revisions: the daily target (e.g. `20 pages`), if there is one. Older exports
+    /// don't have this column.
+    #[serde(default)]
+    target: Option<String>,
+    /// Only for completions: the amount recorded, if there is one. Older exports don't have this
+    /// column.
+    #[serde(default)]
+    amount: Option<f64>,
+    /// Only for revisions: whether the task is something to do or something to avoid. Older
+    /// exports don't have this column.
+    #[serde(default)]
+    task_kind: Option<String>,
+    /// Only for pauses: the last day of the pause. Older exports don't have this column.
+    #[serde(default)]
+    until: Option<NaiveDate>,
+}
+
+/// A task which is being read from CSV, one record at a time
+struct PartialTask {
+    /// Position of the task in the export
+    number: usize,
+    id: Option<String>,
+    slug: Option<String>,
+    kind: TaskKind,
+    detail_history: Vec<TaskDetails>,
+    completions: Vec<Completion>,
+    remarks: Vec<Remark>,
+    archived: Option<(DateTime<Utc>, Option<FixedOffset>)>,
+    pauses: Vec<Pause>,
+}
+
+impl PartialTask {
+    fn new(number: usize) -> PartialTask {
+        PartialTask {
+            number,
+            id: None,
+            slug: None,
+            kind: TaskKind::Do,
+            detail_history: Vec::new(),
+            completions: Vec::new(),
+            remarks: Vec::new(),
+            archived: None,
+            pauses: Vec::new(),
+        }
+    }
+
+    fn into_task(self) -> Task {
+        let mut task = Task::from_parts(
+            self.detail_history,
+            self.completions,
+            self.remarks,
+            self.archived,
+        )
+        .with_kind(self.kind);
+        task.set_id(self.id.unwrap_or_default());
+        task.set_slug(self.slug);
+        for pause in self.pauses {
+            task.add_pause(pause);
+        }
+        task
+    }
+}
+
+impl Export {
+    /// Copy every task in `listing` (and its pauses for every task), as of the listing's current
+    /// time. If `since` is given, only completions and remarks made on or after that date are
+    /// included.
+    pub fn new(listing: &TaskListing, since: Option<Date<Local>>) -> Export {
+        Export {
+            version: EXPORT_VERSION,
+            exported: listing.now().with_timezone(&Utc),
+            tasks: listing
+                .task_iter()
+                .cloned()
+                .map(|mut task| {
+                    if let Some(since) = since {
+                        task.retain_since(since, listing.rules());
+                    }
+                    task
+                })
+                .collect(),
+            pauses: listing.pauses().to_vec(),
+        }
+    }
+
+    /// Serialize as RON (the same format chain stores tasks in)
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, PrettyConfig::default())
+            .expect("couldn't serialize export as RON")
+    }
+
+    /// Serialize as JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("couldn't serialize export as JSON")
+    }
+
+    /// Serialize as CSV, with one row for each revision, completion, remark and pause. The
+    /// export's version and timestamp aren't included.
+    pub fn to_csv(&self) -> String {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+
+        for (n, task) in self.tasks.iter().enumerate() {
+            let revisions = task.revisions().map(|details| CsvRecord {
+                task: n,
+                kind: RecordKind::Revision,
+                datetime: details.revised(),
+                revision_id: Some(details.revision_id()),
+                description: Some(details.description().clone()),
+                schedule: Some(details.schedule().to_string()),
+                remark: None,
+                remark_datetime: None,
+                utc_offset: details.offset().map(|offset| offset.local_minus_utc()),
+                id: Some(task.id().to_string()).filter(|id| !id.is_empty()),
+                slug: task.slug().map(str::to_string),
+                target: details.target().map(|target| target.to_string()),
+                amount: None,
+                task_kind: Some(task.kind().to_string()),
+                until: None,
+            });
+            let completions = task.completions().iter().map(|completion| CsvRecord {
+                task: n,
+                kind: RecordKind::Completion,
+                datetime: completion.datetime(),
+                revision_id: None,
+                description: None,
+                schedule: None,
+                remark: completion.remark().map(|r| r.remark().to_string()),
+                remark_datetime: completion.remark().map(|r| r.datetime()),
+                utc_offset: completion.offset().map(|offset| offset.local_minus_utc()),
+                id: None,
+                slug: None,
+                target: None,
+                amount: completion.amount(),
+                task_kind: None,
+                until: None,
+            });
+            let remarks = task.remarks().iter().map(|remark| CsvRecord {
+                task: n,
+                kind: RecordKind::Remark,
+                datetime: remark.datetime(),
+                revision_id: None,
+                description: None,
+                schedule: None,
+                remark: Some(remark.remark().to_string()),
+                remark_datetime: None,
+                utc_offset: remark.offset().map(|offset| offset.local_minus_utc()),
+                id: None,
+                slug: None,
+                target: None,
+                amount: None,
+                task_kind: None,
+                until: None,
+            });
+            let archived = task.archived().map(|datetime| CsvRecord {
+                task: n,
+                kind: RecordKind::Archived,
+                datetime,
+                revision_id: None,
+                description: None,
+                schedule: None,
+                remark: None,
+                remark_datetime: None,
+                utc_offset: task
+                    .archived_offset()
+                    .map(|offset| offset.local_minus_utc()),
+                id: None,
+                slug: None,
+                target: None,
+                amount: None,
+                task_kind: None,
+                until: None,
+            });
+            let pauses = task
+                .pauses()
+                .iter()
+                .map(|pause| pause_record(n, RecordKind::Pause, pause));
+
+            for record in revisions
+                .chain(completions)
+                .chain(remarks)
+                .chain(archived)
+                .chain(pauses)
+            {
+                writer
+                    .serialize(record)
+                    .expect("couldn't serialize export as CSV");
+            }
+        }
+
+        for pause in self.pauses.iter() {
+            writer
+                .serialize(pause_record(
+                    self.tasks.len(),
+                    RecordKind::GlobalPause,
+                    pause,
+                ))
+                .expect("couldn't serialize export as CSV");
+        }
+
+        String::from_utf8(
+            writer
+                .into_inner()
+                .expect("couldn't serialize export as CSV"),
+        )
+        .expect("CSV export wasn't UTF-8")
+    }
+
+    /// Parse an export in RON, as read from `path` (which is only used to describe errors).
+    /// Completions are placed on days as `rules` define them.
+    pub fn from_ron(path: &Path, contents: &str, rules: &DayRules) -> Result<Export, ChainError> {
+        let export: Export =
+            ron::de::from_str(contents).map_err(|e| ChainError::parse(path, contents, e))?;
+
+        export.validate(path, rules)
+    }
+
+    /// Parse an export in JSON, as read from `path` (which is only used to describe errors).
+    /// Completions are placed on days as `rules` define them.
+    pub fn from_json(path: &Path, contents: &str, rules: &DayRules) -> Result<Export, ChainError> {
+        let export: Export = serde_json::from_str(contents).map_err(|e| {
+            let position = Some((e.line(), e.column())).filter(|(line, _)| *line > 0);
+            // The position is reported separately
+            let message = e.to_string();
+            let message = match message.rfind(" at line ") {
+                Some(end) if position.is_some() => message[..end].to_string(),
+                _ => message,
+            };
+            ChainError::syntax(path, contents, message, position)
+        })?;
+
+        export.validate(path, rules)
+    }
+
+    /// Parse an export in CSV, as read from `path` (which is only used to describe errors).
+    /// Completions are placed on days as `rules` define them. CSV doesn't say when the export was
+    /// made, so it's taken to have been made `now`.
+    pub fn from_csv(
+        path: &Path,
+        contents: &str,
+        rules: &DayRules,
+        now: DateTime<FixedOffset>,
+    ) -> Result<Export, ChainError> {
+        let mut reader = csv::Reader::from_reader(contents.as_bytes());
+        let mut tasks: Vec<Task> = Vec::new();
+        let mut current: Option<PartialTask> = None;
+        let mut pauses: Vec<Pause> = Vec::new();
+
+        let mut problems = Vec::new();
+
+        for result in reader.deserialize::<CsvRecord>() {
+            let record = result.map_err(|e| {
+                let position = e.position().map(|position| (position.line() as usize, 1));
+                ChainError::syntax(path, contents, e.to_string(), position)
+            })?;
+
+            // Pauses for every task don't belong to any task
+            if record.kind == RecordKind::GlobalPause {
+                match record_pause(&record) {
+                    Ok(pause) => pauses.push(pause),
+                    Err(e) => problems.push(format!("pause for every task: {}", e)),
+                }
+                continue;
+            }
+
+            // Records for each task are grouped together, so a new number starts a new task
+            if current.as_ref().map(|partial| partial.number) != Some(record.task) {
+                tasks.extend(current.take().map(PartialTask::into_task));
+                current = Some(PartialTask::new(record.task));
+            }
+            let partial = current.as_mut().unwrap();
+
+            match record.kind {
+                RecordKind::Revision => {
+                    partial.id = record.id.or(partial.id.take());
+                    partial.slug = record.slug.or(partial.slug.take());
+                    if let Some(kind) = record.task_kind {
+                        match kind.parse() {
+                            Ok(kind) => partial.kind = kind,
+                            Err(e) => problems.push(format!("task {}: {}", partial.number, e)),
+                        }
+                    }
+                    let schedule = match record.schedule.as_deref().unwrap_or("daily").parse() {
+                        Ok(schedule) => schedule,
+                        Err(e) => {
+                            problems.push(format!("task {}: {}", partial.number, e));
+                            Schedule::default()
+                        }
+                    };
+                    let revision_id = record
+                        .revision_id
+                        .unwrap_or(partial.detail_history.len() as u64);
+                    let target = match record.target.as_deref().map(str::parse).transpose() {
+                        Ok(target) => target,
+                        Err(e) => {
+                            problems.push(format!("task {}: {}", partial.number, e));
+                            None
+                        }
+                    };
+                    partial.detail_history.push(
+                        TaskDetails::revision(
+                            record.datetime,
+                            record.utc_offset.and_then(FixedOffset::east_opt),
+                            revision_id,
+                            record.description.unwrap_or_default(),
+                            schedule,
+                        )
+                        .with_target(target),
+                    )
+                }
+                RecordKind::Completion => {
+                    // A completion's remark is made at the same time (or, for a backdated
+                    // completion, on the same clock) as the completion, so it has the same offset
+                    let remark_datetime = record.remark_datetime.unwrap_or(record.datetime);
+                    let offset = record.utc_offset.and_then(FixedOffset::east_opt);
+                    partial.completions.push(
+                        Completion::new(
+                            record.datetime,
+                            offset,
+                            record
+                                .remark
+                                .map(|remark| Remark::new(remark_datetime, offset, remark)),
+                        )
+                        .with_amount(record.amount),
+                    )
+                }
+                RecordKind::Remark => partial.remarks.push(Remark::new(
+                    record.datetime,
+                    record.utc_offset.and_then(FixedOffset::east_opt),
+                    record.remark.unwrap_or_default(),
+                )),
+                RecordKind::Archived => {
+                    partial.archived = Some((
+                        record.datetime,
+                        record.utc_offset.and_then(FixedOffset::east_opt),
+                    ))
+                }
+                RecordKind::Pause => match record_pause(&record) {
+                    Ok(pause) => partial.pauses.push(pause),
+                    Err(e) => problems.push(format!("task {}: {}", partial.number, e)),
+                },
+                RecordKind::GlobalPause => unreachable!("pauses for every task are read above"),
+            }
+        }
+
+        tasks.extend(current.take().map(PartialTask::into_task));
+
+        if !problems.is_empty() {
+            return Err(ChainError::Validation {
+                path: path.to_path_buf(),
+                problems,
+            });
+        }
+
+        Export {
+            version: EXPORT_VERSION,
+            exported: now.with_timezone(&Utc),
+            tasks,
+            pauses,
+        }
+        .validate(path, rules)
+    }
+
+    /// Check that the export can be imported (with days as `rules` define them), returning it if
+    /// it can
+    fn validate(self, path: &Path, rules: &DayRules) -> Result<Export, ChainError> {
+        if self.version > EXPORT_VERSION {
+            return Err(TaskError::UnsupportedVersion(self.version).into());
+        }
+
+        let mut problems = Vec::new();
+        for (n, task) in self.tasks.iter().enumerate() {
+            problems.extend(
+                task.problems()
+                    .into_iter()
+                    .map(|problem| format!("task {}: {}", n, problem)),
+            );
+
+            if task
+                .details()
+                .is_some_and(|details| details.description().is_empty())
+            {
+                problems.push(format!("task {}: has no description", n));
+            }
+
+            // Partial amounts and slips can share a day, whole completions can't
+            let mut days: Vec<NaiveDate> = task
+                .completions()
+                .iter()
+                .filter(|completion| completion.amount().is_none())
+                .map(|completion| completion.day(rules))
+                .collect();
+            days.sort();
+            if task.kind() == TaskKind::Do && days.windows(2).any(|pair| pair[0] == pair[1]) {
+                problems.push(format!(
+                    "task {}: was completed more than once on the same day",
+                    n
+                ));
+            }
+        }
+
+        for pause in self.pauses.iter().filter(|pause| !pause.is_valid()) {
+            problems.push(format!(
+                "the pause for every task from {} to {} ends before it starts",
+                pause.from(),
+                pause.to()
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(self)
+        } else {
+            Err(ChainError::Validation {
+                path: path.to_path_buf(),
+                problems,
+            })
+        }
+    }
+}
+
+/// A row of CSV export for a pause (of task number `task`, or of every task)
+fn pause_record(task: usize, kind: RecordKind, pause: &Pause) -> CsvRecord {
+    CsvRecord {
+        task,
+        kind,
+        datetime: Utc.from_utc_datetime(&pause.from().and_hms(0, 0, 0)),
+        revision_id: None,
+        description: None,
+        schedule: None,
+        remark: pause.reason().map(str::to_string),
+        remark_datetime: None,
+        utc_offset: None,
+        id: None,
+        slug: None,
+        target: None,
+        amount: None,
+        task_kind: None,
+        until: Some(pause.to()),
+    }
+}
+
+/// Read the pause held by a row of CSV export
+fn record_pause(record: &CsvRecord) -> Result<Pause, TaskError> {
+    let from = record.datetime.naive_utc().date();
+    let to = record.until.unwrap_or(from);
+    Pause::new(from, to, record.remark.clone())
+}
+
+/// How imported tasks are combined with the existing ones
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportMode {
+    /// Add new tasks, and add whatever's missing to tasks which already exist (and pauses for
+    /// every task which don't exist yet)
+    Merge,
+    /// Replace every task (and pause for every task) with the imported ones
+    Replace,
+}
+
+/// What was added to a single task when merging
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Additions {
+    /// Number of revisions added
+    pub revisions: usize,
+    /// Number of completions added
+    pub completions: usize,
+    /// Number of remarks added
+    pub remarks: usize,
+    /// Number of pauses added
+    pub pauses: usize,
+}
+
+impl Additions {
+    /// Returns true if nothing was added
+    pub fn is_empty(&self) -> bool {
+        *self == Additions::default()
+    }
+}
+
+/// A description of what an import changed
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    /// Descriptions of the tasks which were added
+    pub added: Vec<String>,
+    /// Descriptions of existing tasks which something was added to, along with what was added
+    pub merged: Vec<(String, Additions)>,
+    /// Number of imported tasks which already existed, and had nothing new
+    pub unchanged: usize,
+    /// Number of imported tasks which were left out, as they were repeated in the import
+    pub duplicates: usize,
+    /// Number of existing tasks which were removed (when replacing)
+    pub removed: usize,
+    /// Number of pauses for every task which were added
+    pub pauses_added: usize,
+    /// Number of existing pauses for every task which were removed (when replacing)
+    pub pauses_removed: usize,
+}
+
+impl ImportSummary {
+    /// Returns true if the import didn't change anything
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.merged.is_empty()
+            && self.removed == 0
+            && self.pauses_added == 0
+            && self.pauses_removed == 0
+    }
+}
+
+/// Returns true if `a` and `b` are the same task. Tasks are recognised by their IDs, falling back to
+/// the time they were created for exports written before tasks had IDs.
+fn same_task(a: &Task, b: &Task) -> bool {
+    if a.id().is_empty() || b.id().is_empty() {
+        a.created() == b.created()
+    } else {
+        a.id() == b.id()
+    }
+}
+
+/// Import tasks into `listing`. Tasks are recognised by their IDs, so importing an export of the
+/// same tasks more than once doesn't duplicate anything.
+///
+/// This only changes the listing in memory. Merging keeps the undo history (as tasks are only
+/// added to), but replacing discards it.
+pub fn import(listing: &mut TaskListing, export: Export, mode: ImportMode) -> ImportSummary {
+    let mut summary = ImportSummary::default();
+
+    // Leave out tasks which are repeated in the import itself
+    let mut tasks: Vec<Task> = Vec::new();
+    for task in export.tasks {
+        if tasks.iter().any(|t| same_task(t, &task)) {
+            summary.duplicates += 1;
+        } else {
+            tasks.push(task);
+        }
+    }
+
+    match mode {
+        ImportMode::Replace => {
+            summary.added = tasks
+                .iter()
+                .map(|task| task.description().clone())
+                .collect();
+            summary.removed = listing.replace_tasks(tasks).len();
+            summary.pauses_added = export.pauses.len();
+            summary.pauses_removed = listing.replace_pauses(export.pauses).len();
+        }
+        ImportMode::Merge => {
+            let rules = listing.rules().clone();
+            for task in tasks {
+                let existing = listing
+                    .task_iter_mut()
+                    .find(|existing| same_task(existing, &task));
+
+                match existing {
+                    Some(existing) => {
+                        let additions = existing.merge(task, &rules);
+                        if additions.is_empty() {
+                            summary.unchanged += 1;
+                        } else {
+                            summary
+                                .merged
+                                .push((existing.description().clone(), additions));
+                        }
+                    }
+                    None => {
+                        summary.added.push(task.description().clone());
+                        listing.push(task);
+                    }
+                }
+            }
+
+            for pause in export.pauses {
+                if !listing.pauses().contains(&pause) {
+                    listing.insert_pause(pause);
+                    summary.pauses_added += 1;
+                }
+            }
+        }
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{import, Export, ImportMode};
+    use crate::structs::{DayRules, FixedClock, Pause, Task, TaskKind, TaskListing, TaskOperation};
+    use chrono::prelude::*;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    /// When the tests run
+    fn now() -> DateTime<FixedOffset> {
+        let now = Local.ymd(2019, 12, 1).and_hms(12, 0, 0);
+        now.with_timezone(&now.offset().fix())
+    }
+
+    /// An empty listing whose clock is stopped at `now()`
+    fn empty() -> TaskListing {
+        let mut list = TaskListing::new();
+        list.set_clock(Arc::new(FixedClock::new(now())));
+        list
+    }
+
+    /// A listing with a completed task (with a remark), a renamed and paused task, an archived
+    /// task, and a pause for every task
+    fn listing() -> TaskListing {
+        let mut list = TaskListing::new();
+        let clock = Arc::new(FixedClock::new(now()));
+        list.set_clock(clock.clone());
+        for op in [
+            TaskOperation::Add {
+                description: "read".to_string(),
+                kind: TaskKind::Do,
+            },
+            TaskOperation::Add {
+                description: "gym".to_string(),
+                kind: TaskKind::Do,
+            },
+            TaskOperation::Add {
+                description: "old".to_string(),
+                kind: TaskKind::Avoid,
+            },
+            TaskOperation::SetTarget {
+                task_index: 0,
+                target: Some("20 pages".parse().unwrap()),
+            },
+            TaskOperation::MarkComplete {
+                task_index: 0,
+                remark: Some("chapter 3, finally".to_string()),
+                date: None,
+                amount: None,
+            },
+            TaskOperation::MarkComplete {
+                task_index: 0,
+                remark: None,
+                date: None,
+                amount: Some(2.5),
+            },
+            TaskOperation::AddRemark {
+                task_index: 1,
+                remark: "sore".to_string(),
+            },
+            TaskOperation::Revise {
+                task_index: 1,
+                description: "gym, \"legs\"".to_string(),
+            },
+            TaskOperation::AddPause {
+                task_index: Some(1),
+                pause: Pause::new(
+                    NaiveDate::from_ymd_opt(2019, 12, 1).unwrap(),
+                    NaiveDate::from_ymd_opt(2019, 12, 7).unwrap(),
+                    Some("vacation, at last".to_string()),
+                )
+                .unwrap(),
+            },
+            TaskOperation::AddPause {
+                task_index: Some(1),
+                pause: Pause::new(
+                    NaiveDate::from_ymd_opt(2019, 11, 20).unwrap(),
+                    NaiveDate::from_ymd_opt(2019, 11, 20).unwrap(),
+                    None,
+                )
+                .unwrap(),
+            },
+            TaskOperation::AddPause {
+                task_index: None,
+                pause: Pause::new(
+                    NaiveDate::from_ymd_opt(2019, 11, 25).unwrap(),
+                    NaiveDate::from_ymd_opt(2019, 11, 26).unwrap(),
+                    Some("holiday, finally".to_string()),
+                )
+                .unwrap(),
+            },
+            TaskOperation::MarkComplete {
+                task_index: 2,
+                remark: None,
+                date: None,
+                amount: None,
+            },
+            TaskOperation::MarkComplete {
+                task_index: 2,
+                remark: Some("again".to_string()),
+                date: None,
+                amount: None,
+            },
+            TaskOperation::Archive { task_index: 2 },
+        ]
+        .iter()
+        {
+            list.handle_operation(op).unwrap();
+            clock.advance(chrono::Duration::minutes(1));
+        }
+
+        list
+    }
+
+    /// Check that an export survived a round trip without losing anything
+    fn assert_same(export: &Export, imported: &Export) {
+        assert_eq!(
+            ron::ser::to_string(&export.tasks).unwrap(),
+            ron::ser::to_string(&imported.tasks).unwrap()
+        );
+        assert_eq!(export.pauses.len(), 1);
+        assert_eq!(export.pauses, imported.pauses);
+    }
+
+    #[test]
+    fn round_trip_ron() {
+        let export = Export::new(&listing(), None);
+        let imported = Export::from_ron(
+            Path::new("export.ron"),
+            &export.to_ron(),
+            &DayRules::default(),
+        )
+        .unwrap();
+        assert_same(&export, &imported);
+    }
+
+    #[test]
+    fn round_trip_json() {
+        let export = Export::new(&listing(), None);
+        let imported = Export::from_json(
+            Path::new("export.json"),
+            &export.to_json(),
+            &DayRules::default(),
+        )
+        .unwrap();
+        assert_same(&export, &imported);
+    }
+
+    #[test]
+    fn round_trip_csv() {
+        let export = Export::new(&listing(), None);
+        let imported = Export::from_csv(
+            Path::new("export.csv"),
+            &export.to_csv(),
+            &DayRules::default(),
+            now(),
+        )
+        .unwrap();
+        assert_same(&export, &imported);
+    }
+
+    #[test]
+    fn import_rejects_invalid() {
+        let json = Export::new(&listing(), None)
+            .to_json()
+            .replace("\"description\": \"read\"", "\"description\": \"\"");
+
+        assert!(Export::from_json(Path::new("export.json"), &json, &DayRules::default()).is_err());
+        assert!(Export::from_json(
+            Path::new("export.json"),
+            "{\"version\": 1,",
+            &DayRules::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn import_counts_days_as_rules_define_them() {
+        let created = Local.ymd(2019, 11, 30).and_hms(12, 0, 0);
+        let mut task = Task::new(
+            "read".to_string(),
+            created.with_timezone(&created.offset().fix()),
+        );
+        let rules = DayRules::default();
+        for now in [
+            Local.ymd(2019, 11, 30).and_hms(23, 30, 0),
+            Local.ymd(2019, 12, 1).and_hms(1, 0, 0),
+        ] {
+            let now = now.with_timezone(&now.offset().fix());
+            task.mark_complete(&None, None, None, &rules, now).unwrap();
+        }
+        let mut export = Export::new(&empty(), None);
+        export.tasks.push(task);
+        let json = export.to_json();
+
+        // Both completions count for the same day once days start at 04:00
+        let night_owl = DayRules {
+            day_start: NaiveTime::from_hms(4, 0, 0),
+            ..DayRules::default()
+        };
+        assert!(Export::from_json(Path::new("export.json"), &json, &rules).is_ok());
+        assert!(Export::from_json(Path::new("export.json"), &json, &night_owl).is_err());
+    }
+
+    #[test]
+    fn merge_is_idempotent() {
+        let mut list = listing();
+        let export = Export::new(&list, None);
+
+        let summary = import(&mut list, export.clone(), ImportMode::Merge);
+        assert!(summary.is_empty());
+        assert_eq!(summary.unchanged, 3);
+        assert_eq!(list.total_tasks(), 3);
+
+        // Tasks which don't exist yet are added, and repeats are left out
+        let mut other = empty();
+        let mut doubled = export.clone();
+        doubled.tasks.extend(export.tasks);
+        let summary = import(&mut other, doubled, ImportMode::Merge);
+        assert_eq!(summary.added.len(), 3);
+        assert_eq!(summary.duplicates, 3);
+        assert_eq!(other.total_tasks(), 3);
+    }
+
+    #[test]
+    fn tasks_created_together_stay_apart() {
+        let mut list = empty();
+        for description in ["read", "write"] {
+            list.handle_operation(&TaskOperation::Add {
+                description: description.to_string(),
+                kind: TaskKind::Do,
+            })
+            .unwrap();
+        }
+        let export = Export::new(&list, None);
+        let json = export.to_json();
+        let imported =
+            Export::from_json(Path::new("export.json"), &json, &DayRules::default()).unwrap();
+
+        let mut other = empty();
+        let summary = import(&mut other, imported.clone(), ImportMode::Merge);
+        assert_eq!(summary.added.len(), 2);
+        assert_eq!(summary.duplicates, 0);
+        assert_eq!(other.total_tasks(), 2);
+
+        let summary = import(&mut other, imported, ImportMode::Merge);
+        assert!(summary.is_empty());
+        assert_eq!(summary.unchanged, 2);
+    }
+
+    #[test]
+    fn merge_adds_missing_history() {
+        let mut list = listing();
+        let export = Export::new(&list, None);
+
+        list.handle_operation(&TaskOperation::Uncomplete {
+            task_index: 0,
+            date: list.today().naive_local(),
+        })
+        .unwrap();
+
+        let summary = import(&mut list, export, ImportMode::Merge);
+        assert_eq!(summary.merged.len(), 1);
+        assert_eq!(summary.merged[0].1.completions, 1);
+        assert!(list
+            .task_iter()
+            .next()
+            .unwrap()
+            .completed_today(list.today(), list.rules())
+            .is_some());
+    }
+
+    #[test]
+    fn replace_discards_existing() {
+        let mut list = listing();
+        let mut export = Export::new(&empty(), None);
+        export.tasks.push(Task::new("new".to_string(), now()));
+
+        let summary = import(&mut list, export, ImportMode::Replace);
+        assert_eq!(summary.removed, 3);
+        assert_eq!(summary.pauses_removed, 1);
+        assert_eq!(list.total_tasks(), 1);
+        assert!(list.pauses().is_empty());
+        assert!(list.journal().peek_undo().is_none());
+    }
+
+    #[test]
+    fn pauses_for_every_task_are_imported() {
+        let list = listing();
+        let export = Export::new(&list, None);
+
+        // Replacing keeps the days which were excused
+        let mut other = empty();
+        let summary = import(&mut other, export.clone(), ImportMode::Replace);
+        assert_eq!(summary.pauses_added, 1);
+        assert_eq!(other.pauses(), list.pauses());
+
+        // Merging only adds pauses which are missing
+        let mut other = empty();
+        assert_eq!(
+            import(&mut other, export.clone(), ImportMode::Merge).pauses_added,
+            1
+        );
+        let summary = import(&mut other, export, ImportMode::Merge);
+        assert_eq!(summary.pauses_added, 0);
+        assert!(summary.is_empty());
+        assert_eq!(other.pauses(), list.pauses());
+    }
+}
@@ -15,12 +15,23 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
+use std::path::Path;
+use std::sync::Arc;
+
 use chrono::prelude::*;
 use pancurses::*;
 
-use super::structs::TaskError;
-use super::structs::TaskListing;
-use super::structs::TaskOperation;
+use chain::structs::config::{Colour, Config};
+use chain::structs::target::parse_amount;
+use chain::Clock;
+use chain::DayRules;
+use chain::DayStatus;
+use chain::Pause;
+use chain::StreakStats;
+use chain::TaskError;
+use chain::TaskKind;
+use chain::TaskListing;
+use chain::TaskOperation;
 
 // TODO: in future, we can use std::panic::set_handler()
 macro_rules! tui_panic {
@@ -29,13 +40,38 @@ macro_rules! tui_panic {
     ($fmt:expr, $($arg:tt)*) => ({ endwin(); panic!($fmt, $($arg)*); });
 }
 
-pub fn new_loop(tasks: &mut TaskListing) {
+/// Returns true if `op` can fail with `e` through no fault of the TUI, in which case the listing is
+/// left as it is
+fn is_expected_error(op: &TaskOperation, e: &TaskError) -> bool {
+    match op {
+        TaskOperation::MarkComplete { .. } => matches!(
+            e,
+            TaskError::AlreadyCompleted
+                | TaskError::DateInFuture
+                | TaskError::DateBeforeCreation
+                | TaskError::NoTarget(_)
+                | TaskError::InvalidAmount(_)
+        ),
+        TaskOperation::Uncomplete { .. } => matches!(e, TaskError::NotCompleted),
+        // The pause may have been changed by another process
+        TaskOperation::AddPause { .. } | TaskOperation::RemovePause { .. } => {
+            matches!(e, TaskError::InvalidPause(_, _) | TaskError::NotFound)
+        }
+        // Besides there being nothing to undo or redo, the change may no longer apply (e.g. if
+        // its task was dropped by `chain db repair`), in which case it stays in the journal
+        TaskOperation::Undo | TaskOperation::Redo => true,
+        _ => false,
+    }
+}
+
+pub fn new_loop(tasks: &mut TaskListing, tasks_path: &Path, config: &Config) {
     // ncurses window
     let window = initscr();
 
     // store the window in our global Ui object
-    let mut ui: Ui = Ui::default();
-    ui.window = Some(window);
+    let ui: Ui = Ui {
+        window: Some(window),
+    };
     ui.window().keypad(true); //< makes it so that arrow/function keys are properly represented
 
     // Some settings for ncurses
@@ -45,20 +81,24 @@ pub fn new_loop(tasks: &mut TaskListing) {
     set_blink(true);
     curs_set(0);
 
+    // Colour pairs used for calendar cells
+    init_pair(COMPLETED_PAIR, colour(config.theme.completed), -1);
+    init_pair(MISSED_PAIR, colour(config.theme.missed), -1);
+    init_pair(PENDING_PAIR, colour(config.theme.pending), -1);
+    init_pair(NOT_DUE_PAIR, colour(config.theme.not_due), -1);
+
     // Keep track of states in the TUI
     let mut stack: Vec<Box<dyn UiState>> = Vec::new();
 
     // Push the initial state
-    stack.push(Box::new(ListingState::new(tasks)));
+    stack.push(Box::new(ListingState::new(tasks, config.tui.history_days)));
 
     // Keep track of any results that come from user actions
     let mut state_input_results: Vec<StateInputResult> = Vec::new();
 
     'uiloop: loop {
         // Handle any results generated by user actions until no more are queued
-        while state_input_results.len() > 0 {
-            let result = state_input_results.pop().unwrap();
-
+        while let Some(result) = state_input_results.pop() {
             match result {
                 StateInputResult::EnterState(name) => {
                     // We're being asked to enter another state
@@ -108,26 +148,24 @@ pub fn new_loop(tasks: &mut TaskListing) {
                 }
                 StateInputResult::TaskOperation(op) => {
                     // We're being asked to manipulate the global `TaskListing`
-                    match tasks.handle_and_store(&op) {
+                    match tasks.handle_and_store(&op, tasks_path) {
                         Ok(_) => (),
-                        Err(e) => match op {
-                            TaskOperation::MarkComplete { .. } => match e {
-                                TaskError::AlreadyCompleted { .. } => (),
-                                _ => tui_panic!(
-                                    "while attempting operation {:?}, got error {:?}",
-                                    op,
-                                    e
-                                ),
-                            },
-                            _ => {
-                                tui_panic!("while attempting operation {:?}, got error {:?}", op, e)
-                            }
-                        },
+                        // Another process changed the task list, which has been reloaded so the
+                        // user can see what happened
+                        Err(TaskError::Conflict(_)) => (),
+                        Err(e) if is_expected_error(&op, &e) => (),
+                        Err(e) => {
+                            tui_panic!("while attempting operation {:?}, got error {:?}", op, e)
+                        }
                     }
                 }
             }
         }
 
+        // Pick up any changes made by other processes (e.g. `chain done` in another terminal). If
+        // the file can't be read right now, keep showing what we have.
+        let _ = tasks.reload_if_changed(tasks_path);
+
         // Render all states
         for state in &stack {
             state.render(&ui, tasks);
@@ -192,6 +230,30 @@ pub fn new_loop(tasks: &mut TaskListing) {
     endwin();
 }
 
+/// Colour pair for days on which a task was completed
+const COMPLETED_PAIR: i16 = 1;
+/// Colour pair for days on which a task was missed
+const MISSED_PAIR: i16 = 2;
+/// Colour pair for today, if a task is due but hasn't been completed yet
+const PENDING_PAIR: i16 = 3;
+/// Colour pair for days on which a task wasn't due
+const NOT_DUE_PAIR: i16 = 4;
+
+/// Get the curses colour for a colour in the theme
+fn colour(colour: Colour) -> i16 {
+    match colour {
+        Colour::Default => -1,
+        Colour::Black => COLOR_BLACK,
+        Colour::Red => COLOR_RED,
+        Colour::Green => COLOR_GREEN,
+        Colour::Yellow => COLOR_YELLOW,
+        Colour::Blue => COLOR_BLUE,
+        Colour::Magenta => COLOR_MAGENTA,
+        Colour::Cyan => COLOR_CYAN,
+        Colour::White => COLOR_WHITE,
+    }
+}
+
 /// global UI state
 struct Ui {
     window: Option<Window>,
@@ -204,12 +266,6 @@ impl Ui {
     }
 }
 
-impl Default for Ui {
-    fn default() -> Self {
-        Ui { window: None }
-    }
-}
-
 /// What a state yields upon exiting
 enum StateYield<'a> {
     /// State exits, no value returned
@@ -229,9 +285,19 @@ enum YieldReason {
     GeneralRemark,
     /// user wanted to make a remark with task completion
     CompletionRemark,
+    /// user wanted to record how much of a task's target they did
+    CompletionAmount,
     /// user wanted to enter description for a new task
     #[allow(dead_code)]
     NewTask,
+    /// user wanted to enter description for a new task to avoid
+    NewAvoidTask,
+    /// user wanted to change the description of the selected task
+    EditDescription,
+    /// user wanted to excuse the selected task on the selected day
+    PauseReason,
+    /// user wanted to excuse every task on the selected day
+    PauseAllReason,
 }
 
 /// Used by StateInputResult::EnterState to indicate which state some other state wishes us to
@@ -272,31 +338,127 @@ trait UiState {
     /// Handle another state exiting and yielding some result
     fn handle_yield(&mut self, yielded: String, reason: YieldReason) -> Option<StateInputResult>;
     /// Exit from this state, potentially providing some yield
-    fn output_on_exit(&self) -> StateYield;
+    fn output_on_exit(&self) -> StateYield<'_>;
 }
 
 /// ListingState - initial state which displays available tasks and their completion statuses.
 struct ListingState {
-    /// an index into the global `TaskListing` representing the currently selected task
+    /// an index into the tasks displayed in the listing (which doesn't include archived tasks)
+    /// representing the currently selected row
     task_index: Option<usize>,
+    /// an index into the global `TaskListing` for the task on the currently selected row
+    selected_task: Option<usize>,
     /// every time `task_index` is updated, this becomes its previous value
     prev_index: Option<usize>,
     /// an index into the global `TaskListing` representing the lowest index displayed in the
     /// listing (on screen). i.e. increasing this scrolls down the list.
     scroll_pos: Option<usize>,
+    /// how many days before today the selected calendar cell is (i.e. 0 selects today)
+    day_offset: usize,
+    /// the minimum number of days shown in the calendar (descriptions are truncated to make room)
+    history_days: usize,
+    /// when each day starts, so that the calendar agrees with the rest of chain
+    rules: DayRules,
+    /// where the current time comes from
+    clock: Arc<dyn Clock>,
+}
+
+/// Width of the column showing the current streak
+const STREAK_WIDTH: usize = 5;
+/// Gap between the streak column and the calendar
+const CALENDAR_PAD: usize = 2;
+/// Each day in the calendar is this many columns wide
+const DAY_WIDTH: usize = 4;
+
+/// Work out how the listing is laid out horizontally, returning the width of the description
+/// column, the column the calendar starts at, and the number of days shown in the calendar.
+fn calendar_layout(
+    tasks: &TaskListing,
+    ui_cols: usize,
+    min_days_history: usize,
+) -> (usize, usize, usize) {
+    // Calculate description width based on some minimum days of history to be shown
+    let min_days_history_width: usize = DAY_WIDTH * min_days_history;
+    let max_description_width: usize = tasks.active_task_iter().fold(0, |max, (_, t)| {
+        let task_description_width = t.description().chars().count();
+        if task_description_width > max {
+            return task_description_width;
+        }
+        max
+    });
+
+    let description_width = if ui_cols
+        < (max_description_width + STREAK_WIDTH + CALENDAR_PAD + min_days_history_width)
+    {
+        ui_cols - (min_days_history_width + STREAK_WIDTH + CALENDAR_PAD)
+    } else {
+        max_description_width
+    };
+
+    // Calendar is drawn to the right of the description and streak columns
+    let cal_offset = description_width + STREAK_WIDTH + CALENDAR_PAD;
+    let cal_n_days = (ui_cols - cal_offset) / DAY_WIDTH;
+
+    (description_width, cal_offset, cal_n_days)
 }
 
 impl ListingState {
     /// Create a new `ListingState`. There should only ever be one of these, and it should always
     /// be at the bottom of the stack of UI states.
-    fn new(tasks: &TaskListing) -> Self {
-        let n_tasks = tasks.total_tasks();
+    fn new(tasks: &TaskListing, history_days: usize) -> Self {
+        let n_tasks = tasks.active_task_iter().count();
         ListingState {
             task_index: if n_tasks > 0 { Some(0) } else { None },
+            selected_task: tasks.active_task_iter().next().map(|(index, _task)| index),
             prev_index: if n_tasks > 0 { Some(0) } else { None },
             scroll_pos: if n_tasks > 0 { Some(0) } else { None },
+            day_offset: 0,
+            history_days,
+            rules: tasks.rules().clone(),
+            clock: tasks.clock(),
         }
     }
+
+    /// The day it is now
+    fn today(&self) -> Date<Local> {
+        self.clock.today(&self.rules)
+    }
+
+    /// The day of the selected calendar cell
+    fn selected_day(&self) -> Date<Local> {
+        self.today() - chrono::Duration::days(self.day_offset as i64)
+    }
+
+    /// The day of the selected calendar cell, or `None` if it's today
+    fn selected_date(&self) -> Option<NaiveDate> {
+        if self.day_offset == 0 {
+            None
+        } else {
+            Some(self.selected_day().naive_local())
+        }
+    }
+
+    /// Make sure the selected task still exists, e.g. after a task was removed or archived
+    fn clamp_selection(&mut self, tasks: &TaskListing) {
+        let n_tasks = tasks.active_task_iter().count();
+
+        if n_tasks == 0 {
+            self.task_index = None;
+            self.selected_task = None;
+            self.prev_index = None;
+            self.scroll_pos = None;
+            return;
+        }
+
+        let index = std::cmp::min(self.task_index.unwrap_or(0), n_tasks - 1);
+        self.task_index = Some(index);
+        self.selected_task = tasks
+            .active_task_iter()
+            .nth(index)
+            .map(|(index, _task)| index);
+        self.prev_index = Some(std::cmp::min(self.prev_index.unwrap_or(index), n_tasks - 1));
+        self.scroll_pos = Some(std::cmp::min(self.scroll_pos.unwrap_or(0), index));
+    }
 }
 
 impl UiState for ListingState {
@@ -311,42 +473,26 @@ impl UiState for ListingState {
             " ".repeat(ui.window().get_max_x() as usize),
         );
 
-        // Calculate description width based on some minimum days of history to be shown
-        let calendar_pad: usize = 2;
-        let min_days_history = 5;
-        let min_days_history_width: usize = 4 * min_days_history;
-        let max_description_width: usize = tasks.task_iter().fold(0, |max, t| {
-            let task_description_width = t.description().chars().count();
-            if task_description_width > max {
-                return task_description_width;
-            }
-            max
-        });
-
-        let description_width = if (w.get_max_x() as usize)
-            < (max_description_width + calendar_pad + min_days_history_width)
-        {
-            w.get_max_x() as usize - (min_days_history_width + calendar_pad)
-        } else {
-            max_description_width
-        };
+        let (description_width, cal_offset, cal_n_days) =
+            calendar_layout(tasks, w.get_max_x() as usize, self.history_days);
+        let cal_offset = cal_offset as i32;
+        let cal_n_days = cal_n_days as i32;
 
         // Header + calendar dates
         w.mvaddstr(2, 0, "Task");
         w.mvchgat(2, 0, description_width as i32, A_BOLD | A_UNDERLINE, 0);
+        w.mvaddstr(2, description_width as i32 + 1, "Run");
+        w.mvchgat(2, description_width as i32 + 1, 3, A_BOLD | A_UNDERLINE, 0);
 
-        let cal_width = w.get_max_x() - 0 - (description_width + calendar_pad) as i32;
-        let cal_n_days = cal_width / 4;
-
-        let mut today: Date<Local> = Utc::now().with_timezone(&Local).date();
+        let mut today: Date<Local> = self.today();
         for _n in 0..cal_n_days - 1 {
             today = today.pred();
         }
 
-        let start = today.clone();
+        let start = today;
 
         for n in 0..cal_n_days {
-            let col: i32 = description_width as i32 + calendar_pad as i32 + (4 * n);
+            let col: i32 = cal_offset + (4 * n);
 
             if n == 0 || today.day() == 1 {
                 w.mvaddstr(1, col - 1, " ");
@@ -361,12 +507,15 @@ impl UiState for ListingState {
         }
         today = today.pred();
 
+        let selected_day = self.selected_day();
         let mut active_task_completed = false;
+        let mut active_task_progress = None;
+        let mut active_task_kind = TaskKind::Do;
 
         // Task listing
-        if self.prev_index.is_some() {
+        if let Some(prev_index) = self.prev_index {
             w.mvchgat(
-                (3 + self.prev_index.unwrap() - self.scroll_pos.unwrap()) as i32,
+                (3 + prev_index - self.scroll_pos.unwrap()) as i32,
                 0,
                 w.get_max_x(),
                 A_NORMAL,
@@ -375,82 +524,118 @@ impl UiState for ListingState {
         }
 
         // Skip some number of elements based on scroll_pos
-        let task_iter = tasks.task_iter().skip(self.scroll_pos.unwrap_or(0));
+        let task_iter = tasks.active_task_iter().skip(self.scroll_pos.unwrap_or(0));
 
         let max_entries_visible = (w.get_max_y() - 5) as usize;
 
-        for (n, task) in task_iter.take(max_entries_visible).enumerate() {
+        for (n, (_, task)) in task_iter.take(max_entries_visible).enumerate() {
             let description = task.description();
             let mut description_fmt = description.clone();
 
             let n_task = n + self.scroll_pos.unwrap_or(0);
 
-            let active_task = n_task == self.task_index.unwrap();
+            let active_task = Some(n_task) == self.task_index;
 
             if active_task {
-                active_task_completed = active_task && task.completed_today().is_some();
+                // For tasks to avoid, a slip takes the place of a completion
+                active_task_completed = task.completed_on(selected_day, &self.rules)
+                    || task.slipped_on(selected_day, &self.rules);
+                active_task_progress = task.progress_on(selected_day, &self.rules);
+                active_task_kind = task.kind();
             }
 
-            if description.chars().count() > description_width as usize {
-                description_fmt.truncate(description_width as usize - 3);
+            if description.chars().count() > description_width {
+                description_fmt.truncate(description_width - 3);
                 description_fmt.push_str("...");
             }
 
-            w.mvaddstr((3 + n) as i32, 0, " ".repeat(description_width as usize));
+            w.mvaddstr((3 + n) as i32, 0, " ".repeat(description_width));
             w.mvaddstr((3 + n) as i32, 0, description_fmt);
+
+            // render current streak
+            let streak =
+                StreakStats::for_task(task, today, tasks.rules(), tasks.pauses()).current_length();
+            w.mvaddstr(
+                (3 + n) as i32,
+                description_width as i32 + 1,
+                format!("{:>3}", streak),
+            );
             if active_task {
                 w.mvchgat((3 + n) as i32, 0, w.get_max_x(), A_UNDERLINE, 0);
             }
 
             // render completion status
-            let mut day = start.clone();
+            let mut day = start;
             let mut day_n = 0;
             while day != today.succ() {
-                let col: i32 = description_width as i32 + calendar_pad as i32 + (4 * day_n);
-                let style = if active_task { A_UNDERLINE } else { 0 };
-                let is_today = day == today;
-                if task.completed_on(day) {
-                    init_pair(1, COLOR_GREEN, -1);
-                    if is_today {
-                        w.mvaddstr((3 + n) as i32, col, "o");
-                    } else {
-                        w.mvaddstr((3 + n) as i32, col, "o---");
-                    }
-                    w.mvchgat((3 + n) as i32, col, 4, style, 1);
+                let col: i32 = cal_offset + (4 * day_n);
+                let style = if active_task && day == selected_day {
+                    A_REVERSE
+                } else if active_task {
+                    A_UNDERLINE
                 } else {
-                    let task_existed = task.existed_on(day);
+                    0
+                };
 
-                    init_pair(2, COLOR_RED, -1);
-                    init_pair(3, COLOR_YELLOW, -1);
-
-                    let mut color_pair = 0;
-                    if is_today {
+                let (cell, color_pair): (String, i16) =
+                    match DayStatus::for_task(task, day, today, tasks.rules(), tasks.pauses()) {
+                        DayStatus::Completed if day == today => ("o".into(), COMPLETED_PAIR),
+                        DayStatus::Completed => ("o---".into(), COMPLETED_PAIR),
+                        // Show how much of the target was met (never 100%, as it wasn't met)
+                        DayStatus::Partial => {
+                            let fraction = task
+                                .progress_on(day, tasks.rules())
+                                .map_or(0.0, |progress| progress.fraction());
+                            let percent = ((fraction * 100.0) as u32).min(99);
+                            (format!("{:<4}", format!("{}%", percent)), PENDING_PAIR)
+                        }
+                        // Task isn't due on this day, so skipping it doesn't break the chain
+                        DayStatus::NotDue => (".   ".into(), NOT_DUE_PAIR),
+                        // Task was excused (e.g. a vacation), so it doesn't break the chain
+                        DayStatus::Excused => ("-   ".into(), NOT_DUE_PAIR),
                         // We don't know if the task will be completed today
-                        w.mvaddstr((3 + n) as i32, col, "?   ");
-                        color_pair = 3;
-                    } else if task_existed {
-                        // Not today, task wasn't completed (and it did exist at this point)
-                        w.mvaddstr((3 + n) as i32, col, "x   ");
-                        color_pair = 2;
-                    } else {
+                        DayStatus::Pending => ("?   ".into(), PENDING_PAIR),
+                        // Task wasn't completed (and it did exist at this point)
+                        DayStatus::Missed => ("x   ".into(), MISSED_PAIR),
+                        // Something which was meant to be avoided happened
+                        DayStatus::Slipped => ("!   ".into(), MISSED_PAIR),
                         // Task didn't exist, so it isn't fair to mark it as failed completion
-                        w.mvaddstr((3 + n) as i32, col, "    ");
-                    }
-                    w.mvchgat((3 + n) as i32, col, 4, style, color_pair);
-                }
+                        DayStatus::NotCreated | DayStatus::Future => ("    ".into(), 0),
+                    };
+                w.mvaddstr((3 + n) as i32, col, cell);
+                w.mvchgat((3 + n) as i32, col, 4, style, color_pair);
                 day_n += 1;
                 day = day.succ();
             }
         }
 
         // Keyboard hints based on currently highlighted task
-        let mut hint_string: Vec<String> = Vec::new();
-        hint_string.push("[n] new task".into());
-        hint_string.push("[r] add remark".into());
-        if !active_task_completed {
+        let mut hint_string: Vec<String> = vec![
+            "[n] new task".into(),
+            "[N] new task to avoid".into(),
+            "[r] add remark".into(),
+            "[e] edit".into(),
+            "[u] undo".into(),
+            "[p/P] pause task/all".into(),
+            "[left/right] select day".into(),
+        ];
+        if active_task_kind == TaskKind::Avoid {
+            if active_task_completed {
+                hint_string.push("[space] remove slip".into());
+            } else {
+                hint_string.push("[space] log slip".into());
+                hint_string.push("[enter] log slip with remark".into());
+            }
+        } else if active_task_completed {
+            hint_string.push("[space] uncomplete".into());
+        } else {
             hint_string.push("[space] complete".into());
             hint_string.push("[enter] complete with remark".into());
         }
+        if let Some(progress) = active_task_progress {
+            hint_string.push("[a] record amount".into());
+            hint_string.insert(0, progress.to_string());
+        }
         ui.window().mvaddstr(
             ui.window().get_max_y() - 2,
             0,
@@ -464,8 +649,15 @@ impl UiState for ListingState {
         input: pancurses::Input,
         tasks: &TaskListing,
         ui_rows: usize,
-        _ui_cols: usize,
+        ui_cols: usize,
     ) -> Option<StateInputResult> {
+        // Operations (e.g. undo) may have added or removed tasks since the last input
+        self.clamp_selection(tasks);
+
+        // The window may have been resized so that the selected day is no longer visible
+        let (_, _, cal_n_days) = calendar_layout(tasks, ui_cols, self.history_days);
+        self.day_offset = std::cmp::min(self.day_offset, cal_n_days.saturating_sub(1));
+
         match input {
             Input::KeyUp => {
                 // decrement `task_index`
@@ -479,34 +671,61 @@ impl UiState for ListingState {
             Input::KeyDown => {
                 // increment `task_index`
                 if let Some(index) = self.task_index {
-                    if index + 1 < tasks.total_tasks() {
+                    if index + 1 < tasks.active_task_iter().count() {
                         self.prev_index = Some(index);
                         self.task_index = Some(index + 1);
                     }
                 }
             }
+            Input::KeyLeft if self.day_offset + 1 < cal_n_days => {
+                // select the previous day
+                self.day_offset += 1;
+            }
+            Input::KeyRight => {
+                // select the next day
+                self.day_offset = self.day_offset.saturating_sub(1);
+            }
             Input::Character(c) => match c {
                 ' ' => {
-                    // space - mark complete without remark
-                    return Some(StateInputResult::TaskOperation(
-                        TaskOperation::MarkComplete {
-                            task_index: self.task_index.unwrap(),
-                            remark: None,
-                        },
-                    ));
+                    // space - toggle completion (without remark) on the selected day
+                    if let Some(index) = self.selected_task {
+                        // For tasks to avoid, this toggles a slip instead
+                        let completed = tasks.task_iter().nth(index).is_some_and(|task| {
+                            task.completed_on(self.selected_day(), &self.rules)
+                                || task.slipped_on(self.selected_day(), &self.rules)
+                        });
+
+                        let op = if completed {
+                            TaskOperation::Uncomplete {
+                                task_index: index,
+                                date: self.selected_day().naive_local(),
+                            }
+                        } else {
+                            TaskOperation::MarkComplete {
+                                task_index: index,
+                                remark: None,
+                                date: self.selected_date(),
+                                amount: None,
+                            }
+                        };
+
+                        return Some(StateInputResult::TaskOperation(op));
+                    }
                 }
                 '\n' => {
                     // TODO: move to Input::KeyEnter once pancurses, or the event loop supports the
                     // conversion
                     // ---
                     // enter - mark complete with remark
-                    let selected_task = if let Some(index) = self.task_index {
+                    let selected_task = if let Some(index) = self.selected_task {
                         tasks.task_iter().nth(index)
                     } else {
                         None
                     };
                     if let Some(task) = selected_task {
-                        if task.completed_today().is_none() {
+                        if !task.completed_on(self.selected_day(), &self.rules)
+                            && !task.slipped_on(self.selected_day(), &self.rules)
+                        {
                             return Some(StateInputResult::EnterState(StateName::TextEntry {
                                 prompt: "remark: ".into(),
                                 reason: YieldReason::CompletionRemark,
@@ -514,7 +733,26 @@ impl UiState for ListingState {
                         }
                     }
                 }
-                'r' => {
+                'a' => {
+                    // a - record some of the target (for tasks which have one)
+                    let target = self
+                        .selected_task
+                        .and_then(|index| tasks.task_iter().nth(index))
+                        .and_then(|task| task.details_on(self.selected_day(), &self.rules))
+                        .and_then(|details| details.target().cloned());
+                    if let Some(target) = target {
+                        let prompt = if target.unit().is_empty() {
+                            "amount: ".to_string()
+                        } else {
+                            format!("amount ({}): ", target.unit())
+                        };
+                        return Some(StateInputResult::EnterState(StateName::TextEntry {
+                            prompt,
+                            reason: YieldReason::CompletionAmount,
+                        }));
+                    }
+                }
+                'r' if self.task_index.is_some() => {
                     // r - remark regardless of completion
                     return Some(StateInputResult::EnterState(StateName::TextEntry {
                         prompt: "remark: ".into(),
@@ -531,18 +769,86 @@ impl UiState for ListingState {
                         reason: YieldReason::NewTask,
                     }));
                 }
+                'N' => {
+                    // N - create a new task to avoid
+                    return Some(StateInputResult::EnterState(StateName::TextEntry {
+                        prompt: "new task to avoid: ".into(),
+                        reason: YieldReason::NewAvoidTask,
+                    }));
+                }
+                'e' if self.task_index.is_some() => {
+                    // e - edit the description of the selected task
+                    return Some(StateInputResult::EnterState(StateName::TextEntry {
+                        prompt: "new description: ".into(),
+                        reason: YieldReason::EditDescription,
+                    }));
+                }
+                'p' => {
+                    // p - excuse the selected task on the selected day (or stop excusing it)
+                    let pause = self
+                        .selected_task
+                        .and_then(|index| tasks.task_iter().nth(index))
+                        .and_then(|task| task.pause_on(self.selected_day(), &[]));
+                    match (self.selected_task, pause) {
+                        (Some(index), Some(pause)) => {
+                            return Some(StateInputResult::TaskOperation(
+                                TaskOperation::RemovePause {
+                                    task_index: Some(index),
+                                    pause: pause.clone(),
+                                },
+                            ));
+                        }
+                        (Some(_), None) => {
+                            return Some(StateInputResult::EnterState(StateName::TextEntry {
+                                prompt: "pause reason: ".into(),
+                                reason: YieldReason::PauseReason,
+                            }));
+                        }
+                        _ => (),
+                    }
+                }
+                'P' => {
+                    // P - excuse every task on the selected day (or stop excusing them)
+                    let day = self.selected_day();
+                    match tasks.pauses().iter().find(|pause| pause.covers(day)) {
+                        Some(pause) => {
+                            return Some(StateInputResult::TaskOperation(
+                                TaskOperation::RemovePause {
+                                    task_index: None,
+                                    pause: pause.clone(),
+                                },
+                            ));
+                        }
+                        None => {
+                            return Some(StateInputResult::EnterState(StateName::TextEntry {
+                                prompt: "pause reason (every task): ".into(),
+                                reason: YieldReason::PauseAllReason,
+                            }));
+                        }
+                    }
+                }
+                'u' => {
+                    // u - undo the last change
+                    return Some(StateInputResult::TaskOperation(TaskOperation::Undo));
+                }
+                '\x12' => {
+                    // ctrl-r - redo the last change that was undone
+                    return Some(StateInputResult::TaskOperation(TaskOperation::Redo));
+                }
                 _ => (),
             },
             _ => (),
         }
 
+        // Nothing to scroll if there are no tasks
+        self.task_index?;
+
         // handle scrolling
         // 3 lines taken up at top, 2 at bottom
-        // TODO: handling when no tasks are in the list
-        let max_tasks_visible: usize = (ui_rows - (3 + 2)) as usize;
-        if self.task_index < self.scroll_pos {
-            self.scroll_pos = self.task_index;
-        } else if self.task_index.unwrap() > self.scroll_pos.unwrap() + max_tasks_visible {
+        let max_tasks_visible: usize = ui_rows - (3 + 2);
+        if self.task_index < self.scroll_pos
+            || self.task_index.unwrap() > self.scroll_pos.unwrap() + max_tasks_visible
+        {
             self.scroll_pos = self.task_index;
         } else if self.task_index.unwrap() - self.scroll_pos.unwrap() >= max_tasks_visible {
             self.scroll_pos = Some(self.task_index.unwrap() - max_tasks_visible + 1);
@@ -554,22 +860,56 @@ impl UiState for ListingState {
         match reason {
             YieldReason::CompletionRemark => Some(StateInputResult::TaskOperation(
                 TaskOperation::MarkComplete {
-                    task_index: self.task_index.unwrap(),
+                    task_index: self.selected_task.unwrap(),
                     remark: Some(yielded),
+                    date: self.selected_date(),
+                    amount: None,
                 },
             )),
+            // Anything which isn't an amount is ignored
+            YieldReason::CompletionAmount => parse_amount(&yielded).ok().map(|amount| {
+                StateInputResult::TaskOperation(TaskOperation::MarkComplete {
+                    task_index: self.selected_task.unwrap(),
+                    remark: None,
+                    date: self.selected_date(),
+                    amount: Some(amount),
+                })
+            }),
             YieldReason::GeneralRemark => {
                 Some(StateInputResult::TaskOperation(TaskOperation::AddRemark {
-                    task_index: self.task_index.unwrap(),
+                    task_index: self.selected_task.unwrap(),
                     remark: yielded,
                 }))
             }
             YieldReason::NewTask => Some(StateInputResult::TaskOperation(TaskOperation::Add {
                 description: yielded,
+                kind: TaskKind::Do,
             })),
+            YieldReason::NewAvoidTask => {
+                Some(StateInputResult::TaskOperation(TaskOperation::Add {
+                    description: yielded,
+                    kind: TaskKind::Avoid,
+                }))
+            }
+            YieldReason::EditDescription => {
+                Some(StateInputResult::TaskOperation(TaskOperation::Revise {
+                    task_index: self.selected_task.unwrap(),
+                    description: yielded,
+                }))
+            }
+            YieldReason::PauseReason | YieldReason::PauseAllReason => {
+                let day = self.selected_day().naive_local();
+                let task_index = match reason {
+                    YieldReason::PauseReason => Some(self.selected_task.unwrap()),
+                    _ => None,
+                };
+                Pause::new(day, day, Some(yielded)).ok().map(|pause| {
+                    StateInputResult::TaskOperation(TaskOperation::AddPause { task_index, pause })
+                })
+            }
         }
     }
-    fn output_on_exit(&self) -> StateYield {
+    fn output_on_exit(&self) -> StateYield<'_> {
         // If the user exits the ListingState, they're done using the program
         StateYield::QuitProgram
     }
@@ -601,8 +941,8 @@ impl UiState for TextEntryState {
         _ui_rows: usize,
         _ui_cols: usize,
     ) -> Option<StateInputResult> {
-        match input {
-            Input::Character(c) => match c {
+        if let Input::Character(c) = input {
+            match c {
                 '\n' => {
                     // TODO: move to Input::KeyEnter once pancurses, or the event loop supports the
                     // conversion
@@ -630,8 +970,7 @@ impl UiState for TextEntryState {
                 _ => {
                     self.buff.push(c);
                 }
-            },
-            _ => (),
+            }
         }
 
         None
@@ -640,9 +979,9 @@ impl UiState for TextEntryState {
         // `TextEntryState` will never handle something yielded by another state
         None
     }
-    fn output_on_exit(&self) -> StateYield {
+    fn output_on_exit(&self) -> StateYield<'_> {
         // We only yeild a value if the buffer has anything in it
-        if self.buff.len() == 0 {
+        if self.buff.is_empty() {
             StateYield::Exit
         } else {
             StateYield::ExitWithValue(Some(&self.buff), self.reason)
@@ -659,3 +998,60 @@ impl TextEntryState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::is_expected_error;
+    use chain::{TaskError, TaskListing, TaskOperation};
+    use std::path::Path;
+
+    #[test]
+    fn undo_after_task_was_removed() {
+        // The journal still holds the archiving of a second task, which has since been dropped
+        let contents = r#"TaskListing(
+            version: 2,
+            all_tasks: [
+                Task(
+                    id: "ah45q4",
+                    detail_history: [
+                        TaskDetails(
+                            revised: "2019-12-01T12:00:00Z",
+                            revision_id: 0,
+                            description: "read",
+                            sync_time: None,
+                        ),
+                    ],
+                    completions: [],
+                ),
+            ],
+            journal: Journal(
+                undo: [
+                    JournalEntry(
+                        applied: "2019-12-01T12:05:00Z",
+                        operation: Archive(task_index: 1),
+                        inverse: SetArchived(task_index: 1, archived: None),
+                        reapply: Archive(task_index: 1),
+                    ),
+                ],
+                redo: [],
+            ),
+        )"#;
+        let mut list = TaskListing::parse(Path::new("taskdata.ron"), contents).unwrap();
+
+        let undo = TaskOperation::Undo;
+        let e = list.handle_operation(&undo).unwrap_err();
+        assert_eq!(e, TaskError::NotFound);
+        assert!(is_expected_error(&undo, &e));
+        assert_eq!(list.total_tasks(), 1);
+    }
+
+    #[test]
+    fn unexpected_errors_are_told_apart() {
+        let archive = TaskOperation::Archive { task_index: 0 };
+        assert!(!is_expected_error(&archive, &TaskError::NotFound));
+        assert!(is_expected_error(
+            &TaskOperation::Redo,
+            &TaskError::NothingToRedo
+        ));
+    }
+}